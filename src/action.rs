@@ -0,0 +1,73 @@
+// `--on-packet`: a user command run against the inspected packet with `!`, e.g.
+//
+//     --on-packet 'logger -t vshark {src}:{sport} {dst}:{dport} {hex}'
+//
+// Placeholders are {src} {dst} {sport} {dport} {port} (the lower, service side) {proto} {ts} and
+// {hex}. Each substitution is single-quoted for the shell, so packet contents can't inject commands.
+use crate::decode;
+use crate::network::PacketUpdate;
+use std::io;
+use std::net::Ipv4Addr;
+use std::process::{Command, Stdio};
+
+// POSIX single-quoting: the only character needing care inside '…' is the quote itself
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+// The template with every known placeholder replaced; unknown `{…}` are left for the shell
+pub fn command_line(template: &str, pkt: &PacketUpdate) -> String {
+    let ip = &pkt.raw_data;
+    let (src, dst, proto) = match ip.get(..20) {
+        Some(h) => (Ipv4Addr::new(h[12], h[13], h[14], h[15]).to_string(), Ipv4Addr::new(h[16], h[17], h[18], h[19]).to_string(), h[9].to_string()),
+        None => (String::new(), String::new(), String::new()),
+    };
+    let (sport, dport) = decode::ports(ip).map_or((String::new(), String::new()), |(s, d)| (s.to_string(), d.to_string()));
+    let port = decode::ports(ip).map_or(String::new(), |(s, d)| s.min(d).to_string());
+    let hex: String = ip.iter().map(|b| format!("{:02x}", b)).collect();
+    let ts = format!("{}.{:06}", pkt.ts.as_secs(), pkt.ts.subsec_micros());
+    let fields = [("src", src), ("dst", dst), ("sport", sport), ("dport", dport), ("port", port), ("proto", proto), ("ts", ts), ("hex", hex)];
+    let mut line = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        line.push_str(&rest[..open]);
+        rest = &rest[open..];
+        let field = rest.find('}').and_then(|close| fields.iter().find(|(name, _)| *name == &rest[1..close]).map(|(_, v)| (close, v)));
+        match field {
+            Some((close, value)) => { line.push_str(&shell_quote(value)); rest = &rest[close + 1..]; }
+            None => { line.push('{'); rest = &rest[1..]; }
+        }
+    }
+    line.push_str(rest);
+    line
+}
+
+// Detached from the TUI: no terminal I/O, and not waited on, so a slow command can't stall the frame.
+// The child is reaped by a throwaway thread.
+pub fn spawn(command_line: &str) -> io::Result<()> {
+    let mut child = Command::new("sh").arg("-c").arg(command_line).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, Tcp};
+
+    #[test]
+    fn substitutions_are_quoted_for_the_shell() {
+        let mut pkt = testutil::update(Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 443).packet(b""), 7);
+        pkt.raw_data.truncate(24);
+        assert_eq!(command_line("logger {src}:{sport} > {dst}:{port} {proto} {ts} {nope} {hex", &pkt),
+            "logger '10.0.0.1':'40000' > '10.0.0.2':'443' '6' '7.000000' {nope} {hex");
+        assert_eq!(command_line("echo {hex}", &pkt), format!("echo '{}'", pkt.raw_data.iter().map(|b| format!("{:02x}", b)).collect::<String>()));
+
+        // Whatever the value, the shell sees exactly one word with it verbatim
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        for value in ["it's", "$(touch /tmp/pwned); `id`", "a\nb", "'", "", "\\'\"*"] {
+            let out = Command::new("sh").arg("-c").arg(format!("printf %s {}", shell_quote(value))).output().unwrap();
+            assert_eq!(String::from_utf8(out.stdout).unwrap(), value);
+        }
+    }
+}
@@ -0,0 +1,502 @@
+// User configuration, read from `$XDG_CONFIG_HOME/vshark/config` (or `~/.config/vshark/config`).
+// The format is one `key = value` per line; lines starting with `#` are comments:
+//
+//     color.HTTPS = magenta
+//     color.ICMP = #ff8800
+//     color.default = gray
+//     fps = 30
+//     scan.ports = 20
+//     scan.window = 5
+//     rst.count = 50
+//     rst.window = 5
+//     zerowin.stall = 2
+//     min_packets = 2
+//     hex.width = 16
+//     window = 300
+//     newhost = public
+//     preset.web = tcp and (port 80 or port 443)
+//     tag.DB = host 10.0.0.9
+use crate::detect::{NewHostDetector, NewHostScope, RstDetector, ScanDetector, ZeroWindowDetector};
+use crate::network::LinkOptions;
+use crate::ui::HEX_WIDTHS;
+use ratatui::style::Color;
+use std::{collections::HashMap, env, fs, io::{self, Write}, path::{Path, PathBuf}, str::FromStr, time::Duration};
+
+pub struct Theme {
+    pub protocol_colors: HashMap<String, Color>,
+    pub default_color: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let protocol_colors = [("HTTPS", Color::Magenta), ("DNS", Color::Blue), ("SSH", Color::Green), ("HTTP", Color::Yellow), ("WS", Color::LightYellow), ("NTP", Color::LightBlue), ("QUIC", Color::LightMagenta), ("IGMP", Color::Cyan), ("MQTT", Color::LightGreen), ("ESP", Color::LightRed), ("AH", Color::LightRed)]
+            .into_iter()
+            .map(|(proto, color)| (proto.to_string(), color))
+            .collect();
+        Theme { protocol_colors, default_color: Color::Gray }
+    }
+}
+
+impl Theme {
+    // Resolves a feed tag like "DNS" or "MQTT PUBLISH sensors/temp" by its leading protocol word
+    pub fn protocol_color(&self, tag: Option<&str>) -> Color {
+        tag.and_then(|t| t.split_whitespace().next())
+            .and_then(|proto| self.protocol_colors.get(&proto.to_uppercase()))
+            .copied()
+            .unwrap_or(self.default_color)
+    }
+}
+
+// A `--start`/`--end` bound: a Unix timestamp, or `+secs` relative to the first record
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeBound {
+    Absolute(Duration),
+    Relative(Duration),
+}
+
+impl FromStr for TimeBound {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (relative, secs) = match s.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let secs = secs.parse::<f64>().ok()
+            .filter(|v| v.is_finite() && *v >= 0.0)
+            .ok_or(format!("invalid timestamp: {}", s))?;
+        let d = Duration::from_secs_f64(secs);
+        Ok(if relative { TimeBound::Relative(d) } else { TimeBound::Absolute(d) })
+    }
+}
+
+impl TimeBound {
+    pub fn resolve(self, capture_start: Duration) -> Duration {
+        match self {
+            TimeBound::Absolute(t) => t,
+            TimeBound::Relative(offset) => capture_start + offset,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimeRange {
+    pub start: Option<TimeBound>,
+    pub end: Option<TimeBound>,
+}
+
+impl TimeRange {
+    // Both ends are inclusive; `capture_start` is the timestamp of the first record read
+    pub fn contains(&self, ts: Duration, capture_start: Duration) -> bool {
+        self.start.is_none_or(|b| ts >= b.resolve(capture_start))
+            && self.end.is_none_or(|b| ts <= b.resolve(capture_start))
+    }
+}
+
+#[derive(Default)]
+pub struct Config {
+    pub theme: Theme,
+    pub snaplen: Option<u32>,
+    pub read: Vec<PathBuf>, // --read is repeatable; several files are merged by timestamp
+    pub replay: bool,       // Play --read captures back at their recorded pace instead of loading instantly
+    pub time_range: TimeRange,
+    pub idle_timeout: Option<Duration>,
+    pub max_memory: Option<usize>,
+    pub fps: Option<u32>,
+    pub scan_ports: Option<usize>,     // Distinct ports one source may SYN...
+    pub scan_window: Option<Duration>, // ...within this long before it's flagged as a scan
+    pub rst_count: Option<usize>,      // RSTs one host may send...
+    pub rst_window: Option<Duration>,  // ...within this long before it's flagged as an RST storm
+    pub zero_window_stall: Option<Duration>, // How long a zero window may persist before it's flagged
+    pub new_hosts: NewHostScope,       // Which never-seen sources get a "new host" event
+    pub presets: Vec<(String, String)>, // Named display filters from `preset.<name>` lines, in file order
+    pub tag_rules: Vec<(String, String)>, // `tag.<LABEL>` lines: packets matching the filter get the label
+    pub min_packets: Option<u64>,      // Threshold for hiding tiny flows with `h`
+    pub hex_width: Option<usize>,      // Bytes per hex-dump line: 8, 16 or 32
+    pub window: Option<Duration>,      // Span of the rolling "last N" view toggled with `L`
+    pub subnet_prefix: Option<u8>,     // Prefix length conversations are grouped by with `g`
+    pub debug_log: Option<PathBuf>,    // Set by --debug
+    pub interfaces: Vec<String>,       // --interface is repeatable, one dumpcap each; "any" when empty
+    pub monitor: bool,                 // 802.11 monitor mode (dumpcap -I)
+    pub capture_filter: Option<String>, // BPF filter handed to dumpcap -f
+    pub no_mouse: bool,                // Leave the mouse to the terminal so native text selection works
+    pub export_csv: Option<PathBuf>,   // Flow table written here on exit
+    pub on_packet: Option<String>,     // Shell command template run on the inspected packet with `!`
+    pub link_offset: Option<usize>,    // Bytes before the IP header, overriding link-type detection
+    pub has_fcs: bool,                 // Frames carry a trailing Ethernet FCS to strip before parsing
+    pub duration: Option<Duration>,    // Wall-clock time to capture for before exiting on our own
+    pub headless: bool,                // No TUI: capture, print the accounting summary and exit
+    pub self_test: bool,               // Check the capture binary, permissions and terminal, then exit
+    pub ignored_lines: Vec<String>,    // Config-file lines `parse` skipped, e.g. "line 3: fps = fast"
+}
+
+// Soft cap on retained packet memory when --max-memory isn't given
+pub const DEFAULT_MAX_MEMORY: usize = 256 << 20;
+pub const DEFAULT_FPS: u32 = 30;
+pub const DEFAULT_SCAN_PORTS: usize = 20;
+pub const DEFAULT_SCAN_WINDOW: Duration = Duration::from_secs(5);
+pub const DEFAULT_RST_COUNT: usize = 50;
+pub const DEFAULT_RST_WINDOW: Duration = Duration::from_secs(5);
+pub const DEFAULT_ZERO_WINDOW_STALL: Duration = Duration::from_secs(2);
+pub const DEFAULT_MIN_PACKETS: u64 = 2;
+pub const DEFAULT_HEX_WIDTH: usize = 16;
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(300);
+pub const DEFAULT_SUBNET_PREFIX: u8 = 24;
+// Offered in the `F` menu unless the config redefines the name
+pub const DEFAULT_PRESETS: [(&str, &str); 4] = [
+    ("web", "tcp and (port 80 or port 443)"),
+    ("dns", "udp port 53"),
+    ("ssh", "tcp port 22"),
+    ("icmp", "icmp"),
+];
+pub const DEBUG_LOG: &str = "vshark-debug.log";
+pub const NO_MOUSE_ENV: &str = "VSHARK_NO_MOUSE";
+
+impl Config {
+    pub fn memory_limit(&self) -> usize {
+        self.max_memory.unwrap_or(DEFAULT_MAX_MEMORY)
+    }
+
+    pub fn frame_rate(&self) -> u32 {
+        self.fps.unwrap_or(DEFAULT_FPS)
+    }
+
+    pub fn min_packets(&self) -> u64 {
+        self.min_packets.unwrap_or(DEFAULT_MIN_PACKETS)
+    }
+
+    pub fn link_options(&self) -> LinkOptions {
+        LinkOptions { offset: self.link_offset, fcs: self.has_fcs }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window.unwrap_or(DEFAULT_WINDOW)
+    }
+
+    pub fn hex_width(&self) -> usize {
+        self.hex_width.unwrap_or(DEFAULT_HEX_WIDTH)
+    }
+
+    pub fn subnet_prefix(&self) -> u8 {
+        self.subnet_prefix.unwrap_or(DEFAULT_SUBNET_PREFIX)
+    }
+
+    pub fn scan_detector(&self) -> ScanDetector {
+        ScanDetector::new(self.scan_ports.unwrap_or(DEFAULT_SCAN_PORTS), self.scan_window.unwrap_or(DEFAULT_SCAN_WINDOW))
+    }
+
+    pub fn mouse_capture(&self) -> bool {
+        !self.no_mouse
+    }
+
+    pub fn rst_detector(&self) -> RstDetector {
+        RstDetector::new(self.rst_count.unwrap_or(DEFAULT_RST_COUNT), self.rst_window.unwrap_or(DEFAULT_RST_WINDOW))
+    }
+
+    pub fn zero_window_detector(&self) -> ZeroWindowDetector {
+        ZeroWindowDetector::new(self.zero_window_stall.unwrap_or(DEFAULT_ZERO_WINDOW_STALL))
+    }
+
+    // Built-in presets first, then the user's; a user preset with a built-in's name replaces it in place
+    pub fn presets(&self) -> Vec<(String, String)> {
+        let mut presets: Vec<(String, String)> = DEFAULT_PRESETS.iter().map(|(n, f)| (n.to_string(), f.to_string())).collect();
+        for (name, filter) in &self.presets {
+            match presets.iter_mut().find(|(n, _)| n == name) {
+                Some(preset) => preset.1.clone_from(filter),
+                None => presets.push((name.clone(), filter.clone())),
+            }
+        }
+        presets
+    }
+
+    // Appends a `preset.<name>` line to the config file, so it's there next run, and adds it for this one
+    pub fn save_preset(&mut self, name: &str, filter: &str) -> io::Result<()> {
+        let path = config_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no HOME or XDG_CONFIG_HOME"))?;
+        self.save_preset_to(&path, name, filter)
+    }
+
+    fn save_preset_to(&mut self, path: &Path, name: &str, filter: &str) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // A hand-edited file may not end in a newline; the preset must still start its own line
+        let unterminated = fs::read(path).is_ok_and(|text| !text.is_empty() && !text.ends_with(b"\n"));
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}{}", if unterminated { "\n" } else { "" }, preset_line(name, filter))?;
+        match self.presets.iter_mut().find(|(n, _)| n == name) {
+            Some(preset) => preset.1 = filter.to_string(),
+            None => self.presets.push((name.to_string(), filter.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn new_host_detector(&self) -> NewHostDetector {
+        NewHostDetector::new(self.new_hosts)
+    }
+
+    // The live capture as edited in-app: comma-separated interfaces, then an optional BPF filter
+    pub fn capture_spec(&self) -> String {
+        let interface = if self.interfaces.is_empty() { "any".to_string() } else { self.interfaces.join(",") };
+        match &self.capture_filter {
+            Some(filter) => format!("{} {}", interface, filter),
+            None => interface.to_string(),
+        }
+    }
+
+    pub fn set_capture_spec(&mut self, spec: &str) {
+        let spec = spec.trim();
+        let (interface, filter) = spec.split_once(' ').unwrap_or((spec, ""));
+        self.interfaces = interface.split(',').filter(|i| !i.is_empty() && *i != "any").map(str::to_string).collect();
+        self.capture_filter = Some(filter.trim()).filter(|f| !f.is_empty()).map(str::to_string);
+    }
+
+    // Config file first, then command-line flags on top of it
+    pub fn load() -> Result<Config, String> {
+        let mut config = config_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| Config::parse(&text))
+            .unwrap_or_default();
+        // Any non-empty value other than "0" counts, e.g. VSHARK_NO_MOUSE=1
+        config.no_mouse = env::var_os(NO_MOUSE_ENV).is_some_and(|v| !v.is_empty() && v != "0");
+        config.apply_args(env::args().skip(1))?;
+        if config.monitor && config.interfaces.is_empty() {
+            return Err("--monitor requires --interface".to_string());
+        }
+        if config.replay && config.read.is_empty() {
+            return Err("--replay requires --read".to_string());
+        }
+        // A live headless capture would otherwise never end
+        if config.headless && config.read.is_empty() && config.duration.is_none() {
+            return Err("--headless requires --duration or --read".to_string());
+        }
+        Ok(config)
+    }
+
+    pub fn apply_args(&mut self, args: impl IntoIterator<Item = String>) -> Result<(), String> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |flag: &str| args.next().ok_or(format!("{} requires a value", flag));
+            match arg.as_str() {
+                "--snaplen" => {
+                    let v = value("--snaplen")?;
+                    self.snaplen = Some(v.parse().map_err(|_| format!("invalid --snaplen: {}", v))?);
+                }
+                "--read" => self.read.push(PathBuf::from(value("--read")?)),
+                "--replay" => self.replay = true,
+                "--start" => self.time_range.start = Some(value("--start")?.parse()?),
+                "--end" => self.time_range.end = Some(value("--end")?.parse()?),
+                "--idle-timeout" => {
+                    let v = value("--idle-timeout")?;
+                    self.idle_timeout = Some(Duration::from_secs(v.parse().map_err(|_| format!("invalid --idle-timeout: {}", v))?));
+                }
+                "--max-memory" => {
+                    let v = value("--max-memory")?;
+                    self.max_memory = Some(parse_size(&v).ok_or(format!("invalid --max-memory: {}", v))?);
+                }
+                "--fps" => {
+                    let v = value("--fps")?;
+                    self.fps = Some(v.parse().ok().filter(|&n| (1..=240).contains(&n)).ok_or(format!("invalid --fps: {}", v))?);
+                }
+                "--new-hosts" => self.new_hosts = value("--new-hosts")?.parse()?,
+                "--scan-ports" => {
+                    let v = value("--scan-ports")?;
+                    self.scan_ports = Some(v.parse().ok().filter(|&n| n > 0).ok_or(format!("invalid --scan-ports: {}", v))?);
+                }
+                "--scan-window" => {
+                    let v = value("--scan-window")?;
+                    self.scan_window = Some(Duration::from_secs(v.parse().map_err(|_| format!("invalid --scan-window: {}", v))?));
+                }
+                "--rst-count" => {
+                    let v = value("--rst-count")?;
+                    self.rst_count = Some(v.parse().ok().filter(|&n| n > 0).ok_or(format!("invalid --rst-count: {}", v))?);
+                }
+                "--rst-window" => {
+                    let v = value("--rst-window")?;
+                    self.rst_window = Some(Duration::from_secs(v.parse().map_err(|_| format!("invalid --rst-window: {}", v))?));
+                }
+                "--zero-window-stall" => {
+                    let v = value("--zero-window-stall")?;
+                    self.zero_window_stall = Some(Duration::from_secs(v.parse().map_err(|_| format!("invalid --zero-window-stall: {}", v))?));
+                }
+                "--duration" => {
+                    let v = value("--duration")?;
+                    self.duration = Some(v.parse().ok().filter(|&n| n > 0).map(Duration::from_secs).ok_or(format!("invalid --duration: {}", v))?);
+                }
+                "--headless" => self.headless = true,
+                "--subnet-prefix" => {
+                    let v = value("--subnet-prefix")?;
+                    self.subnet_prefix = Some(v.parse().ok().filter(|&n| n <= 32).ok_or(format!("invalid --subnet-prefix: {}", v))?);
+                }
+                "--self-test" => self.self_test = true,
+                "--window" => {
+                    let v = value("--window")?;
+                    self.window = Some(v.parse().ok().filter(|&n| n > 0).map(Duration::from_secs).ok_or(format!("invalid --window: {}", v))?);
+                }
+                "--min-packets" => {
+                    let v = value("--min-packets")?;
+                    self.min_packets = Some(v.parse().map_err(|_| format!("invalid --min-packets: {}", v))?);
+                }
+                "-i" | "--interface" => self.interfaces.push(value("--interface")?),
+                "--monitor" => self.monitor = true,
+                "-f" | "--filter" => self.capture_filter = Some(value("--filter")?),
+                "--no-mouse" => self.no_mouse = true,
+                // For link types vshark doesn't know, or mislabels: strip exactly this many bytes instead
+                "--link-offset" => {
+                    let v = value("--link-offset")?;
+                    self.link_offset = Some(v.parse().map_err(|_| format!("invalid --link-offset: {}", v))?);
+                }
+                "--has-fcs" => self.has_fcs = true,
+                "--on-packet" => self.on_packet = Some(value("--on-packet")?),
+                "--export-csv" => self.export_csv = Some(PathBuf::from(value("--export-csv")?)),
+                "--debug" => self.debug_log = Some(PathBuf::from(DEBUG_LOG)),
+                other => return Err(format!("unknown argument: {}", other)),
+            }
+        }
+        Ok(())
+    }
+
+    // Unknown keys and unparseable values are ignored so a stale config never blocks startup; they're
+    // kept in `ignored_lines` for --self-test to report
+    pub fn parse(text: &str) -> Config {
+        let mut config = Config::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let Some((key, value)) = line.split_once('=') else {
+                config.ignored_lines.push(format!("line {}: {}", number + 1, line));
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            let accepted = if let Some(proto) = key.strip_prefix("color.") {
+                match Color::from_str(value) {
+                    Ok(color) if proto == "default" => { config.theme.default_color = color; true }
+                    Ok(color) => { config.theme.protocol_colors.insert(proto.to_uppercase(), color); true }
+                    Err(_) => false,
+                }
+            } else if key == "fps" {
+                config.fps = value.parse().ok().filter(|&n| (1..=240).contains(&n));
+                config.fps.is_some()
+            } else if key == "scan.ports" {
+                config.scan_ports = value.parse().ok().filter(|&n| n > 0);
+                config.scan_ports.is_some()
+            } else if key == "scan.window" {
+                config.scan_window = value.parse().ok().map(Duration::from_secs);
+                config.scan_window.is_some()
+            } else if key == "rst.count" {
+                config.rst_count = value.parse().ok().filter(|&n| n > 0);
+                config.rst_count.is_some()
+            } else if key == "rst.window" {
+                config.rst_window = value.parse().ok().map(Duration::from_secs);
+                config.rst_window.is_some()
+            } else if key == "zerowin.stall" {
+                config.zero_window_stall = value.parse().ok().map(Duration::from_secs);
+                config.zero_window_stall.is_some()
+            } else if let Some(name) = key.strip_prefix("preset.").filter(|n| !n.is_empty() && !value.is_empty()) {
+                match config.presets.iter_mut().find(|(n, _)| n == name) {
+                    Some(preset) => preset.1 = value.to_string(),
+                    None => config.presets.push((name.to_string(), value.to_string())),
+                }
+                true
+            } else if let Some(label) = key.strip_prefix("tag.").filter(|l| !l.is_empty() && !value.is_empty()) {
+                config.tag_rules.push((label.to_string(), value.to_string()));
+                true
+            } else if key == "newhost" {
+                let scope = value.parse();
+                config.new_hosts = scope.clone().unwrap_or_default();
+                scope.is_ok()
+            } else if key == "min_packets" {
+                config.min_packets = value.parse().ok();
+                config.min_packets.is_some()
+            } else if key == "window" {
+                config.window = value.parse().ok().filter(|&n| n > 0).map(Duration::from_secs);
+                config.window.is_some()
+            } else if key == "hex.width" {
+                config.hex_width = value.parse().ok().filter(|w| HEX_WIDTHS.contains(w));
+                config.hex_width.is_some()
+            } else if key == "on_packet" {
+                config.on_packet = Some(value.to_string()).filter(|v| !v.is_empty());
+                config.on_packet.is_some()
+            } else if key == "interface" {
+                config.interfaces = value.split(',').map(str::trim).filter(|i| !i.is_empty()).map(str::to_string).collect();
+                true
+            } else if key == "subnet.prefix" {
+                config.subnet_prefix = value.parse().ok().filter(|&n| n <= 32);
+                config.subnet_prefix.is_some()
+            } else {
+                false
+            };
+            if !accepted {
+                config.ignored_lines.push(format!("line {}: {}", number + 1, line));
+            }
+        }
+        config
+    }
+}
+
+// The config line `Config::parse` reads back as this preset
+fn preset_line(name: &str, filter: &str) -> String {
+    format!("preset.{} = {}", name, filter)
+}
+
+// Byte count with an optional K/M/G suffix (powers of 1024), e.g. `512M`
+fn parse_size(s: &str) -> Option<usize> {
+    let (digits, multiplier) = match s.chars().last()?.to_ascii_uppercase() {
+        'K' => (&s[..s.len() - 1], 1 << 10),
+        'M' => (&s[..s.len() - 1], 1 << 20),
+        'G' => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    digits.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+fn config_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("vshark").join("config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::testutil::{self, Tcp};
+
+    #[test]
+    fn color_override_changes_protocol_color() {
+        assert_eq!(Config::default().theme.protocol_color(Some("HTTPS")), Color::Magenta);
+        let config = Config::parse("color.https = red\ncolor.default = #ff8800\n");
+        assert_eq!(config.theme.protocol_color(Some("HTTPS")), Color::Red);
+        assert_eq!(config.theme.protocol_color(Some("DNS")), Color::Blue);
+        assert_eq!(config.theme.protocol_color(None), Color::Rgb(0xff, 0x88, 0x00));
+    }
+
+    #[test]
+    fn saved_preset_reloads_and_filters() {
+        let dir = env::temp_dir().join(format!("vshark-preset-{}", std::process::id()));
+        let path = dir.join("config");
+        // A hand-edited line without its newline comes first
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "color.dns = red").unwrap();
+
+        let mut config = Config::default();
+        config.save_preset_to(&path, "api", "tcp port 8443").unwrap();
+        config.save_preset_to(&path, "dns", "port 53").unwrap();
+        let reloaded = Config::parse(&fs::read_to_string(&path).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(reloaded.presets(), config.presets());
+        assert_eq!(reloaded.theme.protocol_color(Some("DNS")), Color::Red);
+        // The user's "dns" replaces the built-in in place; "api" follows the built-ins
+        let presets = reloaded.presets();
+        assert_eq!(presets.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>(), ["web", "dns", "ssh", "icmp", "api"]);
+        assert_eq!(presets[1].1, "port 53");
+
+        let filter = Filter::parse(&presets[4].1);
+        let api = testutil::update(Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 8443).packet(b""), 1);
+        assert!(filter.matches(&api));
+        assert!(!filter.matches(&testutil::update(Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 443).packet(b""), 1)));
+        assert!(!filter.matches(&testutil::update(testutil::udp([10, 0, 0, 1], 40000, [10, 0, 0, 2], 8443, b""), 1)));
+    }
+}
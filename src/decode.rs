@@ -0,0 +1,1200 @@
+// Packet decoding helpers shared by the sniffer and the inspector.
+// Everything here works on a raw IPv4 packet as stored in `PacketUpdate::raw_data`.
+use crate::json;
+use crate::text;
+
+// Returns the transport payload (bytes after the TCP/UDP header), or the IP payload for other protocols
+pub fn transport_payload(packet: &[u8]) -> &[u8] {
+    if packet.len() < 20 {
+        return &[];
+    }
+    let ihl = ((packet[0] & 0x0f) as usize) * 4;
+    if packet.len() < ihl {
+        return &[];
+    }
+    let l4 = &packet[ihl..];
+    let header_len = match packet[9] {
+        6 if l4.len() >= 20 => ((l4[12] >> 4) as usize) * 4,
+        17 if l4.len() >= 8 => 8,
+        6 | 17 => return &[],
+        _ => 0,
+    };
+    l4.get(header_len..).unwrap_or(&[])
+}
+
+// Offset of the transport payload within the packet: the IP and TCP/UDP header lengths combined
+pub fn payload_offset(packet: &[u8]) -> usize {
+    packet.len() - transport_payload(packet).len()
+}
+
+// Parses "de ad be ef", "de:ad:be:ef" or "deadbeef" into bytes
+pub fn parse_hex(input: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = input.chars().filter(|c| !c.is_whitespace() && *c != ':').collect();
+    if let Some(bad) = digits.iter().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex digit '{}'", bad));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err("odd number of hex digits".to_string());
+    }
+    Ok(digits.chunks(2)
+        .map(|pair| (pair[0].to_digit(16).unwrap() * 16 + pair[1].to_digit(16).unwrap()) as u8)
+        .collect())
+}
+
+// Offset of the first occurrence of `needle` in `haystack`
+pub fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+// Shannon entropy in bits per byte (0.0 = constant, 8.0 = uniformly random)
+pub fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for byte in data {
+        counts[*byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts.iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+// IP protocol number by name where there's a common one, e.g. for the flow CSV
+pub fn ip_protocol_name(protocol: u8) -> String {
+    match protocol {
+        1 => "ICMP".to_string(),
+        6 => "TCP".to_string(),
+        17 => "UDP".to_string(),
+        p => p.to_string(),
+    }
+}
+
+// (source port, destination port) for TCP/UDP packets
+pub fn ports(packet: &[u8]) -> Option<(u16, u16)> {
+    let ihl = ((*packet.first()? & 0x0f) as usize) * 4;
+    if !matches!(packet.get(9), Some(6) | Some(17)) {
+        return None;
+    }
+    let l4 = packet.get(ihl..ihl + 4)?;
+    Some((u16::from_be_bytes([l4[0], l4[1]]), u16::from_be_bytes([l4[2], l4[3]])))
+}
+
+const HTTP_METHODS: [&str; 9] = ["GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "CONNECT", "TRACE"];
+
+// First line of an HTTP/1.x message, if the payload starts one
+pub fn http_start_line(payload: &[u8]) -> Option<&str> {
+    let end = payload.windows(2).position(|w| w == b"\r\n")?;
+    let line = std::str::from_utf8(&payload[..end]).ok()?;
+    let first = line.split(' ').next()?;
+    (first.starts_with("HTTP/1.") || HTTP_METHODS.contains(&first)).then_some(line)
+}
+
+// Feed tag for an HTTP message: "HTTP GET /path" for requests, "HTTP 200" for responses
+pub fn http_label(payload: &[u8]) -> Option<String> {
+    let line = http_start_line(payload)?;
+    let mut parts = line.split(' ');
+    let first = parts.next()?;
+    let second = parts.next()?;
+    if first.starts_with("HTTP/") {
+        Some(format!("HTTP {}", text::sanitize(second)))
+    } else {
+        Some(format!("HTTP {} {}", first, text::sanitize(second)))
+    }
+}
+
+// Case-insensitive header lookup within the header block of an HTTP message
+pub fn http_header<'a>(payload: &'a [u8], name: &str) -> Option<&'a str> {
+    http_start_line(payload)?;
+    let head = payload.windows(4).position(|w| w == b"\r\n\r\n").map_or(payload, |end| &payload[..end]);
+    std::str::from_utf8(head).ok()?
+        .split("\r\n")
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+// Body of a complete HTTP/1.x message, cut to Content-Length or decoded from chunked
+// transfer encoding; None while it is still incomplete
+pub fn http_body(message: &[u8]) -> Option<Vec<u8>> {
+    http_start_line(message)?;
+    let body = &message[find_bytes(message, b"\r\n\r\n")? + 4..];
+    if http_header(message, "Transfer-Encoding").is_some_and(|v| v.to_ascii_lowercase().contains("chunked")) {
+        return dechunk(body);
+    }
+    let len: usize = http_header(message, "Content-Length")?.parse().ok()?;
+    body.get(..len).map(<[u8]>::to_vec)
+}
+
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    loop {
+        let eol = find_bytes(body, b"\r\n")?;
+        // Chunk extensions after ';' are ignored
+        let size_field = std::str::from_utf8(&body[..eol]).ok()?.split(';').next()?.trim();
+        let size = usize::from_str_radix(size_field, 16).ok()?;
+        body = &body[eol + 2..];
+        if size == 0 {
+            return Some(out);
+        }
+        out.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
+
+// Pretty-printed body of a complete HTTP message declared as JSON (`application/json`, `+json`)
+pub fn json_body(message: &[u8]) -> Option<String> {
+    if !http_header(message, "Content-Type")?.to_ascii_lowercase().contains("json") {
+        return None;
+    }
+    json::pretty(&text::lossy_multiline(&http_body(message)?))
+}
+
+fn upgrades_to_websocket(payload: &[u8]) -> bool {
+    http_header(payload, "Upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+}
+
+// Client half of the handshake: a request carrying `Upgrade: websocket`
+pub fn is_websocket_upgrade(payload: &[u8]) -> bool {
+    http_start_line(payload).is_some_and(|line| !line.starts_with("HTTP/")) && upgrades_to_websocket(payload)
+}
+
+// Server half of the handshake: `101 Switching Protocols` agreeing to the upgrade
+pub fn is_websocket_accept(payload: &[u8]) -> bool {
+    http_start_line(payload).is_some_and(|line| line.split(' ').nth(1) == Some("101")) && upgrades_to_websocket(payload)
+}
+
+// Opcode name of the WebSocket frame at the start of the payload
+pub fn websocket_opcode(payload: &[u8]) -> Option<&'static str> {
+    match payload.first()? & 0x0f {
+        0x0 => Some("continuation"),
+        0x1 => Some("text"),
+        0x2 => Some("binary"),
+        0x8 => Some("close"),
+        0x9 => Some("ping"),
+        0xa => Some("pong"),
+        _ => None,
+    }
+}
+
+// Link-layer details kept alongside each packet for the optional L2 inspector line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkInfo {
+    Ethernet { src: [u8; 6], dst: [u8; 6] },
+    Sll { packet_type: u16, src: Option<[u8; 6]> },
+    Wifi { frame_type: u8, subtype: u8, bssid: Option<[u8; 6]>, src: [u8; 6], dst: [u8; 6] },
+    Other,
+}
+
+impl LinkInfo {
+    pub fn from_ethernet(frame: &[u8]) -> LinkInfo {
+        match (frame.get(6..12), frame.get(0..6)) {
+            (Some(src), Some(dst)) => LinkInfo::Ethernet { src: src.try_into().unwrap(), dst: dst.try_into().unwrap() },
+            _ => LinkInfo::Other,
+        }
+    }
+
+    // Linux "cooked" capture, as produced by `-i any`; v2 moves the fields around
+    pub fn from_sll(frame: &[u8], v2: bool) -> LinkInfo {
+        let (packet_type, addr_len, addr) = if v2 {
+            (frame.get(10).map(|&t| t as u16), frame.get(11).copied(), frame.get(12..18))
+        } else {
+            (frame.get(0..2).map(|t| u16::from_be_bytes([t[0], t[1]])), frame.get(5).copied(), frame.get(6..12))
+        };
+        match packet_type {
+            Some(packet_type) => LinkInfo::Sll { packet_type, src: addr.filter(|_| addr_len == Some(6)).map(|a| a.try_into().unwrap()) },
+            None => LinkInfo::Other,
+        }
+    }
+
+    // 802.11 MAC header; which address slot holds the BSSID depends on the To/From-DS bits
+    pub fn from_wifi(mac: &[u8]) -> LinkInfo {
+        if mac.len() < 24 {
+            return LinkInfo::Other;
+        }
+        let addr = |i: usize| -> [u8; 6] { mac[4 + 6 * i..10 + 6 * i].try_into().unwrap() };
+        let (bssid, src, dst) = match mac[1] & 0x03 {
+            0 => (Some(addr(2)), addr(1), addr(0)),
+            1 => (Some(addr(0)), addr(1), addr(2)), // To DS: station -> AP
+            2 => (Some(addr(1)), addr(2), addr(0)), // From DS: AP -> station
+            _ => match mac.get(24..30) {
+                Some(sa) => (None, sa.try_into().unwrap(), addr(2)), // WDS bridge: no BSSID
+                None => return LinkInfo::Other,
+            },
+        };
+        LinkInfo::Wifi { frame_type: (mac[0] >> 2) & 0x03, subtype: mac[0] >> 4, bssid, src, dst }
+    }
+
+    pub fn describe(&self) -> Option<String> {
+        match self {
+            LinkInfo::Ethernet { src, dst } => Some(format!("Ethernet {} ➔ {}", describe_mac(src), describe_mac(dst))),
+            LinkInfo::Sll { packet_type, src } => {
+                let kind = match packet_type {
+                    0 => "to us",
+                    1 => "broadcast",
+                    2 => "multicast",
+                    3 => "to other host",
+                    4 => "outgoing",
+                    _ => "unknown",
+                };
+                Some(match src {
+                    Some(mac) => format!("Linux cooked ({}) from {}", kind, describe_mac(mac)),
+                    None => format!("Linux cooked ({})", kind),
+                })
+            }
+            LinkInfo::Wifi { frame_type, subtype, bssid, src, dst } => {
+                let mut line = format!("802.11 {} {} ➔ {}", wifi_frame_name(*frame_type, *subtype), describe_mac(src), describe_mac(dst));
+                if let Some(bssid) = bssid {
+                    line.push_str(&format!(" (BSSID {})", format_mac(bssid)));
+                }
+                Some(line)
+            }
+            LinkInfo::Other => None,
+        }
+    }
+}
+
+// Length of the radiotap header that monitor-mode captures put in front of the 802.11 frame
+pub fn radiotap_len(frame: &[u8]) -> Option<usize> {
+    if *frame.first()? != 0 {
+        return None; // Only radiotap version 0 exists
+    }
+    let len = u16::from_le_bytes([*frame.get(2)?, *frame.get(3)?]) as usize;
+    (len >= 8 && len <= frame.len()).then_some(len)
+}
+
+// Offset of the network-layer packet inside an 802.11 frame: past the MAC header and LLC/SNAP.
+// Only unencrypted data frames carry one.
+pub fn wifi_payload_offset(mac: &[u8]) -> Option<usize> {
+    let (fc, flags) = (*mac.first()?, *mac.get(1)?);
+    if (fc >> 2) & 0x03 != 2 || flags & 0x40 != 0 {
+        return None; // Not data, or protected
+    }
+    let mut len = if flags & 0x03 == 0x03 { 30 } else { 24 };
+    if fc & 0x80 != 0 {
+        len += 2; // QoS control
+        if flags & 0x80 != 0 { len += 4; } // HT control
+    }
+    match mac.get(len..len + 6) {
+        Some([0xaa, 0xaa, 0x03, 0, 0, 0]) => Some(len + 8),
+        _ => None,
+    }
+}
+
+pub fn wifi_frame_name(frame_type: u8, subtype: u8) -> &'static str {
+    match (frame_type, subtype) {
+        (0, 0) => "Association Request",
+        (0, 1) => "Association Response",
+        (0, 4) => "Probe Request",
+        (0, 5) => "Probe Response",
+        (0, 8) => "Beacon",
+        (0, 10) => "Disassociation",
+        (0, 11) => "Authentication",
+        (0, 12) => "Deauthentication",
+        (0, _) => "Management",
+        (1, _) => "Control",
+        (2, s) if s & 0x08 != 0 => "QoS Data",
+        (2, _) => "Data",
+        _ => "Reserved",
+    }
+}
+
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+fn describe_mac(mac: &[u8; 6]) -> String {
+    match oui_vendor(mac) {
+        Some(vendor) => format!("{} ({})", format_mac(mac), vendor),
+        None => format_mac(mac),
+    }
+}
+
+// A small built-in slice of the IEEE OUI registry, covering common hardware and hypervisors
+const OUI_VENDORS: [([u8; 3], &str); 16] = [
+    ([0x00, 0x00, 0x0c], "Cisco"),
+    ([0x00, 0x03, 0x93], "Apple"),
+    ([0x00, 0x0c, 0x29], "VMware"),
+    ([0x00, 0x14, 0x22], "Dell"),
+    ([0x00, 0x15, 0x5d], "Microsoft Hyper-V"),
+    ([0x00, 0x1b, 0x21], "Intel"),
+    ([0x00, 0x1b, 0x63], "Apple"),
+    ([0x00, 0x1c, 0x42], "Parallels"),
+    ([0x00, 0x50, 0x56], "VMware"),
+    ([0x00, 0xe0, 0x4c], "Realtek"),
+    ([0x08, 0x00, 0x27], "VirtualBox"),
+    ([0x3c, 0xd9, 0x2b], "Hewlett Packard"),
+    ([0x52, 0x54, 0x00], "QEMU/KVM"),
+    ([0xb8, 0x27, 0xeb], "Raspberry Pi"),
+    ([0xdc, 0xa6, 0x32], "Raspberry Pi"),
+    ([0xf4, 0xf5, 0xd8], "Google"),
+];
+
+pub fn oui_vendor(mac: &[u8; 6]) -> Option<&'static str> {
+    if mac == &[0xff; 6] {
+        return Some("broadcast");
+    }
+    OUI_VENDORS.iter().find(|(prefix, _)| mac[..3] == prefix[..]).map(|(_, vendor)| *vendor)
+}
+
+// The inspector's decode of one application message: a headline with fields indented below it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DetailTree {
+    pub text: String,
+    pub children: Vec<DetailTree>,
+}
+
+impl DetailTree {
+    pub fn new(text: impl Into<String>) -> DetailTree {
+        DetailTree { text: text.into(), children: Vec::new() }
+    }
+
+    pub fn field(mut self, text: impl Into<String>) -> DetailTree {
+        self.children.push(DetailTree::new(text));
+        self
+    }
+
+    // One line per node, indented two spaces per level below the headline
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![self.text.clone()];
+        for child in &self.children {
+            lines.extend(child.lines().into_iter().map(|line| format!("  {}", line)));
+        }
+        lines
+    }
+}
+
+// A stateless application protocol. `matches` is the cheap gate (IP protocol, port); `label` names
+// the packet for the feed and `detail` is the inspector's decode. Flow state (WebSocket upgrades)
+// stays in the capture's FlowTracker, which is consulted before the registry.
+pub trait ProtocolDecoder: Sync {
+    fn matches(&self, packet: &[u8]) -> bool;
+    fn label(&self, packet: &[u8]) -> Option<String>;
+    fn detail(&self, _packet: &[u8]) -> Option<DetailTree> {
+        None
+    }
+}
+
+// Either end of a TCP (6) or UDP (17) packet on `port`
+fn on_port(packet: &[u8], protocol: u8, port: u16) -> bool {
+    packet.get(9) == Some(&protocol) && ports(packet).is_some_and(|(s, d)| s == port || d == port)
+}
+
+// A TCP or UDP packet sent to `port`; the port-only decoders don't tag the replies
+fn to_port(packet: &[u8], port: u16) -> bool {
+    ports(packet).is_some_and(|(_, d)| d == port)
+}
+
+// HTTP/1.x on any TCP port, recognised by its start line
+struct Http;
+
+impl ProtocolDecoder for Http {
+    fn matches(&self, packet: &[u8]) -> bool {
+        packet.get(9) == Some(&6)
+    }
+
+    fn label(&self, packet: &[u8]) -> Option<String> {
+        http_label(transport_payload(packet))
+    }
+
+    fn detail(&self, packet: &[u8]) -> Option<DetailTree> {
+        let payload = transport_payload(packet);
+        let line = http_start_line(payload)?;
+        let head = payload.windows(4).position(|w| w == b"\r\n\r\n").map_or(payload, |end| &payload[..end]);
+        Some(text::lossy_multiline(head).split("\r\n").skip(1).fold(DetailTree::new(text::sanitize(line)), |tree, header| tree.field(text::sanitize(header))))
+    }
+}
+
+struct Igmp;
+
+impl ProtocolDecoder for Igmp {
+    fn matches(&self, packet: &[u8]) -> bool {
+        packet.get(9) == Some(&2)
+    }
+
+    fn label(&self, packet: &[u8]) -> Option<String> {
+        igmp_label(transport_payload(packet))
+    }
+}
+
+// IPsec has no ports; the SPI is in the inspector's detail
+struct Ipsec;
+
+impl ProtocolDecoder for Ipsec {
+    fn matches(&self, packet: &[u8]) -> bool {
+        matches!(packet.get(9), Some(50 | 51))
+    }
+
+    fn label(&self, packet: &[u8]) -> Option<String> {
+        ipsec_header(packet[9], transport_payload(packet)).map(|h| h.name().to_string())
+    }
+
+    fn detail(&self, packet: &[u8]) -> Option<DetailTree> {
+        ipsec_header(packet[9], transport_payload(packet)).map(|h| h.detail())
+    }
+}
+
+struct Ntp;
+
+impl ProtocolDecoder for Ntp {
+    fn matches(&self, packet: &[u8]) -> bool {
+        on_port(packet, 17, 123)
+    }
+
+    fn label(&self, packet: &[u8]) -> Option<String> {
+        ntp_header(transport_payload(packet)).map(|ntp| format!("NTP {} v{}", ntp.mode_name(), ntp.version))
+    }
+
+    fn detail(&self, packet: &[u8]) -> Option<DetailTree> {
+        ntp_header(transport_payload(packet)).map(|ntp| DetailTree::new(format!("NTP v{} {}", ntp.version, ntp.mode_name())).field(format!("Stratum {}", ntp.stratum)))
+    }
+}
+
+struct Quic;
+
+impl ProtocolDecoder for Quic {
+    fn matches(&self, packet: &[u8]) -> bool {
+        on_port(packet, 17, 443)
+    }
+
+    fn label(&self, packet: &[u8]) -> Option<String> {
+        quic_label(transport_payload(packet))
+    }
+}
+
+struct Mqtt;
+
+impl ProtocolDecoder for Mqtt {
+    fn matches(&self, packet: &[u8]) -> bool {
+        on_port(packet, 6, 1883)
+    }
+
+    fn label(&self, packet: &[u8]) -> Option<String> {
+        mqtt_label(transport_payload(packet))
+    }
+}
+
+// TLS to 443 is tagged HTTPS whatever the record; the record type is in the detail
+struct Https;
+
+impl ProtocolDecoder for Https {
+    fn matches(&self, packet: &[u8]) -> bool {
+        packet.get(9) == Some(&6) && to_port(packet, 443)
+    }
+
+    fn label(&self, _packet: &[u8]) -> Option<String> {
+        Some("HTTPS".to_string())
+    }
+
+    fn detail(&self, packet: &[u8]) -> Option<DetailTree> {
+        tls_record_label(transport_payload(packet)).map(DetailTree::new)
+    }
+}
+
+// Queries over UDP or TCP; a message that doesn't parse is still tagged by its port
+struct Dns;
+
+impl ProtocolDecoder for Dns {
+    fn matches(&self, packet: &[u8]) -> bool {
+        to_port(packet, 53)
+    }
+
+    fn label(&self, packet: &[u8]) -> Option<String> {
+        Some(dns_payload(packet).and_then(dns_label).unwrap_or_else(|| "DNS".to_string()))
+    }
+
+    fn detail(&self, packet: &[u8]) -> Option<DetailTree> {
+        let message = dns_message(dns_payload(packet)?)?;
+        let kind = if message.response { "response" } else { "query" };
+        Some(DetailTree::new(format!("DNS {} {}", kind, message.name)).field(format!("ID 0x{:04x}", message.id)))
+    }
+}
+
+struct Ssh;
+
+impl ProtocolDecoder for Ssh {
+    fn matches(&self, packet: &[u8]) -> bool {
+        packet.get(9) == Some(&6) && to_port(packet, 22)
+    }
+
+    fn label(&self, _packet: &[u8]) -> Option<String> {
+        Some("SSH".to_string())
+    }
+}
+
+struct MqttTls;
+
+impl ProtocolDecoder for MqttTls {
+    fn matches(&self, packet: &[u8]) -> bool {
+        packet.get(9) == Some(&6) && to_port(packet, 8883)
+    }
+
+    fn label(&self, _packet: &[u8]) -> Option<String> {
+        Some("MQTT TLS".to_string())
+    }
+}
+
+// Consulted in order; the first matching decoder that recognises the packet wins. Payload
+// decoders come first, so the port-only ones at the end only name what nothing else recognised.
+pub static DECODERS: &[&dyn ProtocolDecoder] = &[&Http, &Igmp, &Ipsec, &Ntp, &Quic, &Mqtt, &Https, &Dns, &Ssh, &MqttTls];
+
+pub fn label_with(decoders: &[&dyn ProtocolDecoder], packet: &[u8]) -> Option<String> {
+    decoders.iter().filter(|d| d.matches(packet)).find_map(|d| d.label(packet))
+}
+
+pub fn detail_with(decoders: &[&dyn ProtocolDecoder], packet: &[u8]) -> Option<DetailTree> {
+    decoders.iter().filter(|d| d.matches(packet)).find_map(|d| d.detail(packet))
+}
+
+// Feed tag from the first registered decoder that recognises the packet
+pub fn app_label(packet: &[u8]) -> Option<String> {
+    label_with(DECODERS, packet)
+}
+
+const MQTT_TYPES: [&str; 15] = [
+    "CONNECT", "CONNACK", "PUBLISH", "PUBACK", "PUBREC", "PUBREL", "PUBCOMP", "SUBSCRIBE",
+    "SUBACK", "UNSUBSCRIBE", "UNSUBACK", "PINGREQ", "PINGRESP", "DISCONNECT", "AUTH",
+];
+
+// "MQTT PUBLISH sensors/temp" from the fixed header of the first control packet in a segment
+pub fn mqtt_label(payload: &[u8]) -> Option<String> {
+    let kind = *MQTT_TYPES.get((*payload.first()? >> 4).checked_sub(1)? as usize)?;
+    // Remaining length is a base-128 varint of at most four bytes
+    let mut header_len = 1;
+    loop {
+        let byte = *payload.get(header_len)?;
+        header_len += 1;
+        if byte & 0x80 == 0 { break; }
+        if header_len > 4 { return None; }
+    }
+    if kind != "PUBLISH" {
+        return Some(format!("MQTT {}", kind));
+    }
+    let topic_len = u16::from_be_bytes([*payload.get(header_len)?, *payload.get(header_len + 1)?]) as usize;
+    let topic = payload.get(header_len + 2..header_len + 2 + topic_len)?;
+    Some(format!("MQTT PUBLISH {}", text::lossy(topic)))
+}
+
+// Heuristic QUIC recognition: the fixed bit must be set and long headers must carry a known version
+pub fn quic_label(payload: &[u8]) -> Option<String> {
+    let first = *payload.first()?;
+    if first & 0x40 == 0 {
+        return None;
+    }
+    if first & 0x80 == 0 {
+        return Some("QUIC".to_string()); // Short header: 1-RTT data, no version on the wire
+    }
+    let version = u32::from_be_bytes(payload.get(1..5)?.try_into().ok()?);
+    let (name, v2) = match version {
+        0x0000_0001 => ("v1".to_string(), false),
+        0x6b33_43cf => ("v2".to_string(), true),
+        v if v >> 8 == 0xff_0000 => (format!("draft-{}", v & 0xff), false),
+        _ => return None,
+    };
+    // QUIC v2 shuffled the long-header packet type codes
+    let kind = match ((first >> 4) & 0x03, v2) {
+        (0, false) | (1, true) => "Initial",
+        (1, false) | (2, true) => "0-RTT",
+        (2, false) | (3, true) => "Handshake",
+        _ => "Retry",
+    };
+    Some(format!("QUIC {} {}", kind, name))
+}
+
+// IGMP v1-v3 message type, with the group address when the message names one
+pub fn igmp_label(payload: &[u8]) -> Option<String> {
+    if payload.len() < 8 {
+        return None;
+    }
+    let group = std::net::Ipv4Addr::new(payload[4], payload[5], payload[6], payload[7]);
+    let kind = match payload[0] {
+        0x11 => "Query",
+        0x12 | 0x16 => "Membership Report",
+        0x17 => "Leave Group",
+        // v3 reports carry per-group records instead of a single group field
+        0x22 => return Some("IGMP Membership Report v3".to_string()),
+        _ => return None,
+    };
+    // A query for 0.0.0.0 is a general query covering every group
+    Some(if group.is_unspecified() { format!("IGMP {}", kind) } else { format!("IGMP {} {}", kind, group) })
+}
+
+// Negotiated parameters from a TLS ServerHello at the start of a TCP payload
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TlsParams {
+    pub version: u16,
+    pub cipher_suite: u16,
+}
+
+impl TlsParams {
+    pub fn describe(&self) -> String {
+        format!("{}, {}", tls_version_name(self.version), cipher_suite_name(self.cipher_suite))
+    }
+}
+
+pub fn server_hello(packet: &[u8]) -> Option<TlsParams> {
+    if packet.get(9) != Some(&6) {
+        return None;
+    }
+    let record = transport_payload(packet);
+    // Handshake record carrying a ServerHello (type 2)
+    if record.len() < 9 || record[0] != 0x16 || record[1] != 0x03 || record[5] != 0x02 {
+        return None;
+    }
+    let hello = &record[9..];
+    let mut version = u16::from_be_bytes([*hello.first()?, *hello.get(1)?]);
+    let session_len = *hello.get(34)? as usize;
+    let mut pos = 35 + session_len;
+    let cipher_suite = u16::from_be_bytes([*hello.get(pos)?, *hello.get(pos + 1)?]);
+    pos += 3; // Cipher suite and compression method
+
+    // TLS 1.3 keeps 1.2 in the legacy field and puts the real version in supported_versions
+    if let Some(ext_len) = hello.get(pos..pos + 2) {
+        let end = (pos + 2 + u16::from_be_bytes([ext_len[0], ext_len[1]]) as usize).min(hello.len());
+        pos += 2;
+        while pos + 4 <= end {
+            let kind = u16::from_be_bytes([hello[pos], hello[pos + 1]]);
+            let len = u16::from_be_bytes([hello[pos + 2], hello[pos + 3]]) as usize;
+            if kind == 0x002b && len == 2 {
+                if let Some(v) = hello.get(pos + 4..pos + 6) { version = u16::from_be_bytes([v[0], v[1]]); }
+            }
+            pos += 4 + len;
+        }
+    }
+    Some(TlsParams { version, cipher_suite })
+}
+
+pub fn tls_version_name(version: u16) -> String {
+    match version {
+        0x0300 => "SSL 3.0".to_string(),
+        0x0301 => "TLS 1.0".to_string(),
+        0x0302 => "TLS 1.1".to_string(),
+        0x0303 => "TLS 1.2".to_string(),
+        0x0304 => "TLS 1.3".to_string(),
+        v => format!("TLS 0x{:04x}", v),
+    }
+}
+
+const CIPHER_SUITES: [(u16, &str); 15] = [
+    (0x002f, "TLS_RSA_WITH_AES_128_CBC_SHA"),
+    (0x0035, "TLS_RSA_WITH_AES_256_CBC_SHA"),
+    (0x009c, "TLS_RSA_WITH_AES_128_GCM_SHA256"),
+    (0x009d, "TLS_RSA_WITH_AES_256_GCM_SHA384"),
+    (0x1301, "TLS_AES_128_GCM_SHA256"),
+    (0x1302, "TLS_AES_256_GCM_SHA384"),
+    (0x1303, "TLS_CHACHA20_POLY1305_SHA256"),
+    (0xc013, "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA"),
+    (0xc014, "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA"),
+    (0xc02b, "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"),
+    (0xc02c, "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384"),
+    (0xc02f, "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256"),
+    (0xc030, "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"),
+    (0xcca8, "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256"),
+    (0xcca9, "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256"),
+];
+
+pub fn cipher_suite_name(suite: u16) -> String {
+    CIPHER_SUITES.iter()
+        .find(|(id, _)| *id == suite)
+        .map_or_else(|| format!("cipher 0x{:04x}", suite), |(_, name)| name.to_string())
+}
+
+// Protocol a flow can be force-decoded as from the inspector, whatever its ports
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeAs {
+    Http,
+    Dns,
+    Tls,
+}
+
+impl DecodeAs {
+    // Cycle order for the "inspect as" key; None returns the flow to automatic detection
+    pub fn cycle(current: Option<DecodeAs>) -> Option<DecodeAs> {
+        match current {
+            None => Some(DecodeAs::Http),
+            Some(DecodeAs::Http) => Some(DecodeAs::Dns),
+            Some(DecodeAs::Dns) => Some(DecodeAs::Tls),
+            Some(DecodeAs::Tls) => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DecodeAs::Http => "HTTP",
+            DecodeAs::Dns => "DNS",
+            DecodeAs::Tls => "TLS",
+        }
+    }
+
+    // Tag for one packet of the flow, or None when the payload doesn't parse as this protocol
+    pub fn label(self, packet: &[u8]) -> Option<String> {
+        let payload = transport_payload(packet);
+        match self {
+            DecodeAs::Http => http_label(payload),
+            // DNS over TCP prefixes each message with its length
+            DecodeAs::Dns if packet.get(9) == Some(&6) => dns_label(payload.get(2..)?),
+            DecodeAs::Dns => dns_label(payload),
+            DecodeAs::Tls => tls_record_label(payload),
+        }
+    }
+}
+
+// "DNS query example.com" / "DNS response example.com" from a DNS message's first question
+pub fn dns_label(message: &[u8]) -> Option<String> {
+    if message.len() < 12 {
+        return None;
+    }
+    let kind = if message[2] & 0x80 != 0 { "response" } else { "query" };
+    if u16::from_be_bytes([message[4], message[5]]) == 0 {
+        return Some(format!("DNS {}", kind));
+    }
+    let mut name = String::new();
+    let mut pos = 12;
+    loop {
+        let len = *message.get(pos)? as usize;
+        if len == 0 { break; }
+        if len & 0xc0 != 0 { return None; } // Compression pointers don't belong in the first question
+        let label = message.get(pos + 1..pos + 1 + len)?;
+        if !name.is_empty() { name.push('.'); }
+        name.push_str(&text::lossy(label));
+        pos += 1 + len;
+    }
+    Some(if name.is_empty() { format!("DNS {} <root>", kind) } else { format!("DNS {} {}", kind, name) })
+}
+
+// The DNS message carried by a port-53 packet; over TCP the 2-byte length prefix is skipped
+pub fn dns_payload(packet: &[u8]) -> Option<&[u8]> {
+    let (sport, dport) = ports(packet)?;
+    if sport != 53 && dport != 53 {
+        return None;
+    }
+    let payload = transport_payload(packet);
+    if packet[9] == 6 { payload.get(2..) } else { Some(payload) }
+}
+
+// The parts of a DNS message needed to pair a response with its query
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub response: bool,
+    pub rcode: u8,
+    pub name: String,         // First question
+    pub answers: Vec<String>, // A and AAAA addresses, CNAME targets, in answer order
+}
+
+pub fn dns_message(message: &[u8]) -> Option<DnsMessage> {
+    if message.len() < 12 {
+        return None;
+    }
+    let count = |at: usize| u16::from_be_bytes([message[at], message[at + 1]]);
+    let (questions, answer_count) = (count(4), count(6));
+    let mut name = String::new();
+    let mut pos = 12;
+    for i in 0..questions {
+        let (qname, end) = dns_name(message, pos)?;
+        if i == 0 { name = qname; }
+        pos = end + 4; // QTYPE, QCLASS
+    }
+    let mut answers = Vec::new();
+    for _ in 0..answer_count {
+        let (_, end) = dns_name(message, pos)?;
+        let header = message.get(end..end + 10)?;
+        let rtype = u16::from_be_bytes([header[0], header[1]]);
+        let rdlength = u16::from_be_bytes([header[8], header[9]]) as usize;
+        let rdata = message.get(end + 10..end + 10 + rdlength)?;
+        match (rtype, rdlength) {
+            (1, 4) => answers.push(std::net::Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]).to_string()),
+            (28, 16) => answers.push(std::net::Ipv6Addr::from(<[u8; 16]>::try_from(rdata).ok()?).to_string()),
+            (5, _) => answers.push(dns_name(message, end + 10)?.0),
+            _ => {}
+        }
+        pos = end + 10 + rdlength;
+    }
+    Some(DnsMessage { id: count(0), response: message[2] & 0x80 != 0, rcode: message[3] & 0x0f, name, answers })
+}
+
+// A possibly-compressed name at `pos`, and the offset just past it (before any pointer jump)
+fn dns_name(message: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut name = String::new();
+    let mut end = None;
+    // Bounded so a pointer loop can't spin forever
+    for _ in 0..64 {
+        let len = *message.get(pos)? as usize;
+        if len == 0 {
+            return Some((name, end.unwrap_or(pos + 1)));
+        }
+        if len & 0xc0 == 0xc0 {
+            end.get_or_insert(pos + 2);
+            pos = ((len & 0x3f) << 8) | *message.get(pos + 1)? as usize;
+            continue;
+        }
+        let label = message.get(pos + 1..pos + 1 + len)?;
+        if !name.is_empty() { name.push('.'); }
+        name.push_str(&text::lossy(label));
+        pos += 1 + len;
+    }
+    None
+}
+
+pub fn dns_rcode_name(rcode: u8) -> String {
+    match rcode {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        r => format!("rcode {}", r),
+    }
+}
+
+// Content type of the TLS record at the start of a payload, naming the handshake message if any
+pub fn tls_record_label(payload: &[u8]) -> Option<String> {
+    if payload.len() < 5 || payload[1] != 0x03 {
+        return None;
+    }
+    Some(match payload[0] {
+        0x14 => "TLS ChangeCipherSpec".to_string(),
+        0x15 => "TLS Alert".to_string(),
+        0x16 => match payload.get(5) {
+            Some(1) => "TLS ClientHello".to_string(),
+            Some(2) => "TLS ServerHello".to_string(),
+            Some(11) => "TLS Certificate".to_string(),
+            _ => "TLS Handshake".to_string(),
+        },
+        0x17 => "TLS Application Data".to_string(),
+        _ => return None,
+    })
+}
+
+// TCP flags byte of a segment, if the packet is TCP
+pub fn tcp_flags(ip: &[u8]) -> Option<u8> {
+    if ip.len() < 20 || ip[9] != 6 {
+        return None;
+    }
+    let ihl = ((ip[0] & 0x0f) as usize) * 4;
+    ip.get(ihl + 13).copied()
+}
+
+// Sequence number of a TCP segment
+pub fn tcp_seq(packet: &[u8]) -> Option<u32> {
+    if packet.get(9) != Some(&6) {
+        return None;
+    }
+    let ihl = ((*packet.first()? & 0x0f) as usize) * 4;
+    let seq = packet.get(ihl + 4..ihl + 8)?;
+    Some(u32::from_be_bytes([seq[0], seq[1], seq[2], seq[3]]))
+}
+
+// Acknowledgment number of a TCP segment with the ACK flag set
+pub fn tcp_ack(packet: &[u8]) -> Option<u32> {
+    if tcp_flags(packet)? & 0x10 == 0 {
+        return None;
+    }
+    let ihl = ((packet[0] & 0x0f) as usize) * 4;
+    let ack = packet.get(ihl + 8..ihl + 12)?;
+    Some(u32::from_be_bytes([ack[0], ack[1], ack[2], ack[3]]))
+}
+
+// Receive window a TCP segment advertises, unscaled
+pub fn tcp_window(packet: &[u8]) -> Option<u16> {
+    if packet.get(9) != Some(&6) {
+        return None;
+    }
+    let ihl = ((*packet.first()? & 0x0f) as usize) * 4;
+    let window = packet.get(ihl + 14..ihl + 16)?;
+    Some(u16::from_be_bytes([window[0], window[1]]))
+}
+
+// A segment telling the peer to stop sending: the receiver's buffer is full. RSTs carry
+// a zero window as a matter of course, so they don't count.
+pub fn zero_window(packet: &[u8]) -> bool {
+    let ihl = packet.first().map_or(0, |b| ((b & 0x0f) as usize) * 4);
+    tcp_window(packet) == Some(0) && packet.get(ihl + 13).is_some_and(|flags| flags & 0x04 == 0)
+}
+
+// Decode of the application layer for the inspector
+pub fn app_detail(packet: &[u8]) -> Option<DetailTree> {
+    detail_with(DECODERS, packet)
+}
+
+// The cleartext header of an IPsec packet: ESP starts with the SPI and sequence number, AH puts
+// them after its next-header, length and reserved fields
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpsecHeader {
+    pub protocol: u8, // 50 ESP, 51 AH
+    pub spi: u32,
+    pub seq: u32,
+}
+
+impl IpsecHeader {
+    pub fn name(&self) -> &'static str {
+        if self.protocol == 50 { "ESP" } else { "AH" }
+    }
+
+    pub fn detail(&self) -> DetailTree {
+        DetailTree::new(self.name()).field(format!("SPI 0x{:08x}", self.spi)).field(format!("Sequence {}", self.seq))
+    }
+}
+
+pub fn ipsec_header(protocol: u8, payload: &[u8]) -> Option<IpsecHeader> {
+    let fields = match protocol {
+        50 => payload.get(0..8)?,
+        51 => payload.get(4..12)?,
+        _ => return None,
+    };
+    Some(IpsecHeader {
+        protocol,
+        spi: u32::from_be_bytes([fields[0], fields[1], fields[2], fields[3]]),
+        seq: u32::from_be_bytes([fields[4], fields[5], fields[6], fields[7]]),
+    })
+}
+
+pub struct NtpHeader {
+    pub version: u8,
+    pub mode: u8,
+    pub stratum: u8,
+}
+
+impl NtpHeader {
+    pub fn mode_name(&self) -> &'static str {
+        match self.mode {
+            1 => "symmetric-active",
+            2 => "symmetric-passive",
+            3 => "client",
+            4 => "server",
+            5 => "broadcast",
+            6 => "control",
+            7 => "private",
+            _ => "reserved",
+        }
+    }
+}
+
+// The fixed 48-byte NTPv1-4 header; anything shorter or with a bogus version isn't NTP
+pub fn ntp_header(payload: &[u8]) -> Option<NtpHeader> {
+    if payload.len() < 48 {
+        return None;
+    }
+    let version = (payload[0] >> 3) & 0x07;
+    if !(1..=4).contains(&version) {
+        return None;
+    }
+    Some(NtpHeader { version, mode: payload[0] & 0x07, stratum: payload[1] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn entropy_of_constant_and_uniform_bytes() {
+        assert_eq!(entropy(&[0u8; 64]), 0.0);
+        let uniform: Vec<u8> = (0..=255).collect();
+        assert!((entropy(&uniform) - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ethernet_macs_resolve_vendors() {
+        let ip = testutil::Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80).packet(b"");
+        let frame = testutil::ethernet([0x00, 0x0c, 0x29, 0x12, 0x34, 0x56], [0xff; 6], &ip);
+        let link = LinkInfo::from_ethernet(&frame);
+        assert_eq!(link, LinkInfo::Ethernet { src: [0x00, 0x0c, 0x29, 0x12, 0x34, 0x56], dst: [0xff; 6] });
+        assert_eq!(link.describe().as_deref(), Some("Ethernet 00:0c:29:12:34:56 (VMware) ➔ ff:ff:ff:ff:ff:ff (broadcast)"));
+        assert_eq!(oui_vendor(&[0x02, 0, 0, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn ntp_client_request_label() {
+        let mut request = [0u8; 48];
+        request[0] = 4 << 3 | 3; // LI 0, version 4, mode 3
+        let packet = testutil::udp([10, 0, 0, 1], 40123, [162, 159, 200, 1], 123, &request);
+        assert_eq!(app_label(&packet).as_deref(), Some("NTP client v4"));
+        assert_eq!(app_detail(&packet), Some(DetailTree::new("NTP v4 client").field("Stratum 0")));
+    }
+
+    #[test]
+    fn quic_initial_long_header() {
+        let initial = [0xc3, 0x00, 0x00, 0x00, 0x01, 0x08, 1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(quic_label(&initial).as_deref(), Some("QUIC Initial v1"));
+        let packet = testutil::udp([10, 0, 0, 1], 50000, [142, 250, 0, 1], 443, &initial);
+        assert_eq!(app_label(&packet).as_deref(), Some("QUIC Initial v1"));
+        assert_eq!(quic_label(&[0x80, 0, 0, 0, 1]), None); // Fixed bit clear
+    }
+
+    #[test]
+    fn igmpv2_membership_report() {
+        let report = [0x16, 0x00, 0xfa, 0x04, 239, 255, 255, 250];
+        let packet = testutil::ipv4(2, [192, 168, 1, 20], [239, 255, 255, 250], &report);
+        assert_eq!(app_label(&packet).as_deref(), Some("IGMP Membership Report 239.255.255.250"));
+        assert_eq!(igmp_label(&[0x11, 0x64, 0, 0, 0, 0, 0, 0]).as_deref(), Some("IGMP Query"));
+        assert_eq!(igmp_label(&report[..6]), None);
+    }
+
+    #[test]
+    fn mqtt_publish_topic() {
+        // PUBLISH, QoS 0: remaining length, topic length, topic, then the message
+        let publish = [&[0x30, 0x11, 0x00, 0x0c][..], b"sensors/temp", b"21.5"].concat();
+        let client = testutil::Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 1883);
+        assert_eq!(app_label(&client.packet(&publish)).as_deref(), Some("MQTT PUBLISH sensors/temp"));
+
+        // A two-byte remaining length moves the topic along
+        let mut long = vec![0x32, 0x83, 0x01, 0x00, 0x04];
+        long.extend_from_slice(b"logs");
+        long.extend_from_slice(&[0; 125]);
+        assert_eq!(mqtt_label(&long).as_deref(), Some("MQTT PUBLISH logs"));
+
+        assert_eq!(mqtt_label(&[0xc0, 0x00]).as_deref(), Some("MQTT PINGREQ"));
+        assert_eq!(mqtt_label(&publish[..8]), None);
+    }
+
+    #[test]
+    fn esp_spi_and_sequence() {
+        let esp = [&[0xc0, 0xff, 0xee, 0x01, 0, 0, 0, 42][..], &[0x99; 24]].concat();
+        let packet = testutil::ipv4(50, [10, 0, 0, 1], [198, 51, 100, 7], &esp);
+        assert_eq!(ipsec_header(50, transport_payload(&packet)), Some(IpsecHeader { protocol: 50, spi: 0xc0ff_ee01, seq: 42 }));
+        assert_eq!(app_label(&packet).as_deref(), Some("ESP"));
+        assert_eq!(app_detail(&packet).map(|d| d.lines()), Some(vec!["ESP".to_string(), "  SPI 0xc0ffee01".to_string(), "  Sequence 42".to_string()]));
+
+        // AH carries next-header, length and reserved bytes in front
+        let ah = [&[6, 4, 0, 0, 0, 0, 0x10, 0x01, 0, 0, 0, 3][..], &[0; 12]].concat();
+        assert_eq!(ipsec_header(51, &ah).map(|h| h.detail()), Some(DetailTree::new("AH").field("SPI 0x00001001").field("Sequence 3")));
+        assert_eq!(ipsec_header(50, &esp[..7]), None);
+    }
+
+    // A ServerHello record choosing `suite`, with `extensions` appended verbatim
+    fn server_hello_record(suite: u16, extensions: &[u8]) -> Vec<u8> {
+        let mut hello = vec![0x03, 0x03];
+        hello.extend_from_slice(&[0x5a; 32]); // Random
+        hello.push(32);
+        hello.extend_from_slice(&[0x11; 32]); // Session ID
+        hello.extend_from_slice(&suite.to_be_bytes());
+        hello.push(0); // Compression
+        hello.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        hello.extend_from_slice(extensions);
+        let mut handshake = vec![0x02, 0, (hello.len() >> 8) as u8, hello.len() as u8];
+        handshake.extend_from_slice(&hello);
+        let mut record = vec![0x16, 0x03, 0x03];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn server_hello_negotiated_parameters() {
+        let server = testutil::Tcp::new([93, 184, 216, 34], 443, [10, 0, 0, 1], 40000);
+        let tls12 = server_hello(&server.packet(&server_hello_record(0xc02f, &[]))).unwrap();
+        assert_eq!(tls12, TlsParams { version: 0x0303, cipher_suite: 0xc02f });
+        assert_eq!(tls12.describe(), "TLS 1.2, TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256");
+
+        // TLS 1.3 only shows up in supported_versions, after an unrelated extension
+        let extensions = [0x00, 0x33, 0x00, 0x02, 0xaa, 0xbb, 0x00, 0x2b, 0x00, 0x02, 0x03, 0x04];
+        let tls13 = server_hello(&server.packet(&server_hello_record(0x1301, &extensions))).unwrap();
+        assert_eq!(tls13.describe(), "TLS 1.3, TLS_AES_128_GCM_SHA256");
+
+        let mut client_hello = server_hello_record(0x1301, &[]);
+        client_hello[5] = 0x01;
+        assert_eq!(server_hello(&server.packet(&client_hello)), None);
+    }
+
+    #[test]
+    fn payload_offset_skips_each_header() {
+        let tcp = testutil::Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80).packet(b"GET /");
+        assert_eq!(payload_offset(&tcp), 40);
+        assert_eq!(&tcp[payload_offset(&tcp)..], b"GET /");
+
+        // TCP options push the payload out by the data offset
+        let mut options = testutil::Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80).packet(&[1, 1, 1, 1, b'x']);
+        options[32] = 0x60;
+        assert_eq!(payload_offset(&options), 44);
+        assert_eq!(&options[payload_offset(&options)..], b"x");
+
+        let udp = testutil::udp([10, 0, 0, 1], 5353, [224, 0, 0, 251], 5353, b"mdns");
+        assert_eq!(&udp[payload_offset(&udp)..], b"mdns");
+        assert_eq!(payload_offset(&udp), 28);
+        assert_eq!(payload_offset(&[0x45; 8]), 8); // Too short to hold a header: no payload
+    }
+
+    #[test]
+    fn http_request_detail_lists_its_headers() {
+        let client = testutil::Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 8080);
+        let packet = client.packet(b"GET /status HTTP/1.1\r\nHost: example\r\nAccept: */*\r\n\r\n");
+        assert_eq!(app_label(&packet).as_deref(), Some("HTTP GET /status"));
+        assert_eq!(app_detail(&packet), Some(DetailTree::new("GET /status HTTP/1.1").field("Host: example").field("Accept: */*")));
+        assert_eq!(app_label(&client.packet(b"\x00\x01binary")), None);
+    }
+
+    #[test]
+    fn dns_query_is_named_by_its_question() {
+        let query = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01";
+        let udp = testutil::udp([10, 0, 0, 1], 53000, [10, 0, 0, 53], 53, query);
+        assert_eq!(app_label(&udp).as_deref(), Some("DNS query example.com"));
+        assert_eq!(app_detail(&udp), Some(DetailTree::new("DNS query example.com").field("ID 0x1234")));
+        // Over TCP the message follows a length prefix
+        let tcp = testutil::Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 53], 53).packet(&[&[0, query.len() as u8][..], query].concat());
+        assert_eq!(app_label(&tcp).as_deref(), Some("DNS query example.com"));
+        // Anything else sent to 53 is still DNS by its port
+        assert_eq!(app_label(&testutil::udp([10, 0, 0, 1], 53000, [10, 0, 0, 53], 53, b"junk")).as_deref(), Some("DNS"));
+    }
+
+    #[test]
+    fn port_only_protocols_tag_what_is_sent_to_them() {
+        let to = |dport| testutil::Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], dport);
+        let hello = [0x16, 0x03, 0x01, 0x00, 0x40, 0x01];
+        assert_eq!(app_label(&to(443).packet(&hello)).as_deref(), Some("HTTPS"));
+        assert_eq!(app_detail(&to(443).packet(&hello)), Some(DetailTree::new("TLS ClientHello")));
+        assert_eq!(app_label(&to(22).packet(b"SSH-2.0-OpenSSH_9.6\r\n")).as_deref(), Some("SSH"));
+        assert_eq!(app_label(&to(8883).packet(b"")).as_deref(), Some("MQTT TLS"));
+        // Replies from those ports, and UDP to the TCP-only ones, aren't tagged
+        assert_eq!(app_label(&testutil::Tcp::new([10, 0, 0, 2], 22, [10, 0, 0, 1], 40000).packet(b"")), None);
+        assert_eq!(app_label(&testutil::udp([10, 0, 0, 1], 40000, [10, 0, 0, 2], 22, b"")), None);
+    }
+
+    #[test]
+    fn registered_decoder_is_consulted_in_order() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Echo {
+            port: u16,
+            consulted: AtomicUsize,
+        }
+
+        impl ProtocolDecoder for Echo {
+            fn matches(&self, packet: &[u8]) -> bool {
+                self.consulted.fetch_add(1, Ordering::Relaxed);
+                to_port(packet, self.port)
+            }
+
+            fn label(&self, packet: &[u8]) -> Option<String> {
+                Some(format!("ECHO {}", transport_payload(packet).len()))
+            }
+
+            fn detail(&self, _packet: &[u8]) -> Option<DetailTree> {
+                Some(DetailTree::new("Echo").field("seen"))
+            }
+        }
+
+        let echo = Echo { port: 7, consulted: AtomicUsize::new(0) };
+        let registry: Vec<&dyn ProtocolDecoder> = [&echo as &dyn ProtocolDecoder].into_iter().chain(DECODERS.iter().copied()).collect();
+        let to_echo = testutil::Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 7).packet(b"GET / HTTP/1.1\r\n");
+        // Ahead of the built-in HTTP decoder, the mock claims the request
+        assert_eq!(label_with(&registry, &to_echo).as_deref(), Some("ECHO 16"));
+        assert_eq!(detail_with(&registry, &to_echo).map(|d| d.lines()), Some(vec!["Echo".to_string(), "  seen".to_string()]));
+        assert_eq!(echo.consulted.load(Ordering::Relaxed), 2);
+
+        // A packet it doesn't match falls through to the rest of the registry
+        let elsewhere = testutil::Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 22).packet(b"");
+        assert_eq!(label_with(&registry, &elsewhere).as_deref(), Some("SSH"));
+        assert_eq!(echo.consulted.load(Ordering::Relaxed), 3);
+    }
+}
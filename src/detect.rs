@@ -0,0 +1,415 @@
+// Lightweight traffic detectors and the event log they report to. Each detector watches the
+// packet stream as it is ingested and returns an `Event` when something looks wrong.
+use crate::decode;
+use crate::model;
+use crate::network::PacketUpdate;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+// Everything the events pane reports: detector findings and changes in capture health
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    PortScan { src: Ipv4Addr, ports: usize, window: Duration },
+    RstStorm { src: Ipv4Addr, count: usize, window: Duration },
+    ZeroWindow { endpoint: (Ipv4Addr, u16), stalled: Duration },
+    NewHost(Ipv4Addr),
+    CaptureStopped(Option<i32>), // dumpcap's exit code, when it has been reaped
+    Shed(u64),                   // Packets evicted to stay under --max-memory
+}
+
+impl Event {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Event::PortScan { .. } | Event::CaptureStopped(_) => Severity::Critical,
+            Event::RstStorm { .. } | Event::ZeroWindow { .. } => Severity::Warning,
+            Event::Shed(_) | Event::NewHost(_) => Severity::Info,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Event::PortScan { src, ports, window } => format!("[SCAN] {} sent SYNs to {} ports in {}s", src, ports, window.as_secs()),
+            Event::RstStorm { src, count, window } => {
+                let rate = *count as f64 / window.as_secs_f64().max(1.0);
+                format!("[RST] {} sent {} RSTs in {}s ({:.1}/s)", src, count, window.as_secs(), rate)
+            }
+            Event::ZeroWindow { endpoint, stalled } => format!("[ZERO-WIN] {}:{} advertised a zero window for {:.1}s", endpoint.0, endpoint.1, stalled.as_secs_f64()),
+            Event::CaptureStopped(Some(code)) => format!("[CAPTURE] dumpcap exited with code {}", code),
+            Event::CaptureStopped(None) => "[CAPTURE] capture stopped".to_string(),
+            Event::NewHost(ip) => format!("[HOST] new host {} appeared", ip),
+            Event::Shed(n) => format!("[MEMORY] shed {} packets to stay under the memory limit", n),
+        }
+    }
+}
+
+// Timestamped events, oldest first, keeping only the newest `capacity`
+pub struct EventLog {
+    capacity: usize,
+    entries: VecDeque<(Duration, Event)>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> EventLog {
+        EventLog { capacity, entries: VecDeque::new() }
+    }
+
+    pub fn emit_event(&mut self, ts: Duration, event: Event) {
+        self.entries.push_back((ts, event));
+        if self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, (Duration, Event)> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+// Flags a source that sends bare SYNs to at least `ports` distinct destination ports within `window`
+pub struct ScanDetector {
+    pub ports: usize,
+    pub window: Duration,
+    syns: HashMap<Ipv4Addr, VecDeque<(Duration, u16)>>,
+    alerted: HashMap<Ipv4Addr, Duration>, // Last alert per source, so a scan is reported once per window
+}
+
+impl ScanDetector {
+    pub fn new(ports: usize, window: Duration) -> ScanDetector {
+        ScanDetector { ports, window, syns: HashMap::new(), alerted: HashMap::new() }
+    }
+
+    pub fn observe(&mut self, pkt: &PacketUpdate) -> Option<Event> {
+        // SYN set, ACK clear: a connection attempt rather than a handshake reply
+        if decode::tcp_flags(&pkt.raw_data)? & 0x12 != 0x02 {
+            return None;
+        }
+        let ip = &pkt.raw_data;
+        let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+        let (_, dport) = decode::ports(ip)?;
+
+        let recent = self.syns.entry(src).or_default();
+        recent.push_back((pkt.ts, dport));
+        while recent.front().is_some_and(|&(ts, _)| pkt.ts.saturating_sub(ts) > self.window) {
+            recent.pop_front();
+        }
+        let distinct: HashSet<u16> = recent.iter().map(|&(_, port)| port).collect();
+        if distinct.len() < self.ports {
+            return None;
+        }
+        if self.alerted.get(&src).is_some_and(|&last| pkt.ts.saturating_sub(last) <= self.window) {
+            return None;
+        }
+        self.alerted.insert(src, pkt.ts);
+        Some(Event::PortScan { src, ports: distinct.len(), window: self.window })
+    }
+
+    pub fn clear(&mut self) {
+        self.syns.clear();
+        self.alerted.clear();
+    }
+}
+
+// Flags a host sending at least `count` RSTs within `window`: a failing service refusing
+// connections, a scanner's probes being torn down, or a misbehaving middlebox
+pub struct RstDetector {
+    pub count: usize,
+    pub window: Duration,
+    resets: HashMap<Ipv4Addr, VecDeque<Duration>>,
+    alerted: HashMap<Ipv4Addr, Duration>,
+}
+
+impl RstDetector {
+    pub fn new(count: usize, window: Duration) -> RstDetector {
+        RstDetector { count, window, resets: HashMap::new(), alerted: HashMap::new() }
+    }
+
+    pub fn observe(&mut self, pkt: &PacketUpdate) -> Option<Event> {
+        if decode::tcp_flags(&pkt.raw_data)? & 0x04 == 0 {
+            return None;
+        }
+        let ip = &pkt.raw_data;
+        let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+        let recent = self.resets.entry(src).or_default();
+        recent.push_back(pkt.ts);
+        while recent.front().is_some_and(|&ts| pkt.ts.saturating_sub(ts) > self.window) {
+            recent.pop_front();
+        }
+        if recent.len() < self.count {
+            return None;
+        }
+        if self.alerted.get(&src).is_some_and(|&last| pkt.ts.saturating_sub(last) <= self.window) {
+            return None;
+        }
+        self.alerted.insert(src, pkt.ts);
+        Some(Event::RstStorm { src, count: recent.len(), window: self.window })
+    }
+
+    pub fn clear(&mut self) {
+        self.resets.clear();
+        self.alerted.clear();
+    }
+}
+
+// Which sources `NewHostDetector` reports; set with --new-hosts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NewHostScope {
+    #[default]
+    Off,
+    All,
+    Public, // Private, link-local and loopback sources are tracked but never reported
+}
+
+impl FromStr for NewHostScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "off" => Ok(NewHostScope::Off),
+            "all" => Ok(NewHostScope::All),
+            "public" => Ok(NewHostScope::Public),
+            _ => Err(format!("invalid --new-hosts: {} (expected off, all or public)", s)),
+        }
+    }
+}
+
+// Reports the first packet from each source address not seen before this session, e.g. a
+// device joining the network. Remembers every source, so memory grows with distinct hosts.
+pub struct NewHostDetector {
+    pub scope: NewHostScope,
+    known: HashSet<Ipv4Addr>,
+}
+
+impl NewHostDetector {
+    pub fn new(scope: NewHostScope) -> NewHostDetector {
+        NewHostDetector { scope, known: HashSet::new() }
+    }
+
+    pub fn observe(&mut self, pkt: &PacketUpdate) -> Option<Event> {
+        if self.scope == NewHostScope::Off {
+            return None;
+        }
+        let ip = pkt.raw_data.get(..20)?;
+        let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+        if !self.known.insert(src) || (self.scope == NewHostScope::Public && model::is_local(src)) {
+            return None;
+        }
+        Some(Event::NewHost(src))
+    }
+
+    pub fn clear(&mut self) {
+        self.known.clear();
+    }
+}
+
+// Counts duplicate ACKs: pure ACKs (no payload, no SYN/FIN/RST) repeating the ACK number their
+// direction last sent, which is how a receiver signals a hole ahead of fast retransmit. The first
+// repeat is dup 1; anything else from that direction resets the count.
+type Direction = ((Ipv4Addr, u16), (Ipv4Addr, u16)); // (src, dst)
+
+#[derive(Default)]
+pub struct DupAckTracker {
+    last: HashMap<Direction, (u32, u32)>, // ACK number last sent, and how many times since it was repeated
+}
+
+impl DupAckTracker {
+    pub fn observe(&mut self, pkt: &PacketUpdate) -> Option<u32> {
+        let ip = &pkt.raw_data;
+        let ack = decode::tcp_ack(ip)?;
+        let (sport, dport) = decode::ports(ip)?;
+        let direction = ((Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]), sport), (Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]), dport));
+        let flags = decode::tcp_flags(ip)?;
+        if flags & 0x05 != 0 {
+            self.last.remove(&direction);
+            return None;
+        }
+        let pure = flags & 0x02 == 0 && decode::transport_payload(ip).is_empty();
+        match self.last.get_mut(&direction) {
+            Some((last, repeats)) if pure && *last == ack => {
+                *repeats += 1;
+                Some(*repeats)
+            }
+            _ => {
+                self.last.insert(direction, (ack, 0));
+                None
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.last.clear();
+    }
+}
+
+// Flags a TCP endpoint that keeps advertising a zero window for at least `stall`: a receiver
+// that has stopped draining its socket. Reported once per stall; a non-zero window resets it.
+pub struct ZeroWindowDetector {
+    pub stall: Duration,
+    stalled: HashMap<(Ipv4Addr, u16), (Duration, bool)>, // Since when, and whether it was reported
+}
+
+impl ZeroWindowDetector {
+    pub fn new(stall: Duration) -> ZeroWindowDetector {
+        ZeroWindowDetector { stall, stalled: HashMap::new() }
+    }
+
+    pub fn observe(&mut self, pkt: &PacketUpdate) -> Option<Event> {
+        let ip = &pkt.raw_data;
+        decode::tcp_window(ip)?;
+        let (sport, _) = decode::ports(ip)?;
+        let endpoint = (Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]), sport);
+        if !decode::zero_window(ip) {
+            self.stalled.remove(&endpoint);
+            return None;
+        }
+        let (since, reported) = self.stalled.entry(endpoint).or_insert((pkt.ts, false));
+        let stalled_for = pkt.ts.saturating_sub(*since);
+        if *reported || stalled_for < self.stall {
+            return None;
+        }
+        *reported = true;
+        Some(Event::ZeroWindow { endpoint, stalled: stalled_for })
+    }
+
+    pub fn clear(&mut self) {
+        self.stalled.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, Tcp, ACK, RST, SYN};
+
+    const SCANNER: [u8; 4] = [10, 0, 0, 66];
+    const TARGET: [u8; 4] = [10, 0, 0, 2];
+
+    fn syn(src: [u8; 4], dport: u16, secs: u64) -> PacketUpdate {
+        testutil::update(Tcp::new(src, 50000, TARGET, dport).flags(SYN).packet(b""), secs)
+    }
+
+    #[test]
+    fn event_log_keeps_the_newest_in_order() {
+        let mut log = EventLog::new(3);
+        let mut scans = ScanDetector::new(3, Duration::from_secs(5));
+        for port in 1..=3 {
+            if let Some(event) = scans.observe(&syn(SCANNER, port, 2)) {
+                log.emit_event(Duration::from_secs(2), event);
+            }
+        }
+        log.emit_event(Duration::from_secs(1), Event::NewHost(Ipv4Addr::from(TARGET)));
+        log.emit_event(Duration::from_secs(3), Event::Shed(12));
+        log.emit_event(Duration::from_secs(4), Event::ZeroWindow { endpoint: (Ipv4Addr::from(TARGET), 80), stalled: Duration::from_millis(2500) });
+
+        // Kept in arrival order, not re-sorted by timestamp; the scan was pushed out by the fourth
+        let entries: Vec<(u64, Severity, String)> = log.iter().map(|(ts, e)| (ts.as_secs(), e.severity(), e.message())).collect();
+        assert_eq!(entries, [
+            (1, Severity::Info, "[HOST] new host 10.0.0.2 appeared".to_string()),
+            (3, Severity::Info, "[MEMORY] shed 12 packets to stay under the memory limit".to_string()),
+            (4, Severity::Warning, "[ZERO-WIN] 10.0.0.2:80 advertised a zero window for 2.5s".to_string()),
+        ]);
+        assert_eq!(Event::PortScan { src: Ipv4Addr::from(SCANNER), ports: 3, window: Duration::from_secs(5) }.severity(), Severity::Critical);
+    }
+
+    #[test]
+    fn scan_fires_on_many_ports_only() {
+        let mut detector = ScanDetector::new(10, Duration::from_secs(5));
+        let events: Vec<Event> = (1..=20).filter_map(|port| detector.observe(&syn(SCANNER, port, 1))).collect();
+        assert_eq!(events, [Event::PortScan { src: Ipv4Addr::from(SCANNER), ports: 10, window: Duration::from_secs(5) }]);
+
+        // A client reconnecting to one service, and the handshake replies, are normal traffic
+        let mut detector = ScanDetector::new(10, Duration::from_secs(5));
+        for i in 0..50 {
+            assert_eq!(detector.observe(&syn([10, 0, 0, 1], 443, i / 10)), None);
+            let reply = testutil::update(Tcp::new(TARGET, 443, [10, 0, 0, 1], 50000).flags(SYN | ACK).packet(b""), i / 10);
+            assert_eq!(detector.observe(&reply), None);
+        }
+    }
+
+    #[test]
+    fn rst_burst_from_one_host_alerts_once() {
+        let rst = |src: [u8; 4], secs: u64| testutil::update(Tcp::new(src, 80, TARGET, 40000).flags(RST | ACK).packet(b""), secs);
+        let mut detector = RstDetector::new(5, Duration::from_secs(2));
+
+        // One reset a few seconds apart is ordinary connection teardown
+        for secs in [0, 3, 6, 9, 12] {
+            assert_eq!(detector.observe(&rst(SCANNER, secs)), None);
+        }
+        let events: Vec<Event> = (0..10).filter_map(|_| detector.observe(&rst(SCANNER, 20))).collect();
+        assert_eq!(events, [Event::RstStorm { src: Ipv4Addr::from(SCANNER), count: 5, window: Duration::from_secs(2) }]);
+
+        // Other hosts are counted separately, and non-RST segments not at all
+        assert_eq!(detector.observe(&rst([10, 0, 0, 1], 20)), None);
+        assert_eq!(detector.observe(&testutil::update(Tcp::new(SCANNER, 80, TARGET, 40000).packet(b""), 20)), None);
+    }
+
+    #[test]
+    fn zero_window_is_flagged_once_stalled() {
+        let receiver = Tcp::new(TARGET, 80, SCANNER, 40000);
+        let full = |secs| testutil::update(Tcp { window: 0, ..receiver }.packet(b""), secs);
+        assert!(decode::zero_window(&full(0).raw_data));
+        assert!(!decode::zero_window(&testutil::update(receiver.packet(b""), 0).raw_data));
+        assert!(!decode::zero_window(&testutil::update(Tcp { window: 0, ..receiver }.flags(RST).packet(b""), 0).raw_data));
+
+        let mut detector = ZeroWindowDetector::new(Duration::from_secs(2));
+        assert_eq!(detector.observe(&full(10)), None);
+        assert_eq!(detector.observe(&full(12)), Some(Event::ZeroWindow { endpoint: (Ipv4Addr::from(TARGET), 80), stalled: Duration::from_secs(2) }));
+        assert_eq!(detector.observe(&full(13)), None);
+
+        // The window reopening ends the stall, so a normal segment never alerts
+        assert_eq!(detector.observe(&testutil::update(receiver.packet(b""), 14)), None);
+        assert_eq!(detector.observe(&full(15)), None);
+    }
+
+    #[test]
+    fn repeated_acks_count_as_duplicates() {
+        let receiver = Tcp { ack: 5000, ..Tcp::new(TARGET, 80, SCANNER, 40000) };
+        let ack = |tcp: Tcp, payload: &[u8]| testutil::update(tcp.packet(payload), 1);
+        let mut tracker = DupAckTracker::default();
+        let counts: Vec<Option<u32>> = (0..3).map(|_| tracker.observe(&ack(receiver, b""))).collect();
+        assert_eq!(counts, [None, Some(1), Some(2)]);
+
+        // Data carrying the same ACK isn't a duplicate, and moving the ACK on starts over
+        assert_eq!(tracker.observe(&ack(receiver, b"data")), None);
+        assert_eq!(tracker.observe(&ack(receiver, b"")), Some(1));
+        assert_eq!(tracker.observe(&ack(Tcp { ack: 6000, ..receiver }, b"")), None);
+        assert_eq!(tracker.observe(&ack(Tcp { ack: 6000, ..receiver }, b"")), Some(1));
+        // The other direction is tracked on its own
+        assert_eq!(tracker.observe(&ack(Tcp { ack: 6000, ..Tcp::new(SCANNER, 40000, TARGET, 80) }, b"")), None);
+    }
+
+    #[test]
+    fn only_the_first_packet_from_a_new_host_is_reported() {
+        let mut detector = NewHostDetector::new(NewHostScope::All);
+        let from = |src: [u8; 4], secs| testutil::update(Tcp::new(src, 50000, TARGET, 80).packet(b""), secs);
+        assert_eq!(detector.observe(&from(SCANNER, 0)), Some(Event::NewHost(Ipv4Addr::from(SCANNER))));
+        assert_eq!(detector.observe(&from(SCANNER, 1)), None);
+        assert_eq!(detector.observe(&from([10, 0, 0, 7], 2)), Some(Event::NewHost(Ipv4Addr::from([10, 0, 0, 7]))));
+
+        // Public scope still remembers local sources but never reports them
+        let mut public = NewHostDetector::new(NewHostScope::Public);
+        assert_eq!(public.observe(&from(SCANNER, 0)), None);
+        assert_eq!(public.observe(&from([8, 8, 8, 8], 1)), Some(Event::NewHost(Ipv4Addr::new(8, 8, 8, 8))));
+        assert_eq!(public.observe(&from([8, 8, 8, 8], 2)), None);
+    }
+}
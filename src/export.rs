@@ -0,0 +1,149 @@
+// Spreadsheet-friendly exports of the conversation table, and snapshots of the rendered feed
+use crate::decode;
+use crate::model::Conversation;
+use ratatui::style::{Color, Modifier};
+use ratatui::text::Line;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+pub const FLOW_CSV_HEADER: &str = "src,dst,proto,src_port,dst_port,packets,bytes,captured_bytes,first_ts,last_ts,duration";
+
+// RFC 4180 quoting: only fields containing a comma, quote or line break are wrapped, with quotes doubled
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn seconds(ts: Duration) -> String {
+    format!("{}.{:06}", ts.as_secs(), ts.subsec_micros())
+}
+
+// One row per conversation, oldest first; returns the number of rows written
+pub fn write_flow_csv<W: Write>(out: &mut W, conversations: &HashMap<String, Conversation>) -> io::Result<usize> {
+    let mut rows: Vec<&Conversation> = conversations.values().filter(|c| c.origin.is_some()).collect();
+    rows.sort_by_key(|c| (c.first_seen, c.origin.map(|o| (o.src, o.dst))));
+    writeln!(out, "{}", FLOW_CSV_HEADER)?;
+    for c in &rows {
+        let Some(origin) = c.origin else { continue };
+        writeln!(out, "{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&origin.src.0.to_string()), csv_field(&origin.dst.0.to_string()), csv_field(&decode::ip_protocol_name(origin.protocol)),
+            origin.src.1, origin.dst.1, c.packets, c.bytes, c.captured_bytes,
+            seconds(c.first_seen), seconds(c.last_seen), seconds(c.last_seen.saturating_sub(c.first_seen)))?;
+    }
+    out.flush()?;
+    Ok(rows.len())
+}
+
+// CSS colour for a terminal colour, using the xterm defaults; `Reset` leaves the page's own
+fn css_color(color: Color) -> Option<String> {
+    Some(match color {
+        Color::Reset => return None,
+        Color::Black => "#000000".to_string(),
+        Color::Red => "#cd0000".to_string(),
+        Color::Green => "#00cd00".to_string(),
+        Color::Yellow => "#cdcd00".to_string(),
+        Color::Blue => "#0000ee".to_string(),
+        Color::Magenta => "#cd00cd".to_string(),
+        Color::Cyan => "#00cdcd".to_string(),
+        Color::Gray => "#e5e5e5".to_string(),
+        Color::DarkGray => "#7f7f7f".to_string(),
+        Color::LightRed => "#ff0000".to_string(),
+        Color::LightGreen => "#00ff00".to_string(),
+        Color::LightYellow => "#ffff00".to_string(),
+        Color::LightBlue => "#5c5cff".to_string(),
+        Color::LightMagenta => "#ff00ff".to_string(),
+        Color::LightCyan => "#00ffff".to_string(),
+        Color::White => "#ffffff".to_string(),
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        Color::Indexed(_) => return None,
+    })
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// One rendered line as HTML: every styled span becomes a <span style=…>, unstyled text stays bare
+pub fn html_line(line: &Line) -> String {
+    let mut html = String::new();
+    for span in &line.spans {
+        let style = line.style.patch(span.style);
+        let mut css = Vec::new();
+        if let Some(fg) = style.fg.and_then(css_color) { css.push(format!("color:{}", fg)); }
+        if let Some(bg) = style.bg.and_then(css_color) { css.push(format!("background:{}", bg)); }
+        if style.add_modifier.contains(Modifier::BOLD) { css.push("font-weight:bold".to_string()); }
+        if style.add_modifier.contains(Modifier::ITALIC) { css.push("font-style:italic".to_string()); }
+        if style.add_modifier.contains(Modifier::UNDERLINED) { css.push("text-decoration:underline".to_string()); }
+        let text = html_escape(&span.content);
+        if css.is_empty() {
+            html.push_str(&text);
+        } else {
+            html.push_str(&format!("<span style=\"{}\">{}</span>", css.join(";"), text));
+        }
+    }
+    html
+}
+
+// A standalone page holding `lines` on the terminal's dark background
+pub fn write_html<W: Write>(out: &mut W, title: &str, lines: &[Line]) -> io::Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\"><title>{}</title></head>", html_escape(title))?;
+    writeln!(out, "<body style=\"background:#000000;color:#e5e5e5\"><pre>")?;
+    for line in lines {
+        writeln!(out, "{}", html_line(line))?;
+    }
+    writeln!(out, "</pre></body></html>")?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, Tcp};
+
+    #[test]
+    fn flow_csv_header_and_row_format() {
+        let client = Tcp::new([10, 0, 0, 1], 40000, [93, 184, 216, 34], 443);
+        let mut conversations = HashMap::new();
+        for (i, micros) in [1_500_000, 2_000_250].into_iter().enumerate() {
+            let mut update = testutil::update(client.packet(&[0; 10][..i * 10]), 0);
+            update.ts = Duration::from_micros(micros);
+            update.orig_len += 100; // Cut short by a snaplen
+            conversations.entry("web".to_string()).or_insert_with(|| Conversation::new(update.ts)).push(update);
+        }
+        conversations.insert("empty".to_string(), Conversation::new(Duration::ZERO));
+
+        let mut out = Vec::new();
+        assert_eq!(write_flow_csv(&mut out, &conversations).unwrap(), 1);
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n{}\n", FLOW_CSV_HEADER,
+            "10.0.0.1,93.184.216.34,TCP,40000,443,2,290,90,1.500000,2.000250,0.500250"));
+        assert_eq!(csv_field("say \"hi\", twice"), "\"say \"\"hi\"\", twice\"");
+    }
+
+    #[test]
+    fn html_snapshot_styles_each_span() {
+        use ratatui::style::Style;
+        use ratatui::text::Span;
+        let line = Line::from(vec![
+            Span::styled("★", Style::default().fg(Color::Yellow)),
+            Span::raw(" 12 "),
+            Span::styled("10.0.0.1 ➔ 10.0.0.2 [HTTP GET /a?b<c>&d]", Style::default().fg(Color::Rgb(0x12, 0xab, 0xef)).bg(Color::Black).add_modifier(Modifier::BOLD)),
+            Span::styled("idx", Style::default().fg(Color::Indexed(42))),
+        ]);
+        assert_eq!(html_line(&line), concat!(
+            "<span style=\"color:#cdcd00\">★</span> 12 ",
+            "<span style=\"color:#12abef;background:#000000;font-weight:bold\">10.0.0.1 ➔ 10.0.0.2 [HTTP GET /a?b&lt;c&gt;&amp;d]</span>",
+            "idx",
+        ));
+
+        let mut page = Vec::new();
+        write_html(&mut page, "vshark <feed>", &[line]).unwrap();
+        let page = String::from_utf8(page).unwrap();
+        assert!(page.contains("<title>vshark &lt;feed&gt;</title>"));
+        assert!(page.contains("<pre>\n<span style=\"color:#cdcd00\">★</span>"));
+    }
+}
@@ -0,0 +1,268 @@
+// Display filter engine. The search bar is parsed into a `Filter` expression:
+//
+//     tcp and (port 80 or port 443)     udp port 53     host 10.0.0.5 and not dns     hex deadbeef     iface wlan0
+//     src == 10.0.0.0/8 and not dst == 10.0.0.0/8
+//
+// Juxtaposed terms are ANDed; any word that isn't a keyword is a case-insensitive
+// substring match on the summary line, so plain text searches keep working.
+use crate::decode;
+use crate::network::PacketUpdate;
+use std::net::Ipv4Addr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    All,
+    Text(String),
+    Protocol(u8),
+    Port(u16),
+    Host(Ipv4Addr),
+    Src(Ipv4Addr, u8), // Source address within a prefix; /32 is a single host
+    Dst(Ipv4Addr, u8),
+    Bytes(Vec<u8>), // Raw byte pattern anywhere in the packet
+    Interface(String),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+impl Filter {
+    // Falls back to a plain substring match when the query isn't a valid expression
+    pub fn parse(query: &str) -> Filter {
+        let query = query.trim();
+        if query.is_empty() {
+            return Filter::All;
+        }
+        Parser::new(query).parse().unwrap_or_else(|_| Filter::Text(query.to_lowercase()))
+    }
+
+    pub fn matches(&self, pkt: &PacketUpdate) -> bool {
+        let ip = &pkt.raw_data;
+        match self {
+            Filter::All => true,
+            Filter::Text(t) => pkt.summary.to_lowercase().contains(t),
+            Filter::Protocol(p) => ip.get(9) == Some(p),
+            Filter::Port(port) => decode::ports(ip).is_some_and(|(s, d)| s == *port || d == *port),
+            Filter::Host(addr) => ip.len() >= 20 && (ip[12..16] == addr.octets() || ip[16..20] == addr.octets()),
+            Filter::Src(net, prefix) => ip.len() >= 20 && in_subnet(&ip[12..16], *net, *prefix),
+            Filter::Dst(net, prefix) => ip.len() >= 20 && in_subnet(&ip[16..20], *net, *prefix),
+            Filter::Bytes(pattern) => decode::find_bytes(ip, pattern).is_some(),
+            Filter::Interface(name) => pkt.interface.as_deref() == Some(name.as_str()),
+            Filter::Not(f) => !f.matches(pkt),
+            Filter::And(a, b) => a.matches(pkt) && b.matches(pkt),
+            Filter::Or(a, b) => a.matches(pkt) || b.matches(pkt),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(query: &'a str) -> Parser<'a> {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        for (i, c) in query.char_indices() {
+            match c {
+                '(' | ')' | ' ' => {
+                    if let Some(s) = start.take() { tokens.push(&query[s..i]); }
+                    if c != ' ' { tokens.push(&query[i..i + 1]); }
+                }
+                _ => { start.get_or_insert(i); }
+            }
+        }
+        if let Some(s) = start { tokens.push(&query[s..]); }
+        Parser { tokens, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let token = self.peek().ok_or("unexpected end of filter")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn parse(mut self) -> Result<Filter, String> {
+        let filter = self.expr()?;
+        match self.peek() {
+            None => Ok(filter),
+            Some(t) => Err(format!("unexpected '{}'", t)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Filter, String> {
+        let mut left = self.term()?;
+        while self.peek().is_some_and(|t| t.eq_ignore_ascii_case("or")) {
+            self.pos += 1;
+            left = Filter::Or(Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Filter, String> {
+        let mut left = self.factor()?;
+        while let Some(t) = self.peek() {
+            if t.eq_ignore_ascii_case("or") || t == ")" { break; }
+            if t.eq_ignore_ascii_case("and") { self.pos += 1; }
+            left = Filter::And(Box::new(left), Box::new(self.factor()?));
+        }
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> Result<Filter, String> {
+        let token = self.next()?;
+        let keyword = token.to_ascii_lowercase();
+        match keyword.as_str() {
+            "not" | "!" => Ok(Filter::Not(Box::new(self.factor()?))),
+            "(" => {
+                let inner = self.expr()?;
+                match self.next()? {
+                    ")" => Ok(inner),
+                    t => Err(format!("expected ')', found '{}'", t)),
+                }
+            }
+            "tcp" => Ok(Filter::Protocol(6)),
+            "udp" => Ok(Filter::Protocol(17)),
+            "icmp" => Ok(Filter::Protocol(1)),
+            "port" | "tcp.port" | "udp.port" => {
+                let value = self.value()?;
+                let port = value.parse().map_err(|_| format!("invalid port '{}'", value))?;
+                // `tcp.port == 80` also pins the transport protocol
+                Ok(match keyword.as_str() {
+                    "tcp.port" => Filter::And(Box::new(Filter::Protocol(6)), Box::new(Filter::Port(port))),
+                    "udp.port" => Filter::And(Box::new(Filter::Protocol(17)), Box::new(Filter::Port(port))),
+                    _ => Filter::Port(port),
+                })
+            }
+            "host" | "ip.addr" => {
+                let value = self.value()?;
+                Ok(Filter::Host(value.parse().map_err(|_| format!("invalid address '{}'", value))?))
+            }
+            "src" | "ip.src" | "dst" | "ip.dst" => {
+                let value = self.value()?;
+                let (addr, prefix) = value.split_once('/').unwrap_or((value, "32"));
+                let addr = addr.parse().map_err(|_| format!("invalid address '{}'", value))?;
+                let prefix = prefix.parse().ok().filter(|&p| p <= 32).ok_or(format!("invalid prefix '{}'", value))?;
+                Ok(if keyword.ends_with("src") { Filter::Src(addr, prefix) } else { Filter::Dst(addr, prefix) })
+            }
+            "hex" => Ok(Filter::Bytes(decode::parse_hex(self.value()?)?)),
+            "iface" | "interface" => Ok(Filter::Interface(self.value()?.to_string())),
+            ")" => Err("unbalanced ')'".to_string()),
+            _ => Ok(Filter::Text(token.to_lowercase())),
+        }
+    }
+
+    // Operand of a keyword, with optional `==`
+    fn value(&mut self) -> Result<&'a str, String> {
+        let token = self.next()?;
+        if token == "==" { self.next() } else { Ok(token) }
+    }
+}
+
+fn in_subnet(addr: &[u8], net: Ipv4Addr, prefix: u8) -> bool {
+    let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+    let addr = u32::from_be_bytes([addr[0], addr[1], addr[2], addr[3]]);
+    addr & mask == u32::from(net) & mask
+}
+
+// Filter expression isolating the conversation a packet belongs to, for "copy as filter"
+pub fn packet_filter(pkt: &PacketUpdate) -> String {
+    let ip = &pkt.raw_data;
+    if ip.len() < 20 {
+        return String::new();
+    }
+    let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+    let dst = Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]);
+    let mut expr = format!("ip.addr == {} and ip.addr == {}", src, dst);
+    if let Some((sport, dport)) = decode::ports(ip) {
+        let proto = if ip[9] == 6 { "tcp" } else { "udp" };
+        // The lower port is almost always the service side
+        expr.push_str(&format!(" and {}.port == {}", proto, sport.min(dport)));
+    }
+    expr
+}
+
+// Filter text for a hex-search pattern, in the compact form the parser accepts
+pub fn hex_filter(pattern: &[u8]) -> String {
+    let hex: String = pattern.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("hex {}", hex)
+}
+
+// Quick-action filter for one port, e.g. from the selected conversation
+pub fn port_filter(port: u16) -> String {
+    format!("port {}", port)
+}
+
+// Quick-action filter for traffic leaving (or, with `outbound` false, arriving at) one host
+pub fn direction_filter(host: Ipv4Addr, outbound: bool) -> String {
+    format!("{} == {}", if outbound { "src" } else { "dst" }, host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, Tcp};
+
+    fn to(dport: u16) -> PacketUpdate {
+        testutil::update(Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], dport).packet(b""), 1)
+    }
+
+    #[test]
+    fn port_filter_matches_either_end() {
+        let filter = Filter::parse(&port_filter(443));
+        assert_eq!(filter, Filter::Port(443));
+        assert!(filter.matches(&to(443)));
+        assert!(filter.matches(&testutil::update(Tcp::new([10, 0, 0, 2], 443, [10, 0, 0, 1], 40000).packet(b""), 1)));
+        assert!(!filter.matches(&to(80)));
+    }
+
+    #[test]
+    fn direction_filters_tell_src_from_dst() {
+        let host = Ipv4Addr::new(10, 0, 0, 1);
+        let (outbound, inbound) = (to(80), testutil::update(Tcp::new([10, 0, 0, 2], 80, [10, 0, 0, 1], 40000).packet(b""), 1));
+        let from_host = Filter::parse(&direction_filter(host, true));
+        assert_eq!(from_host, Filter::Src(host, 32));
+        assert!(from_host.matches(&outbound));
+        assert!(!from_host.matches(&inbound));
+
+        let to_host = Filter::parse(&direction_filter(host, false));
+        assert_eq!(to_host, Filter::Dst(host, 32));
+        assert!(to_host.matches(&inbound));
+        assert!(!to_host.matches(&outbound));
+    }
+
+    #[test]
+    fn hex_pattern_found_in_packet() {
+        let pattern = decode::parse_hex("de:ad be ef").unwrap();
+        assert_eq!(pattern, [0xde, 0xad, 0xbe, 0xef]);
+        assert!(decode::parse_hex("dea").is_err());
+        assert!(decode::parse_hex("zz").is_err());
+
+        let pkt = testutil::update(Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80).packet(&[0, 1, 0xde, 0xad, 0xbe, 0xef]), 1);
+        assert_eq!(decode::find_bytes(&pkt.raw_data, &pattern), Some(42));
+        let filter = Filter::parse(&hex_filter(&pattern));
+        assert_eq!(filter, Filter::Bytes(pattern));
+        assert!(filter.matches(&pkt));
+        assert!(!filter.matches(&to(80)));
+    }
+
+    #[test]
+    fn packet_filter_isolates_the_conversation() {
+        let request = to(443);
+        let expr = packet_filter(&request);
+        assert_eq!(expr, "ip.addr == 10.0.0.1 and ip.addr == 10.0.0.2 and tcp.port == 443");
+        let filter = Filter::parse(&expr);
+        assert!(filter.matches(&request));
+        assert!(filter.matches(&testutil::update(Tcp::new([10, 0, 0, 2], 443, [10, 0, 0, 1], 40000).packet(b""), 1)));
+        assert!(!filter.matches(&to(80)));
+
+        let dns = testutil::update(testutil::udp([10, 0, 0, 1], 5353, [10, 0, 0, 53], 53, b""), 1);
+        assert_eq!(packet_filter(&dns), "ip.addr == 10.0.0.1 and ip.addr == 10.0.0.53 and udp.port == 53");
+        let icmp = testutil::update(testutil::ipv4(1, [10, 0, 0, 1], [10, 0, 0, 2], &[8, 0, 0, 0]), 1);
+        assert_eq!(packet_filter(&icmp), "ip.addr == 10.0.0.1 and ip.addr == 10.0.0.2");
+    }
+}
@@ -0,0 +1,43 @@
+use nucleo::pattern::{CaseMatching, Pattern};
+use nucleo::{Config, Matcher};
+
+// A candidate's text, remembering where it came from
+struct Candidate<'a>(usize, &'a str);
+
+impl AsRef<str> for Candidate<'_> {
+    fn as_ref(&self) -> &str {
+        self.1
+    }
+}
+
+// Ranks candidates against a fuzzy query, best match first, as indices into `candidates`. Non-matches are dropped.
+// `prefer_prefix` breaks ties toward earlier matches, so "168" puts 192.168.1.1 above 10.1.2.168.
+pub fn rank(query: &str, candidates: &[String]) -> Vec<usize> {
+    let mut config = Config::DEFAULT;
+    config.prefer_prefix = true;
+    let mut matcher = Matcher::new(config);
+
+    Pattern::parse(query, CaseMatching::Ignore)
+        .match_list(candidates.iter().enumerate().map(|(i, c)| Candidate(i, c)), &mut matcher)
+        .into_iter()
+        .map(|(candidate, _)| candidate.0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn earlier_match_ranks_first() {
+        let candidates = ["10.1.2.168".to_string(), "192.168.1.1".to_string()];
+        let ranked = rank("168", &candidates);
+        assert_eq!(ranked, vec![1, 0]);
+    }
+
+    #[test]
+    fn ports_in_candidate_text_match() {
+        let candidates = ["10.0.0.5 ➔ 8.8.8.8 :40000 :53".to_string(), "10.0.0.5 ➔ 1.1.1.1 :40001 :443".to_string()];
+        assert_eq!(rank(":443", &candidates), vec![1]);
+    }
+}
@@ -0,0 +1,99 @@
+// Post-decode hooks: each ingested packet is offered to the registered hooks before it's filed
+// and drawn, and any label a hook returns is shown in front of the packet's protocol tag, where the
+// theme's `color.<LABEL>` picks it up. Labels are kept apart from the tag, so "inspect as" retags
+// keep them and the protocol histogram doesn't count them. Rules from the config file come as `tag.<LABEL> = <display filter>`:
+//
+//     tag.DB = host 10.0.0.9 and port 5432
+//
+// Hooks written in Rust implement `PacketHook` and are added in `Hooks::new`.
+use crate::filter::Filter;
+use crate::network::PacketUpdate;
+
+pub trait PacketHook {
+    fn label(&self, pkt: &PacketUpdate) -> Option<String>;
+}
+
+// A config `tag.<LABEL>` rule: packets matching the filter get the label
+pub struct FilterTag {
+    pub label: String,
+    pub filter: Filter,
+}
+
+impl PacketHook for FilterTag {
+    fn label(&self, pkt: &PacketUpdate) -> Option<String> {
+        self.filter.matches(pkt).then(|| self.label.clone())
+    }
+}
+
+#[derive(Default)]
+pub struct Hooks {
+    hooks: Vec<Box<dyn PacketHook>>,
+}
+
+impl Hooks {
+    pub fn new(rules: &[(String, String)]) -> Hooks {
+        let mut hooks = Hooks::default();
+        for (label, filter) in rules {
+            hooks.register(Box::new(FilterTag { label: label.clone(), filter: Filter::parse(filter) }));
+        }
+        hooks
+    }
+
+    pub fn register(&mut self, hook: Box<dyn PacketHook>) {
+        self.hooks.push(hook);
+    }
+
+    // Labels from every hook that fires, in registration order, e.g. "DB HTTPS"
+    pub fn apply(&self, pkt: &mut PacketUpdate) {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let labels: Vec<String> = self.hooks.iter().filter_map(|hook| hook.label(pkt)).collect();
+        if !labels.is_empty() {
+            pkt.set_labels(labels);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::DecodeAs;
+    use crate::testutil::{self, Tcp};
+
+    // A hook written in Rust: anything to the given port
+    struct PortHook(u16, &'static str);
+
+    impl PacketHook for PortHook {
+        fn label(&self, pkt: &PacketUpdate) -> Option<String> {
+            crate::decode::ports(&pkt.raw_data).is_some_and(|(_, d)| d == self.0).then(|| self.1.to_string())
+        }
+    }
+
+    #[test]
+    fn registered_hook_labels_matching_packets() {
+        let mut hooks = Hooks::new(&[("DB".to_string(), "host 10.0.0.9".to_string())]);
+        hooks.register(Box::new(PortHook(5432, "PG")));
+        let to = |dst, dport| {
+            let mut pkt = testutil::update(Tcp::new([10, 0, 0, 1], 40000, dst, dport).packet(b""), 1);
+            pkt.retag("TCP".to_string());
+            hooks.apply(&mut pkt);
+            pkt
+        };
+
+        let db = to([10, 0, 0, 9], 5432);
+        assert_eq!(db.labels, ["DB", "PG"]);
+        assert_eq!(db.summary, "10.0.0.1 ➔ 10.0.0.9 [DB PG TCP]");
+        assert_eq!(db.tag.as_deref(), Some("TCP")); // The protocol tag, which the histogram counts, is untouched
+
+        let other = to([10, 0, 0, 2], 80);
+        assert!(other.labels.is_empty());
+        assert_eq!(other.summary, "10.0.0.1 ➔ 10.0.0.2 [TCP]");
+
+        // An "inspect as" retag replaces the protocol, not the labels
+        let mut forced = to([10, 0, 0, 9], 80);
+        forced.retag(DecodeAs::Http.name().to_string());
+        assert_eq!(forced.summary, "10.0.0.1 ➔ 10.0.0.9 [DB HTTP]");
+        assert_eq!(forced.display_tag().as_deref(), Some("DB HTTP"));
+    }
+}
@@ -0,0 +1,118 @@
+// Minimal JSON validator and pretty-printer for the inspector. Input that isn't a single
+// well-formed JSON value yields None, so callers can fall back to showing raw bytes.
+const MAX_DEPTH: usize = 64;
+
+pub fn pretty(text: &str) -> Option<String> {
+    let mut printer = Printer { src: text.as_bytes(), pos: 0, out: String::new() };
+    printer.value(0)?;
+    printer.skip_whitespace();
+    (printer.pos == printer.src.len()).then_some(printer.out)
+}
+
+struct Printer<'a> {
+    src: &'a [u8],
+    pos: usize,
+    out: String,
+}
+
+impl Printer<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.src.get(self.pos).is_some_and(|b| b" \t\r\n".contains(b)) {
+            self.pos += 1;
+        }
+    }
+
+    fn newline(&mut self, depth: usize) {
+        self.out.push('\n');
+        self.out.push_str(&"  ".repeat(depth));
+    }
+
+    fn value(&mut self, depth: usize) -> Option<()> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.skip_whitespace();
+        match *self.src.get(self.pos)? {
+            b'{' => self.container(depth, b'}', true),
+            b'[' => self.container(depth, b']', false),
+            b'"' => self.string(),
+            b't' => self.literal("true"),
+            b'f' => self.literal("false"),
+            b'n' => self.literal("null"),
+            b'-' | b'0'..=b'9' => self.number(),
+            _ => None,
+        }
+    }
+
+    fn container(&mut self, depth: usize, close: u8, object: bool) -> Option<()> {
+        self.out.push(self.src[self.pos] as char);
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.src.get(self.pos) == Some(&close) {
+            self.pos += 1;
+            self.out.push(close as char);
+            return Some(());
+        }
+        loop {
+            self.newline(depth + 1);
+            if object {
+                self.skip_whitespace();
+                if self.src.get(self.pos) != Some(&b'"') { return None; }
+                self.string()?;
+                self.skip_whitespace();
+                if self.src.get(self.pos) != Some(&b':') { return None; }
+                self.pos += 1;
+                self.out.push_str(": ");
+            }
+            self.value(depth + 1)?;
+            self.skip_whitespace();
+            match *self.src.get(self.pos)? {
+                b',' => { self.pos += 1; self.out.push(','); }
+                c if c == close => {
+                    self.pos += 1;
+                    self.newline(depth);
+                    self.out.push(close as char);
+                    return Some(());
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    // Copied through verbatim, escapes included
+    fn string(&mut self) -> Option<()> {
+        let start = self.pos;
+        self.pos += 1;
+        loop {
+            match *self.src.get(self.pos)? {
+                b'"' => break,
+                b'\\' => self.pos += 2,
+                c if c < 0x20 => return None,
+                _ => self.pos += 1,
+            }
+        }
+        self.pos += 1;
+        self.out.push_str(std::str::from_utf8(&self.src[start..self.pos]).ok()?);
+        Some(())
+    }
+
+    fn literal(&mut self, word: &str) -> Option<()> {
+        if !self.src[self.pos..].starts_with(word.as_bytes()) {
+            return None;
+        }
+        self.pos += word.len();
+        self.out.push_str(word);
+        Some(())
+    }
+
+    fn number(&mut self) -> Option<()> {
+        let start = self.pos;
+        while self.src.get(self.pos).is_some_and(|b| b"+-.eE0123456789".contains(b)) {
+            self.pos += 1;
+        }
+        let number = std::str::from_utf8(&self.src[start..self.pos]).ok()?;
+        number.parse::<f64>().ok()?;
+        self.out.push_str(number);
+        Some(())
+    }
+}
@@ -0,0 +1,77 @@
+// `--debug` diagnostics, as `tracing` events. Records go to a file, never the terminal, so they can't
+// corrupt the TUI:
+//
+//     12.034s DEBUG vshark::network: record dropped: not IPv4 (ethertype 0x86dd)
+use std::{fs::File, io, path::Path, sync::Mutex};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::fmt::{self, time::Uptime, MakeWriter};
+
+// Plain-text records at DEBUG and above, timed from startup
+fn subscriber<W>(writer: W) -> impl Subscriber + Send + Sync
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    fmt::fmt().with_writer(writer).with_ansi(false).with_timer(Uptime::default()).with_max_level(Level::DEBUG).finish()
+}
+
+// Installs the file subscriber; without it every `tracing` event is a no-op
+pub fn init(path: &Path) -> io::Result<()> {
+    let file = File::create(path)?;
+    tracing::subscriber::set_global_default(subscriber(Mutex::new(file))).map_err(|e| io::Error::other(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TimeRange;
+    use crate::network::{self, CaptureEvent, LinkOptions};
+    use crate::testutil;
+    use std::io::Write;
+    use std::sync::{mpsc, Arc};
+    use std::time::Duration;
+
+    // A log sink the test can read back
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Captured {
+        type Writer = Captured;
+
+        fn make_writer(&'a self) -> Captured {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn bad_packet_is_logged() {
+        let captured = Captured::default();
+
+        // An IPv6 EtherType in front of a payload that isn't IPv4 either
+        let mut frame = testutil::ethernet([2, 0, 0, 0, 0, 1], [2, 0, 0, 0, 0, 2], &[0x60; 40]);
+        frame[12..14].copy_from_slice(&[0x86, 0xdd]);
+        let pcap = testutil::pcap(1, &[(Duration::from_secs(7), &frame)]);
+        let (tx, rx) = mpsc::channel();
+        tracing::subscriber::with_default(subscriber(captured.clone()), || {
+            network::read_pcap(pcap.as_slice(), &tx, &TimeRange::default(), LinkOptions::default());
+        });
+        assert!(matches!(rx.try_iter().collect::<Vec<_>>().as_slice(), [CaptureEvent::Dropped(_)]));
+
+        let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = log.lines().filter(|l| l.contains("DEBUG vshark::network:")).collect();
+        let position = |message: &str| lines.iter().position(|l| l.contains(message));
+        let (read, dropped, sent) = (position("record 1 at 7s: caplen 54, origlen 54"), position("record dropped: not IPv4 (ethertype 0x86dd)"), position("record 1 sent"));
+        assert!(read.is_some() && dropped.is_some() && sent.is_some(), "{}", log);
+        assert!(read < dropped && dropped < sent);
+    }
+}
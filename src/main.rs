@@ -1,87 +1,508 @@
+mod action;
+mod clipboard;
+mod config;
+mod decode;
+mod detect;
+mod export;
+mod filter;
+mod fuzzy;
+mod hooks;
+mod json;
+mod logging;
+mod model;
 mod network;
+mod selftest;
+mod text;
+#[cfg(test)]
+mod testutil;
+mod ui;
 
-use crate::network::PacketUpdate;
+use crate::filter::Filter;
+use crate::model::{ArpTable, Bookmarks, Conversation};
+use crate::network::{CaptureEvent, PacketUpdate};
+use crate::ui::{ColorMode, LayoutMode, TextInput, TimeFormat};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    symbols,
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Sparkline, Wrap},
+    widgets::{Axis, Block, Borders, Chart, Clear, Dataset, Gauge, GraphType, List, ListItem, ListState, Paragraph, Sparkline, Wrap},
     Terminal,
 };
-use std::{collections::HashMap, io, sync::mpsc, time::{Duration, Instant}};
+use std::{collections::{HashMap, HashSet}, io::{self, IsTerminal}, net::Ipv4Addr, sync::{atomic::Ordering, mpsc}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 
-// Helper: Formats raw bytes into a "Hex + ASCII" view
-fn format_hex(data: &[u8]) -> String {
+// Helper: Formats raw bytes into an "Offset + Hex + ASCII" view, `width` bytes per line
+fn format_hex(data: &[u8], width: usize) -> String {
     let mut output = String::new();
-    for chunk in data.chunks(16) {
+    for (line, chunk) in data.chunks(width).enumerate() {
+        output.push_str(&format!("{:04x}  ", line * width));
         for byte in chunk {
             output.push_str(&format!("{:02x} ", byte));
         }
-        if chunk.len() < 16 {
-            for _ in 0..(16 - chunk.len()) { output.push_str("   "); }
+        if chunk.len() < width {
+            for _ in 0..(width - chunk.len()) { output.push_str("   "); }
         }
         output.push_str(" | ");
-        for byte in chunk {
-            if byte.is_ascii_graphic() || *byte == b' ' {
-                output.push(*byte as char);
-            } else {
-                output.push('.');
-            }
-        }
+        output.extend(chunk.iter().map(|&b| text::printable(b)));
         output.push('\n');
     }
     output
 }
 
+// Points the inspector at the selected stream's newest packet, unless it's locked to the one it shows
+fn follow_selection(inspected: &mut Option<PacketUpdate>, selected: Option<&String>, conversations: &HashMap<String, Conversation>, locked: bool) {
+    if let Some(target) = selected.filter(|_| !locked) {
+        if let Some(last_pkt) = conversations.get(target).and_then(|c| c.messages.back()) {
+            *inspected = Some(last_pkt.clone());
+        }
+    }
+}
+
+// Feed rows: the selected stream's packets, or with nothing selected whatever the display filter matches
+fn feed_packets<'a>(history: &'a [PacketUpdate], selected: Option<&String>, filter: &Filter, merges: &model::Merges, active_since: Duration) -> Vec<&'a PacketUpdate> {
+    history.iter()
+        .filter(|pkt| pkt.ts >= active_since)
+        .filter(|pkt| match selected {
+            Some(target) => merges.resolve(model::stream_key(&pkt.summary)) == target,
+            None => filter.matches(pkt),
+        })
+        .collect()
+}
+
+// Alternate screen, plus mouse reporting unless --no-mouse leaves selection to the terminal
+fn enter_screen<W: io::Write>(out: &mut W, mouse: bool) -> io::Result<()> {
+    execute!(out, EnterAlternateScreen)?;
+    if mouse {
+        execute!(out, EnableMouseCapture)?;
+    }
+    Ok(())
+}
+
+fn leave_screen<W: io::Write>(out: &mut W, mouse: bool) -> io::Result<()> {
+    execute!(out, LeaveAlternateScreen)?;
+    if mouse {
+        execute!(out, DisableMouseCapture)?;
+    }
+    Ok(())
+}
+
+const FEED_LEN: usize = 50;
+
+// What a capture accumulates. A confirmed `c` throws all of it away, as does restarting without keeping state.
+struct Session {
+    conversations: HashMap<String, Conversation>,
+    pinned: HashSet<String>,
+    capture_start: Option<Duration>,
+    tag_totals: HashMap<String, u64>,
+    arp_table: ArpTable,
+    dns_tracker: model::DnsTracker,
+    bookmarks: Bookmarks,
+    scan_detector: detect::ScanDetector,
+    rst_detector: detect::RstDetector,
+    zero_window_detector: detect::ZeroWindowDetector,
+    dup_acks: detect::DupAckTracker,
+    new_host_detector: detect::NewHostDetector,
+    events: detect::EventLog,
+    chat_history: Vec<PacketUpdate>,
+}
+
+impl Session {
+    fn new(config: &config::Config) -> Session {
+        Session {
+            conversations: HashMap::new(),
+            pinned: HashSet::new(),
+            capture_start: None,
+            tag_totals: HashMap::new(),
+            arp_table: ArpTable::default(),
+            dns_tracker: model::DnsTracker::new(model::DNS_QUERY_TIMEOUT, model::DNS_TRACKED),
+            bookmarks: Bookmarks::default(),
+            scan_detector: config.scan_detector(),
+            rst_detector: config.rst_detector(),
+            zero_window_detector: config.zero_window_detector(),
+            dup_acks: detect::DupAckTracker::default(),
+            new_host_detector: config.new_host_detector(),
+            events: detect::EventLog::new(100),
+            chat_history: Vec::new(),
+        }
+    }
+
+    // Files a packet under its stream; the feed only shows the last FEED_LEN of them
+    fn record(&mut self, stream: String, update: PacketUpdate) {
+        self.chat_history.push(update.clone());
+        if self.chat_history.len() > FEED_LEN { self.chat_history.remove(0); }
+        self.conversations.entry(stream).or_insert_with(|| Conversation::new(update.ts)).push(update);
+    }
+}
+
+// `c`, confirmed: drops every captured packet and what was learned from them. Detectors keep their thresholds.
+fn clear_session(session: &mut Session, selected_stream: &mut Option<String>) {
+    session.capture_start = None;
+    session.conversations.clear();
+    session.pinned.clear();
+    session.tag_totals.clear();
+    session.arp_table = ArpTable::default();
+    session.bookmarks = Bookmarks::default();
+    session.scan_detector.clear();
+    session.rst_detector.clear();
+    session.zero_window_detector.clear();
+    session.dup_acks.clear();
+    session.new_host_detector.clear();
+    session.events.clear();
+    session.dns_tracker.clear();
+    session.chat_history.clear();
+    *selected_stream = None;
+}
+
+// `u`: back to the unfiltered "All" feed; captured data is untouched
+fn reset_view(selected_stream: &mut Option<String>, search_query: &mut TextInput) {
+    *selected_stream = None;
+    search_query.clear();
+}
+
+// Esc backs out one level at a time: the search bar (dropping its query but keeping the selection),
+// then the compact inspector, then the selected stream and any merge in progress
+fn escape(searching: &mut bool, search_query: &mut TextInput, inspector_popup: &mut bool, selected_stream: &mut Option<String>, merge_pending: &mut Option<String>) {
+    if *searching {
+        *searching = false;
+        search_query.clear();
+    } else if *inspector_popup {
+        *inspector_popup = false;
+    } else {
+        *selected_stream = None;
+        *merge_pending = None;
+    }
+}
+
+// Installs a freshly started capture, or on failure marks it stopped with the reason for the banner
+fn start_capture(started: io::Result<network::Sniffer>, sniffer: &mut Option<network::Sniffer>, capture_stopped: &mut Option<Option<i32>>, capture_error: &mut Option<String>) {
+    match started {
+        Ok(s) => { *sniffer = Some(s); *capture_stopped = None; *capture_error = None; }
+        Err(e) => { *sniffer = None; *capture_stopped = Some(None); *capture_error = Some(e.to_string()); }
+    }
+}
+
+// What the inspector dumps: the whole IP packet, or with `P` only what follows the transport header
+fn inspector_bytes(pkt: &PacketUpdate, payload_only: bool) -> &[u8] {
+    if payload_only { decode::transport_payload(&pkt.raw_data) } else { &pkt.raw_data }
+}
+
+// The same dump as `format_hex`, with the bytes in `selection` highlighted in both columns
+fn hex_lines(data: &[u8], width: usize, selection: std::ops::Range<usize>) -> Vec<Line<'static>> {
+    let highlight = Style::default().add_modifier(Modifier::REVERSED);
+    data.chunks(width).enumerate().map(|(line, chunk)| {
+        let offset = line * width;
+        let mut spans = vec![Span::raw(format!("{:04x}  ", offset))];
+        for (i, byte) in chunk.iter().enumerate() {
+            let style = if selection.contains(&(offset + i)) { highlight } else { Style::default() };
+            spans.push(Span::styled(format!("{:02x}", byte), style));
+            spans.push(Span::raw(" "));
+        }
+        spans.push(Span::raw(format!("{} | ", "   ".repeat(width - chunk.len()))));
+        for (i, &byte) in chunk.iter().enumerate() {
+            let style = if selection.contains(&(offset + i)) { highlight } else { Style::default() };
+            spans.push(Span::styled(text::printable(byte).to_string(), style));
+        }
+        Line::from(spans)
+    }).collect()
+}
+
+// Side-by-side dump of two packets, `width` bytes per side, with differing bytes in red
+fn diff_lines(a: &[u8], b: &[u8], width: usize) -> Vec<Line<'static>> {
+    let differs: HashSet<usize> = ui::diff_offsets(a, b).into_iter().collect();
+    let side = |data: &[u8], offset: usize| -> Vec<Span<'static>> {
+        (offset..offset + width).map(|i| match data.get(i) {
+            Some(byte) => Span::styled(format!("{:02x} ", byte), if differs.contains(&i) { Style::default().fg(Color::Red).add_modifier(Modifier::BOLD) } else { Style::default() }),
+            None => Span::raw("   "),
+        }).collect()
+    };
+    (0..a.len().max(b.len())).step_by(width).map(|offset| {
+        let mut spans = vec![Span::styled(format!("{:04x}  ", offset), Style::default().fg(Color::DarkGray))];
+        spans.extend(side(a, offset));
+        spans.push(Span::raw("│ "));
+        spans.extend(side(b, offset));
+        Line::from(spans)
+    }).collect()
+}
+
+// Helper: Carves a centered popup area out of the full frame
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage((100 - percent_y) / 2), Constraint::Percentage(percent_y), Constraint::Percentage((100 - percent_y) / 2)])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage((100 - percent_x) / 2), Constraint::Percentage(percent_x), Constraint::Percentage((100 - percent_x) / 2)])
+        .split(vertical[1])[1]
+}
+
+// Helper: The capture loop without a terminal. Runs until `duration` has passed on `clock` or the
+// capture ends, and returns what it collected.
+fn run_headless(rx: &mpsc::Receiver<CaptureEvent>, duration: Option<Duration>, memory_limit: usize, clock: &mut impl network::Clock) -> (HashMap<String, Conversation>, model::Accounting) {
+    let mut conversations: HashMap<String, Conversation> = HashMap::new();
+    let mut accounting = model::Accounting::default();
+    let deadline = duration.map(|d| clock.now() + d);
+    while deadline.is_none_or(|d| clock.now() < d) {
+        match rx.try_recv() {
+            Ok(CaptureEvent::Packet(update)) => {
+                accounting.processed += 1;
+                conversations.entry(model::stream_key(&update.summary).to_string()).or_insert_with(|| Conversation::new(update.ts)).push(update);
+            }
+            Ok(CaptureEvent::Binding(..)) => accounting.processed += 1,
+            Ok(CaptureEvent::Dropped(reason)) => accounting.record_drop(reason, 1),
+            // Each live reader sends its own Stopped; the run ends once all of them have hung up
+            Ok(CaptureEvent::Stopped(_)) => {}
+            Ok(CaptureEvent::Loaded) | Err(mpsc::TryRecvError::Disconnected) => break,
+            Err(mpsc::TryRecvError::Empty) => {
+                accounting.record_drop(model::DROP_SHED, model::shed_to_limit(&mut conversations, memory_limit));
+                clock.sleep(Duration::from_millis(10));
+            }
+        }
+    }
+    (conversations, accounting)
+}
+
+// Helper: Arrow-select the interface to capture on; Esc or q keeps the default "any". Keys come from
+// `next_event`, which is `event::read` outside of tests.
+fn pick_interface<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, interfaces: &[String], mut next_event: impl FnMut() -> io::Result<Event>) -> io::Result<Option<String>> {
+    let mut state = ListState::default();
+    state.select(Some(0));
+    loop {
+        terminal.draw(|f| {
+            let area = centered_rect(50, 60, f.size());
+            let items: Vec<ListItem> = interfaces.iter().map(|i| ListItem::new(i.as_str()).style(Style::default().fg(Color::Cyan))).collect();
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(List::new(items)
+                .block(Block::default().title(" Capture on ── Enter to start, Esc for any ").borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow)))
+                .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow))
+                .highlight_symbol(">> "), area, &mut state);
+        })?;
+        if let Event::Key(key) = next_event()? {
+            let i = state.selected().unwrap_or(0);
+            match key.code {
+                KeyCode::Enter => return Ok(interfaces.get(i).cloned()),
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+                KeyCode::Down => state.select(Some((i + 1) % interfaces.len())),
+                KeyCode::Up => state.select(Some(if i == 0 { interfaces.len() - 1 } else { i - 1 })),
+                _ => {}
+            }
+        }
+    }
+}
+
+// Helper: Writes the --export-csv flow table, if one was asked for
+fn export_on_exit(config: &config::Config, conversations: &HashMap<String, Conversation>) -> io::Result<()> {
+    if let Some(path) = &config.export_csv {
+        let rows = export::write_flow_csv(&mut io::BufWriter::new(std::fs::File::create(path)?), conversations)?;
+        println!("wrote {} flows to {}", rows, path.display());
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), io::Error> {
+    // --self-test reports a config that fails to load as one of its checks, instead of exiting here
+    let (mut config, load_error) = match config::Config::load() {
+        Ok(config) => (config, None),
+        Err(e) if std::env::args().any(|arg| arg == "--self-test") => (config::Config::default(), Some(e)),
+        Err(e) => {
+            eprintln!("vshark: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Some(path) = &config.debug_log {
+        if let Err(e) = logging::init(path) {
+            eprintln!("vshark: cannot open {}: {}", path.display(), e);
+            std::process::exit(2);
+        }
+    }
+    if config.self_test || load_error.is_some() {
+        std::process::exit(selftest::report(&selftest::run(&selftest::System, &config, load_error.as_deref())));
+    }
+    let mut capture_sources = Vec::new();
+    for path in &config.read {
+        match network::open_capture(path) {
+            Ok(source) => capture_sources.push(source),
+            Err(e) => {
+                eprintln!("vshark: cannot read {}: {}", path.display(), e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // Headless: no terminal at all, for scripted and cron-style captures. Dropping the sniffer kills dumpcap.
+    if config.headless {
+        let (tx, rx) = mpsc::channel::<CaptureEvent>();
+        let sniffer = if capture_sources.is_empty() { Some(network::run_sniffer(tx, &config)?) } else { network::run_reader(capture_sources, tx, &config); None };
+        let (conversations, accounting) = run_headless(&rx, config.duration, config.memory_limit(), &mut network::WallClock::start());
+        drop(sniffer);
+        println!("{} conversations · {}", conversations.len(), accounting.summary());
+        return export_on_exit(&config, &conversations);
+    }
+
     // 1. Terminal Setup
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    enter_screen(&mut stdout, config.mouse_capture())?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
+    // First run with nothing configured: ask which NIC to capture on rather than assuming "any"
+    if capture_sources.is_empty() && config.interfaces.is_empty() && io::stdout().is_terminal() {
+        let interfaces = network::list_interfaces().unwrap_or_default();
+        if !interfaces.is_empty() {
+            if let Some(interface) = pick_interface(&mut terminal, &interfaces, event::read)? {
+                config.interfaces.push(interface);
+            }
+        }
+    }
+
     // 2. State & Communication
-    let (tx, rx) = mpsc::channel::<PacketUpdate>();
-    let mut child_process = network::run_sniffer(tx);
+    let (mut tx, mut rx) = mpsc::channel::<CaptureEvent>();
+    let live = capture_sources.is_empty();
+    // `loading` tracks --read progress until the reader reports it's done
+    let (mut sniffer, mut loading) = if live {
+        (None, None)
+    } else {
+        (None, Some(network::run_reader(capture_sources, tx.clone(), &config)))
+    };
+    // Some(code) once the live capture has died; the inner code is None if it was signalled
+    let mut capture_stopped: Option<Option<i32>> = None;
+    let mut capture_error: Option<String> = None; // Why dumpcap couldn't be started, shown in the stopped banner
+    if live {
+        start_capture(network::run_sniffer(tx.clone(), &config), &mut sniffer, &mut capture_stopped, &mut capture_error);
+    }
 
-    let mut conversations: HashMap<String, u64> = HashMap::new();
-    let mut chat_history: Vec<PacketUpdate> = Vec::new();
+    let mut latest_ts = Duration::ZERO;
+    let mut time_format = TimeFormat::default();
+    let mut accounting = model::Accounting::default();
+    let mut session = Session::new(&config);
+    let mut show_bookmarks = false;
+    let mut color_mode = ColorMode::default();
+    let mut hide_small_flows = false;
+    let mut diff: Option<(PacketUpdate, PacketUpdate)> = None; // Bookmarked pair shown side by side with `D`
+    let mut merges = model::Merges::default();
+    let mut merge_pending: Option<String> = None; // First stream picked with `M`, awaiting the second
+    let mut byte_mode = ui::ByteMode::default();
+    let mut rolling_window = false; // Only show what was active in the last `window`; older data is kept for export
+    let mut window = config.window();
+    let hooks = hooks::Hooks::new(&config.tag_rules);
+    let mut show_events = true;
+    let mut show_arp_table = false;
+    let mut show_roles = false; // Sidebar names session.conversations by client and server instead of by stream
+    let mut show_subnets = false; // Conversations aggregated into subnet pairs, in place of the feed
+    let mut show_seq_graph = false;
+    let mut oriented = true; // A selected stream reads local-first with ⇒/⇐ arrows; `O` restores src ➔ dst
     let mut list_state = ListState::default();
     let mut selected_stream: Option<String> = None;
     let mut searching = false;
-    let mut search_query = String::new();
+    let mut hex_searching = false;
+    let mut hex_query = TextInput::default();
+    let mut goto_editing = false; // `T` in --read mode: jump to a Unix timestamp or +secs into the capture
+    let mut goto_input = TextInput::default();
+    let mut search_query = TextInput::default();
+    // `R` re-targets the live capture: edit "interface [filter]", then choose whether to keep state
+    let mut capture_editing = false;
+    let mut capture_input = TextInput::default();
+    let mut capture_pending: Option<String> = None;
+    let mut direction_menu: Option<(Ipv4Addr, Ipv4Addr)> = None; // `d` on a flow: pick which host's egress/ingress to filter
+    let mut clear_armed: Option<Instant> = None; // Set by the first `c` of the double-press clear
+    let mut notice: Option<(String, Instant)> = None; // Transient one-line result, e.g. of a flush
+
+    // `F` preset menu: arrows + Enter apply a saved filter; typing a name and Enter saves the current one
+    let mut preset_menu = false;
+    let mut preset_state = ListState::default();
+    let mut preset_name = TextInput::default();
+
+    // Fuzzy finder popup state
+    let mut finding = false;
+    let mut finder_query = TextInput::default();
+    let mut finder_state = ListState::default();
     
     // Sparkline state
     let mut sparkline_data: Vec<u64> = vec![0; 100];
     let mut packets_this_tick: u64 = 0;
     let mut last_tick = Instant::now();
+    let mut dirty = true; // Redraw only when something changed, or on the 200ms tick
     let mut formatted_hex_view = String::from("Select a stream to inspect...");
+    let mut inspector_entropy: Option<f64> = None;
+    let mut inspector_snap: Option<(u32, u32)> = None;
+    let mut inspector_locked = false;
+    let mut snapshot_pending = false; // `H` asks the next frame to save its feed as HTML
+    let mut inspector_popup = false; // The compact layout's inspector, opened with Enter
+    let mut inspected: Option<PacketUpdate> = None; // Packet shown in the inspector
+    let mut show_link_layer = false;
+    let mut show_parity = false; // Inspector shows the summary string beside its structured rendering
+    let mut payload_only = false; // Inspector dumps just the transport payload, offsets counted from its start
+    let mut show_json = true;
+    let mut show_preview = false;
+    let mut decode_overrides: HashMap<model::FlowKey, decode::DecodeAs> = HashMap::new(); // "Inspect as" protocol, per flow
+    let mut layout_mode = LayoutMode::default();
+    let mut hex_scroll: u16 = 0;
+    let mut hex_selection = ui::ByteSelection::default(); // Byte cursor in the full-screen hex view
+    let mut hex_width = config.hex_width(); // Bytes per hex-dump line, cycled with `W`
+
+    let mut consumed: u64 = 0; // Capture records handled, compared with what the sniffer has read
+    let mut lag = ui::LagMonitor::default();
 
     terminal.clear()?;
+    let started = Instant::now(); // --duration is wall-clock time, however fast packets arrive
 
     loop {
         // 3. Process Incoming Packets
-        while let Ok(update) = rx.try_recv() {
-            let ip_pair = if let Some(pos) = update.summary.find(" [") {
-                update.summary[..pos].to_string()
-            } else {
-                update.summary.clone()
+        for event in rx.try_iter().take(ui::MAX_EVENTS_PER_FRAME) {
+            dirty = true;
+            if !matches!(event, CaptureEvent::Stopped(_) | CaptureEvent::Loaded) { consumed += 1; }
+            let mut update = match event {
+                CaptureEvent::Packet(update) => update,
+                CaptureEvent::Binding(ip, mac) => { accounting.processed += 1; session.arp_table.observe(ip, mac); continue; }
+                CaptureEvent::Dropped(reason) => { accounting.record_drop(reason, 1); continue; }
+                CaptureEvent::Loaded => {
+                    loading = None;
+                    let span = latest_ts.saturating_sub(session.capture_start.unwrap_or(latest_ts));
+                    notice = Some((format!("loaded {} packets spanning {:.1}s · skipped {} records", accounting.processed, span.as_secs_f64(), accounting.seen() - accounting.processed), Instant::now()));
+                    continue;
+                }
+                CaptureEvent::Stopped(interface) => {
+                    // The other interfaces may still be capturing; only the last reader stops the capture
+                    if sniffer.as_mut().is_some_and(|s| !s.reader_stopped()) {
+                        notice = Some((format!("⚠ capture on {} stopped", interface.as_deref().unwrap_or("one interface")), Instant::now()));
+                        continue;
+                    }
+                    let code = sniffer.as_mut().and_then(|s| s.exit_code());
+                    capture_stopped = Some(code);
+                    session.events.emit_event(latest_ts, detect::Event::CaptureStopped(code));
+                    continue;
+                }
             };
+            accounting.processed += 1;
+            session.arp_table.observe_packet(&update);
+            for event in [session.scan_detector.observe(&update), session.rst_detector.observe(&update), session.zero_window_detector.observe(&update), session.new_host_detector.observe(&update)].into_iter().flatten() {
+                session.events.emit_event(update.ts, event);
+            }
+            if let Some(decode_as) = model::FlowKey::from_packet(&update.raw_data).and_then(|flow| decode_overrides.get(&flow)) {
+                if let Some(tag) = decode_as.label(&update.raw_data) { update.retag(tag); }
+            }
+            hooks.apply(&mut update);
+            session.dns_tracker.observe(&update);
+            update.dup_ack = session.dup_acks.observe(&update);
+            let ip_pair = merges.resolve(model::stream_key(&update.summary)).to_string();
 
-            let count = conversations.entry(ip_pair).or_insert(0);
-            *count += 1;
+            latest_ts = latest_ts.max(update.ts);
+            session.capture_start.get_or_insert(update.ts);
             packets_this_tick += 1;
+            // Hook labels sit beside the tag, not in it, so only protocols are counted
+            if let Some(tag) = &update.tag {
+                *session.tag_totals.entry(model::protocol_of(tag).to_string()).or_insert(0) += 1;
+            }
 
-            chat_history.push(update);
-            if chat_history.len() > 50 { chat_history.remove(0); }
+            session.record(ip_pair, update);
         }
 
         // 4. Update Sparkline and Hex Cache
@@ -90,110 +511,737 @@ async fn main() -> Result<(), io::Error> {
             if sparkline_data.len() > 100 { sparkline_data.remove(0); }
             packets_this_tick = 0;
             last_tick = Instant::now();
-            
-            // Update Hex view only when data or selection might have changed
-            if let Some(ref target) = selected_stream {
-                if let Some(last_pkt) = chat_history.iter().filter(|p| p.summary.contains(target)).last() {
-                    formatted_hex_view = format_hex(&last_pkt.raw_data);
+            dirty = true;
+
+            // Idle expiry is judged against the wall clock live, and against the newest packet in --read mode
+            if let Some(timeout) = config.idle_timeout {
+                let now = if !config.read.is_empty() { latest_ts } else { SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default() };
+                accounting.record_drop(model::DROP_EXPIRED, model::expire_idle(&mut session.conversations, &session.pinned, now, timeout));
+            }
+            if config.duration.is_some_and(|d| started.elapsed() >= d) {
+                break;
+            }
+            if clear_armed.is_some_and(|t| t.elapsed() >= ui::CLEAR_CONFIRM_WINDOW) {
+                clear_armed = None;
+            }
+            if notice.as_ref().is_some_and(|(_, t)| t.elapsed() >= ui::NOTICE_DURATION) {
+                notice = None;
+            }
+            // dumpcap may still be exiting when EOF arrives; pick up its exit code once it has
+            if let Some(sniffer) = &sniffer { lag.observe(sniffer.records(), consumed); }
+            if capture_stopped == Some(None) {
+                capture_stopped = Some(sniffer.as_mut().and_then(|s| s.exit_code()));
+            }
+            let shed = model::shed_to_limit(&mut session.conversations, config.memory_limit());
+            if shed > 0 { session.events.emit_event(latest_ts, detect::Event::Shed(shed)); }
+            accounting.record_drop(model::DROP_SHED, shed);
+
+            follow_selection(&mut inspected, selected_stream.as_ref(), &session.conversations, inspector_locked);
+            if let Some(pkt) = &inspected {
+                formatted_hex_view = format_hex(inspector_bytes(pkt, payload_only), hex_width);
+                if let Some(l7) = decode::app_detail(&pkt.raw_data) {
+                    formatted_hex_view = format!("L7  {}\n\n{}", l7.lines().join("\n    "), formatted_hex_view);
+                }
+                if let Some(dns) = session.dns_tracker.exchange(&pkt.raw_data) {
+                    formatted_hex_view = format!("{}\n\n{}", dns.describe(), formatted_hex_view);
+                }
+                let conversation = session.conversations.get(merges.resolve(model::stream_key(&pkt.summary)));
+                // A JSON body is usually spread over several segments, so it's reassembled from the conversation
+                if let Some(json) = conversation.filter(|_| show_json).and_then(|c| c.http_message(pkt)).and_then(|m| decode::json_body(&m)) {
+                    formatted_hex_view = format!("JSON\n{}\n\n{}", json, formatted_hex_view);
+                }
+                // Negotiated TLS parameters belong to the conversation, not just the ServerHello packet
+                if let Some(tls) = conversation.and_then(|c| c.tls) {
+                    formatted_hex_view = format!("{}\n\n{}", tls.describe(), formatted_hex_view);
+                }
+                if let Some(l2) = pkt.link.describe().filter(|_| show_link_layer) {
+                    formatted_hex_view = format!("L2  {}\n\n{}", l2, formatted_hex_view);
                 }
+                if show_parity {
+                    formatted_hex_view = format!("{}\n\n{}", ui::parity_lines(pkt), formatted_hex_view);
+                }
+                let payload = decode::transport_payload(&pkt.raw_data);
+                inspector_entropy = (!payload.is_empty()).then(|| decode::entropy(payload));
+                inspector_snap = pkt.is_truncated().then_some((pkt.cap_len, pkt.orig_len));
             }
         }
 
         // 5. Drawing
-        terminal.draw(|f| {
-            let size = f.size();
-            let main_v = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Min(3), Constraint::Length(if searching { 3 } else { 0 })])
-                .split(size);
-
-            let main_h = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
-                .split(main_v[0]);
-
-            let right_v = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(35), Constraint::Length(3)])
-                .split(main_h[1]);
-
-            // Sidebar
-            let mut streams: Vec<String> = conversations.keys()
-                .filter(|s| s.to_lowercase().contains(&search_query.to_lowercase()))
-                .cloned().collect();
-            streams.sort();
-
-            if let Some(ref target) = selected_stream {
-                if let Some(pos) = streams.iter().position(|s| s == target) {
-                    list_state.select(Some(pos));
+        let display_filter = Filter::parse(search_query.as_str());
+        let min_packets = if hide_small_flows { config.min_packets() } else { 0 };
+        let active_since = model::active_since(latest_ts, rolling_window.then_some(window));
+        if dirty {
+            terminal.draw(|f| {
+                let size = f.size();
+                let compact = layout_mode.for_height(size.height) == LayoutMode::Compact;
+
+                // Full-screen hex replaces the split layout entirely
+                if layout_mode == LayoutMode::FullHex {
+                    let title = format!(" Hex ── {} (Esc to return) ", selected_stream.as_deref().unwrap_or("no stream"));
+                    let Some(bytes) = inspected.as_ref().map(|p| inspector_bytes(p, payload_only)).filter(|b| !b.is_empty()) else {
+                        f.render_widget(Paragraph::new(formatted_hex_view.as_str()).block(Block::default().title(title).borders(Borders::ALL)), size);
+                        return;
+                    };
+                    let hex_v = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Min(3), Constraint::Length(8)])
+                        .split(size);
+
+                    // Decoded headers sit above the dump; the dump itself is redrawn with the selection highlighted
+                    let rows = bytes.len().div_ceil(hex_width);
+                    let header_lines = formatted_hex_view.lines().count().saturating_sub(rows);
+                    let mut lines: Vec<Line> = formatted_hex_view.lines().take(header_lines).map(Line::from).collect();
+                    let selection = hex_selection.range(bytes.len());
+                    lines.extend(hex_lines(bytes, hex_width, selection.clone()));
+
+                    // Keep the cursor's row on screen
+                    let cursor_row = (header_lines + hex_selection.cursor().min(bytes.len() - 1) / hex_width) as u16;
+                    let height = hex_v[0].height.saturating_sub(2).max(1);
+                    hex_scroll = hex_scroll.clamp((cursor_row + 1).saturating_sub(height), cursor_row);
+                    f.render_widget(Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL)).scroll((hex_scroll, 0)), hex_v[0]);
+
+                    let readout_title = format!(" Selection ── 0x{:04x}..0x{:04x} ({} bytes) ", selection.start, selection.end, selection.len());
+                    let readout: Vec<Line> = ui::interpret_bytes(&bytes[selection]).into_iter().map(Line::from).collect();
+                    f.render_widget(Paragraph::new(readout).block(Block::default().title(readout_title).borders(Borders::ALL)).style(Style::default().fg(Color::Cyan)), hex_v[1]);
+                    return;
                 }
-            }
 
-            let sidebar_items: Vec<ListItem> = streams.iter().map(|s| {
-                let count = conversations.get(s).unwrap_or(&0);
-                ListItem::new(format!("[{}] {}", count, s)).style(Style::default().fg(Color::Cyan))
-            }).collect();
+                let main_v = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(if capture_stopped.is_some() || loading.is_some() || lag.is_lagging() || clear_armed.is_some() || notice.is_some() { 1 } else { 0 }), Constraint::Min(3), Constraint::Length(if !compact && (searching || hex_searching || capture_editing || goto_editing || capture_pending.is_some() || direction_menu.is_some()) { 3 } else { 0 })])
+                    .split(size);
 
-            let sidebar = List::new(sidebar_items)
-                .block(Block::default().title(" Streams ").borders(Borders::ALL))
-                .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow))
-                .highlight_symbol(">> ");
-            f.render_stateful_widget(sidebar, main_h[0], &mut list_state);
-
-            // Feed
-            let feed_lines: Vec<Line> = chat_history.iter()
-                .filter(|pkt| {
-                    if let Some(ref t) = selected_stream { pkt.summary.contains(t) }
-                    else { pkt.summary.to_lowercase().contains(&search_query.to_lowercase()) }
-                })
-                .map(|pkt| {
-                    let color = if pkt.summary.contains("[HTTPS]") { Color::Magenta }
-                        else if pkt.summary.contains("[DNS]") { Color::Blue }
-                        else if pkt.summary.contains("[SSH]") { Color::Green }
-                        else { Color::Gray };
-                    Line::from(Span::styled(&pkt.summary, Style::default().fg(color)))
+                // Stopped-capture banner
+                if let Some(code) = capture_stopped {
+                    let reason = match (&capture_error, code) {
+                        (Some(e), _) => e.clone(),
+                        (None, Some(c)) => format!("capture stopped (exit code {})", c),
+                        (None, None) => String::from("capture stopped"),
+                    };
+                    f.render_widget(Paragraph::new(format!(" ⚠ {} ── press r to restart ", reason)).style(Style::default().fg(Color::White).bg(Color::Red)), main_v[0]);
+                } else if let Some(speed) = loading.as_ref().and_then(|p| p.replay.as_ref()) {
+                    let speed = network::replay_speed_name(speed.load(Ordering::Relaxed));
+                    f.render_widget(Paragraph::new(format!(" ▶ replaying at {} · {} packets ── +/- to change speed ", speed, accounting.processed)).style(Style::default().fg(Color::White).bg(Color::Blue)), main_v[0]);
+                } else if let Some(progress) = &loading {
+                    // A known total gets a gauge; stdin only gets a spinner and the running count
+                    match progress.fraction() {
+                        Some(ratio) => f.render_widget(Gauge::default().ratio(ratio).label(format!("loading {:.0}% · {} packets", ratio * 100.0, accounting.processed)).gauge_style(Style::default().fg(Color::Blue).bg(Color::Black)), main_v[0]),
+                        None => {
+                            let spinner = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+                            let frame = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() / 100 % spinner.len() as u128;
+                            f.render_widget(Paragraph::new(format!(" {} loading from stdin · {} packets ", spinner[frame as usize], accounting.processed)).style(Style::default().fg(Color::White).bg(Color::Blue)), main_v[0]);
+                        }
+                    }
+                } else if lag.is_lagging() {
+                    f.render_widget(Paragraph::new(format!(" ⚠ UI lagging ({} records behind) ── consider a capture filter (-f) or --snaplen ", lag.backlog())).style(Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD)), main_v[0]);
+                } else if clear_armed.is_some() {
+                    f.render_widget(Paragraph::new(" Press c again to clear all captured data; any other key cancels ").style(Style::default().fg(Color::Black).bg(Color::Yellow)), main_v[0]);
+                } else if let Some((text, _)) = &notice {
+                    f.render_widget(Paragraph::new(format!(" {} ", text)).style(Style::default().fg(Color::Black).bg(Color::Green)), main_v[0]);
+                }
+
+                // Compact: the feed gets every row; the other panes collapse to nothing
+                let main_h = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints(if compact { [Constraint::Length(0), Constraint::Min(1)] } else { [Constraint::Percentage(30), Constraint::Percentage(70)] })
+                    .split(main_v[1]);
+
+                let right_v = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints(if compact { [Constraint::Min(1), Constraint::Length(0), Constraint::Length(0), Constraint::Length(0)] }
+                        else { [Constraint::Percentage(50), Constraint::Percentage(35), Constraint::Length(3), Constraint::Length(if !show_events || session.events.is_empty() { 0 } else { session.events.len().min(4) as u16 + 2 })] })
+                    .split(main_h[1]);
+
+                // Sidebar
+                let streams = model::visible_streams(&session.conversations, &display_filter, min_packets, active_since);
+
+                // The highlight always mirrors `selected_stream`, including when the filter hides it
+                list_state.select(selected_stream.as_ref().and_then(|target| streams.iter().position(|s| s == target)));
+
+                let sidebar_items: Vec<ListItem> = streams.iter().map(|s| {
+                    let (count, bytes) = session.conversations.get(s).map_or((0, 0), |c| (c.packets, byte_mode.bytes(c)));
+                    let name = session.conversations.get(s).filter(|_| show_roles).and_then(ui::roles_label).unwrap_or_else(|| s.clone());
+                    let pin = if session.pinned.contains(s) { "📌 " } else { "" };
+                    let merged = match merges.merged_into(s) { 0 => String::new(), n => format!(" (+{} merged)", n) };
+                    let style = if merge_pending.as_ref() == Some(s) { Style::default().fg(Color::Magenta) } else { Style::default().fg(Color::Cyan) };
+                    let label = format!("{}[{} · {}] {}{}", pin, count, ui::format_bytes(bytes), name, merged);
+                    // Whatever room the label and highlight symbol leave, up to the full ring
+                    let room = (main_h[0].width as usize).saturating_sub(label.chars().count() + 6);
+                    let spark = session.conversations.get(s).filter(|_| room >= 3).map_or(String::new(), |c| format!(" {}", ui::activity_sparkline(&c.activity.recent(latest_ts), room - 1)));
+                    ListItem::new(Line::from(vec![Span::styled(label, style), Span::styled(spark, Style::default().fg(Color::Green))]))
                 }).collect();
 
-            f.render_widget(Paragraph::new(feed_lines).block(Block::default().title(" Feed ").borders(Borders::ALL)).wrap(Wrap { trim: true }), right_v[0]);
+                let mut sidebar_block = Block::default().title(ui::streams_title(&session.tag_totals, main_h[0].width.saturating_sub(2) as usize)).borders(Borders::ALL)
+                    .title_bottom(ui::conversations_label(session.conversations.len(), streams.len()));
+                if hide_small_flows {
+                    sidebar_block = sidebar_block.title_bottom(format!(" hiding < {} pkts ", min_packets));
+                }
+                if byte_mode == ui::ByteMode::Captured {
+                    sidebar_block = sidebar_block.title_bottom(format!(" {} bytes ", byte_mode.name()));
+                }
+                if rolling_window {
+                    sidebar_block = sidebar_block.title_bottom(format!(" {} ", ui::window_label(window)));
+                }
+                let sidebar = List::new(sidebar_items)
+                    .block(sidebar_block)
+                    .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow))
+                    .highlight_symbol(">> ");
+                f.render_stateful_widget(sidebar, main_h[0], &mut list_state);
 
-            // Inspector
-            f.render_widget(Paragraph::new(formatted_hex_view.as_str()).block(Block::default().title(" Hex Inspector ").borders(Borders::ALL)).style(Style::default().fg(Color::DarkGray)), right_v[1]);
+                // Feed
+                let mut previous_ts = None;
+                let feed_lines: Vec<Line> = feed_packets(&session.chat_history, selected_stream.as_ref(), &display_filter, &merges, active_since).into_iter()
+                    .map(|pkt| {
+                        // A selected stream is coloured by direction; portless traffic has no flow key and keeps its protocol colour
+                        let direction_color = model::FlowDirection::of(&pkt.raw_data).filter(|_| selected_stream.is_some()).map(ui::direction_color);
+                        let flow_color = model::FlowKey::from_packet(&pkt.raw_data).filter(|_| color_mode == ColorMode::Flow).map(|key| ui::FLOW_PALETTE[ui::flow_palette_index(&key)]);
+                        let color = direction_color.or(flow_color).unwrap_or_else(|| config.theme.protocol_color(pkt.display_tag().as_deref()));
+                        let time = time_format.format(pkt.ts, session.capture_start.unwrap_or(pkt.ts), previous_ts.replace(pkt.ts));
+                        let summary = if oriented && selected_stream.is_some() { ui::oriented_summary(&pkt.summary, &pkt.raw_data) } else { pkt.summary.clone() };
+                        Line::from(vec![
+                            Span::styled(if session.bookmarks.is_marked(pkt) { "★" } else { " " }, Style::default().fg(Color::Yellow)),
+                            Span::styled(format!("{:>6} {:>12} ", pkt.frame, time), Style::default().fg(Color::DarkGray)),
+                            Span::styled(pkt.interface.as_deref().map_or(String::new(), |i| format!("{} ", i)), Style::default().fg(Color::Cyan)),
+                            Span::styled(summary, Style::default().fg(color)),
+                            Span::styled(if decode::zero_window(&pkt.raw_data) { " [zero-win]" } else { "" }, Style::default().fg(Color::Red)),
+                            Span::styled(pkt.dup_ack.map_or(String::new(), |n| format!(" [dup-ack {}]", n)), Style::default().fg(Color::Red)),
+                            Span::styled(if show_preview { format!("  {}", text::preview(decode::transport_payload(&pkt.raw_data), 20)) } else { String::new() }, Style::default().fg(Color::DarkGray)),
+                        ])
+                    }).collect();
 
-            // Sparkline
-            f.render_widget(Sparkline::default().block(Block::default().title(" Activity ").borders(Borders::LEFT | Borders::RIGHT | Borders::BOTTOM)).data(&sparkline_data).style(Style::default().fg(Color::Green)), right_v[2]);
+                // The snapshot is written from the very lines about to be drawn, colours included
+                if std::mem::take(&mut snapshot_pending) {
+                    let path = format!("vshark-feed-{}.html", SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs());
+                    let title = format!("vshark ── {}", selected_stream.as_deref().unwrap_or("all streams"));
+                    let result = std::fs::File::create(&path).map(io::BufWriter::new)
+                        .and_then(|mut out| export::write_html(&mut out, &title, &feed_lines));
+                    notice = Some((match result {
+                        Ok(()) => format!("wrote {} lines to {}", feed_lines.len(), path),
+                        Err(e) => format!("⚠ could not write {}: {}", path, e),
+                    }, Instant::now()));
+                }
 
-            // Search Bar
-            if searching {
-                f.render_widget(Paragraph::new(format!(" SEARCH: {}█", search_query)).block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), main_v[1]);
-            }
-        })?;
+                if show_arp_table {
+                    // ARP table replaces the feed; IPs claimed by more than one MAC are flagged red
+                    let arp_lines: Vec<Line> = session.arp_table.bindings.iter().map(|(ip, macs)| {
+                        let macs: Vec<String> = macs.iter().map(decode::format_mac).collect();
+                        let (style, note) = if session.arp_table.is_conflict(ip) { (Style::default().fg(Color::Red).add_modifier(Modifier::BOLD), "  CONFLICT") } else { (Style::default().fg(Color::Gray), "") };
+                        Line::from(Span::styled(format!("{:<15}  {}{}", ip, macs.join(", "), note), style))
+                    }).collect();
+                    f.render_widget(Paragraph::new(arp_lines).block(Block::default().title(" ARP Table ").borders(Borders::ALL)), right_v[0]);
+                } else if show_subnets {
+                    let prefix = config.subnet_prefix();
+                    let groups = model::group_by_subnet(streams.iter().filter_map(|s| session.conversations.get(s)), prefix);
+                    let subnet_lines: Vec<Line> = groups.iter().map(|(key, group)| {
+                        Line::from(Span::styled(format!("[{} · {}] {} ({} conversations)", group.packets, ui::format_bytes(group.bytes), key.label(), group.conversations), Style::default().fg(Color::Cyan)))
+                    }).collect();
+                    f.render_widget(Paragraph::new(subnet_lines).block(Block::default().title(format!(" Subnets /{} ({}) ── g to close ", prefix, groups.len())).borders(Borders::ALL)), right_v[0]);
+                } else if show_seq_graph {
+                    // Time-sequence graph of the selected TCP stream replaces the feed: flat runs are stalls,
+                    // points dropping back below the line are retransmissions
+                    let conversation = selected_stream.as_ref().and_then(|s| session.conversations.get(s));
+                    let block = Block::default().borders(Borders::ALL);
+                    match conversation.and_then(|c| Some((c.origin?, c.seq_points()?))) {
+                        Some((origin, points)) => {
+                            let max_x = points.iter().flatten().map(|p| p.0).fold(0.0, f64::max).max(1.0);
+                            let max_y = points.iter().flatten().map(|p| p.1).fold(0.0, f64::max).max(1.0);
+                            let names = [format!("{}:{} → {}:{}", origin.src.0, origin.src.1, origin.dst.0, origin.dst.1), format!("{}:{} → {}:{}", origin.dst.0, origin.dst.1, origin.src.0, origin.src.1)];
+                            let datasets = points.iter().zip(&names).zip([model::FlowDirection::Outbound, model::FlowDirection::Inbound]).map(|((data, name), direction)| {
+                                Dataset::default().name(name.as_str()).marker(symbols::Marker::Braille).graph_type(GraphType::Scatter).style(Style::default().fg(ui::direction_color(direction))).data(data)
+                            }).collect();
+                            let chart = Chart::new(datasets)
+                                .block(block.title(" TCP Sequence ── G to close "))
+                                .x_axis(Axis::default().title("s").bounds([0.0, max_x]).labels(vec![Span::raw("0"), Span::raw(format!("{:.1}", max_x))]).style(Style::default().fg(Color::DarkGray)))
+                                .y_axis(Axis::default().title("seq").bounds([0.0, max_y]).labels(vec![Span::raw("0"), Span::raw(format!("{:.0}", max_y))]).style(Style::default().fg(Color::DarkGray)));
+                            f.render_widget(chart, right_v[0]);
+                        }
+                        None => f.render_widget(Paragraph::new(" Select a TCP stream to graph its sequence numbers ").block(block.title(" TCP Sequence ── G to close ")), right_v[0]),
+                    }
+                } else if show_bookmarks {
+                    // Bookmarks replace the feed; the mark last jumped to is highlighted
+                    let mark_lines: Vec<Line> = session.bookmarks.iter().enumerate().map(|(i, pkt)| {
+                        let time = time_format.format(pkt.ts, session.capture_start.unwrap_or(pkt.ts), None);
+                        let style = if session.bookmarks.cursor() == Some(i) { Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD) } else { Style::default().fg(config.theme.protocol_color(pkt.display_tag().as_deref())) };
+                        Line::from(Span::styled(format!("★{:>6} {:>12} {}", pkt.frame, time, pkt.summary), style))
+                    }).collect();
+                    f.render_widget(Paragraph::new(mark_lines).block(Block::default().title(format!(" Bookmarks ({}) ── n/N to jump ", session.bookmarks.len())).borders(Borders::ALL)), right_v[0]);
+                } else {
+                    let feed_title = ui::feed_title(feed_lines.len(), session.chat_history.len(), color_mode);
+                    let mut feed_block = Block::default().title(feed_title).borders(Borders::ALL);
+                    // With the sidebar gone, the selection is named on the feed itself
+                    if compact {
+                        feed_block = feed_block.title_bottom(format!(" {} ── Enter to inspect ", selected_stream.as_deref().unwrap_or("all streams")));
+                    }
+                    f.render_widget(Paragraph::new(feed_lines).block(feed_block).wrap(Wrap { trim: true }), right_v[0]);
+                }
+
+                // Inspector
+                let mut inspector_title = String::from(" Hex Inspector ");
+                if let Some(pkt) = &inspected {
+                    inspector_title.push_str(&format!("── No. {} ", pkt.frame));
+                    if let Some(interface) = &pkt.interface { inspector_title.push_str(&format!("on {} ", interface)); }
+                }
+                if let Some(e) = inspector_entropy { inspector_title.push_str(&format!("── entropy {:.1}/8 ", e)); }
+                if let Some(pkt) = inspected.as_ref().filter(|_| payload_only) {
+                    inspector_title.push_str(&format!("── payload @0x{:x} ", decode::payload_offset(&pkt.raw_data)));
+                }
+                if let Some((cap, orig)) = inspector_snap { inspector_title.push_str(&format!("[snap {}/{}] ", cap, orig)); }
+                if let Some(decode_as) = inspected.as_ref().and_then(|p| model::FlowKey::from_packet(&p.raw_data)).and_then(|flow| decode_overrides.get(&flow)) {
+                    inspector_title.push_str(&format!("── as {} ", decode_as.name()));
+                }
+                if inspector_locked { inspector_title.push_str("🔒 "); }
+                let inspector_area = if compact { centered_rect(90, 80, size) } else { right_v[1] };
+                // Jumbo frames run past the pane; the full-screen view scrolls through them
+                let dump_rows = inspected.as_ref().map_or(0, |p| inspector_bytes(p, payload_only).len().div_ceil(hex_width));
+                if dump_rows > inspector_area.height.saturating_sub(2) as usize {
+                    inspector_title.push_str(&format!("── {} rows, x to page ", dump_rows));
+                }
+                if !compact || inspector_popup {
+                    if compact { f.render_widget(Clear, inspector_area); }
+                    f.render_widget(Paragraph::new(formatted_hex_view.as_str()).block(Block::default().title(inspector_title).borders(Borders::ALL)).style(Style::default().fg(Color::DarkGray)), inspector_area);
+                }
+
+                // Sparkline; five 200ms buckets make up the last second
+                let packets_per_second: u64 = sparkline_data.iter().rev().take(5).sum();
+                f.render_widget(Sparkline::default().block(Block::default().title(format!(" Activity ── {} pkt/s ── {} ", packets_per_second, accounting.summary())).borders(Borders::LEFT | Borders::RIGHT | Borders::BOTTOM)).data(&sparkline_data).style(Style::default().fg(Color::Green)), right_v[2]);
+
+                // Events: only shown once something has been logged, newest last
+                if show_events && !session.events.is_empty() {
+                    let event_lines: Vec<Line> = session.events.iter().rev().take(4).rev().map(|(ts, event)| {
+                        let time = time_format.format(*ts, session.capture_start.unwrap_or(*ts), None);
+                        Line::from(vec![
+                            Span::styled(format!("{:>12} ", time), Style::default().fg(Color::DarkGray)),
+                            Span::styled(event.message(), ui::severity_style(event.severity())),
+                        ])
+                    }).collect();
+                    let worst = session.events.iter().map(|(_, e)| e.severity()).max().unwrap_or(detect::Severity::Info);
+                    let block = Block::default().title(format!(" Events ({}) ── X to clear ", session.events.len())).borders(Borders::ALL).border_style(ui::severity_style(worst));
+                    f.render_widget(Paragraph::new(event_lines).block(block), right_v[3]);
+                }
+
+                // Search Bar
+                // The terminal's own cursor marks the edit position. Compact draws it over the feed's last rows.
+                let input_area = if compact { Rect { y: main_v[1].bottom().saturating_sub(3), height: 3.min(main_v[1].height), ..main_v[1] } } else { main_v[2] };
+                if compact && (searching || hex_searching || capture_editing || goto_editing || capture_pending.is_some() || direction_menu.is_some()) {
+                    f.render_widget(Clear, input_area);
+                }
+                if hex_searching {
+                    let mut spans = vec![Span::raw(format!(" HEX: {}", hex_query.as_str()))];
+                    f.set_cursor(input_area.x + 7 + hex_query.cursor() as u16, input_area.y + 1);
+                    if let Err(e) = decode::parse_hex(hex_query.as_str()) {
+                        spans.push(Span::styled(format!("  ({})", e), Style::default().fg(Color::Red)));
+                    }
+                    f.render_widget(Paragraph::new(Line::from(spans)).block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), input_area);
+                }
+                if capture_editing {
+                    f.set_cursor(input_area.x + 11 + capture_input.cursor() as u16, input_area.y + 1);
+                    f.render_widget(Paragraph::new(format!(" CAPTURE: {}", capture_input.as_str())).block(Block::default().title(" interface[,interface…] [bpf filter] ").borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), input_area);
+                }
+                if goto_editing {
+                    let mut spans = vec![Span::raw(format!(" GO TO: {}", goto_input.as_str()))];
+                    f.set_cursor(input_area.x + 9 + goto_input.cursor() as u16, input_area.y + 1);
+                    let when = goto_input.as_str().trim();
+                    if let Some(e) = when.parse::<config::TimeBound>().err().filter(|_| !when.is_empty()) {
+                        spans.push(Span::styled(format!("  ({})", e), Style::default().fg(Color::Red)));
+                    }
+                    f.render_widget(Paragraph::new(Line::from(spans)).block(Block::default().title(" unix timestamp or +seconds from the first packet ").borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), input_area);
+                }
+                if let Some(spec) = &capture_pending {
+                    f.render_widget(Paragraph::new(format!(" Restart capture on '{}': keep existing conversations? [y/n, Esc cancels]", spec)).block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), input_area);
+                }
+                if let Some((a, b)) = &direction_menu {
+                    f.render_widget(Paragraph::new(format!(" Filter: [1] from {a}  [2] to {a}  [3] from {b}  [4] to {b}  (Esc cancels)")).block(Block::default().title(" Direction ").borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), input_area);
+                }
+                if searching {
+                    f.set_cursor(input_area.x + 10 + search_query.cursor() as u16, input_area.y + 1);
+                    f.render_widget(Paragraph::new(format!(" SEARCH: {}", search_query.as_str())).block(Block::default().title_bottom(" Tab/Shift-Tab: next/prev match ").borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), input_area);
+                }
+
+                // Fuzzy Finder Popup
+                if finding {
+                    let area = centered_rect(60, 50, size);
+                    let popup_v = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(3), Constraint::Min(1)])
+                        .split(area);
+                    let (_, texts): (Vec<String>, Vec<String>) = ui::finder_candidates(&session.conversations).into_iter().unzip();
+                    let matches: Vec<ListItem> = fuzzy::rank(finder_query.as_str(), &texts).into_iter()
+                        .map(|i| ListItem::new(texts[i].as_str()).style(Style::default().fg(Color::Cyan)))
+                        .collect();
+
+                    f.render_widget(Clear, area);
+                    f.set_cursor(popup_v[0].x + 8 + finder_query.cursor() as u16, popup_v[0].y + 1);
+                    f.render_widget(Paragraph::new(format!(" JUMP: {}", finder_query.as_str())).block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), popup_v[0]);
+                    f.render_stateful_widget(List::new(matches)
+                        .block(Block::default().title(" Conversations ").borders(Borders::ALL))
+                        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow))
+                        .highlight_symbol(">> "), popup_v[1], &mut finder_state);
+                }
+
+                if preset_menu {
+                    let area = centered_rect(60, 50, size);
+                    let popup_v = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(3), Constraint::Min(1)])
+                        .split(area);
+                    let items: Vec<ListItem> = config.presets().into_iter()
+                        .map(|(name, filter)| ListItem::new(format!("{:<10} {}", name, filter)).style(Style::default().fg(Color::Cyan)))
+                        .collect();
+                    f.render_widget(Clear, area);
+                    f.set_cursor(popup_v[0].x + 15 + preset_name.cursor() as u16, popup_v[0].y + 1);
+                    let save_hint = if search_query.as_str().trim().is_empty() { " type a name to save the current filter ".to_string() } else { format!(" Enter saves '{}' under this name ", search_query.as_str().trim()) };
+                    f.render_widget(Paragraph::new(format!(" SAVE PRESET: {}", preset_name.as_str())).block(Block::default().title_bottom(save_hint).borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), popup_v[0]);
+                    f.render_stateful_widget(List::new(items)
+                        .block(Block::default().title(" Filter Presets ── Enter to apply ").borders(Borders::ALL))
+                        .highlight_style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow))
+                        .highlight_symbol(">> "), popup_v[1], &mut preset_state);
+                }
+
+                if let Some((a, b)) = &diff {
+                    let area = centered_rect(80, 70, size);
+                    let changed = ui::diff_offsets(&a.raw_data, &b.raw_data).len();
+                    let title = format!(" Diff ── No. {} │ No. {} ── {} bytes differ (Esc to close) ", a.frame, b.frame, changed);
+                    f.render_widget(Clear, area);
+                    f.render_widget(Paragraph::new(diff_lines(&a.raw_data, &b.raw_data, 8)).block(Block::default().title(title).borders(Borders::ALL).border_style(Style::default().fg(Color::Yellow))), area);
+                }
+            })?;
+            dirty = false;
+        }
 
         // 6. Input Handling
-        if event::poll(Duration::from_millis(10))? {
+        let recent_packets = packets_this_tick + sparkline_data.last().copied().unwrap_or(0);
+        if event::poll(ui::poll_interval(config.frame_rate(), recent_packets))? {
+            // Any event, including a resize, warrants a redraw
+            dirty = true;
             if let Event::Key(key) = event::read()? {
-                let mut streams: Vec<String> = conversations.keys()
-                    .filter(|s| s.to_lowercase().contains(&search_query.to_lowercase()))
-                    .cloned().collect();
-                streams.sort();
+                let streams = model::visible_streams(&session.conversations, &display_filter, min_packets, active_since);
 
-                if searching {
+                if layout_mode == LayoutMode::FullHex {
+                    // Arrows move the byte cursor (the view scrolls to follow it); shift drags a selection
+                    let len = inspected.as_ref().map_or(0, |p| inspector_bytes(p, payload_only).len());
+                    let extend = key.modifiers.contains(KeyModifiers::SHIFT);
                     match key.code {
-                        KeyCode::Enter => searching = false,
-                        KeyCode::Esc => { searching = false; search_query.clear(); selected_stream = None; }
-                        KeyCode::Backspace => { search_query.pop(); }
-                        KeyCode::Char(c) => search_query.push(c),
+                        KeyCode::Esc => layout_mode = layout_mode.escape(),
+                        KeyCode::Char('x') => layout_mode = layout_mode.toggle_full_hex(),
+                        KeyCode::Char('q') => break,
+                        KeyCode::Left => hex_selection.move_by(-1, len, extend),
+                        KeyCode::Right => hex_selection.move_by(1, len, extend),
+                        KeyCode::Up => hex_selection.move_by(-(hex_width as isize), len, extend),
+                        KeyCode::Down => hex_selection.move_by(hex_width as isize, len, extend),
+                        KeyCode::PageUp => hex_selection.move_by(-16 * hex_width as isize, len, extend),
+                        KeyCode::PageDown => hex_selection.move_by(16 * hex_width as isize, len, extend),
+                        KeyCode::Char('W') => hex_width = ui::next_hex_width(hex_width),
+                        KeyCode::Home => { hex_selection = ui::ByteSelection::default(); hex_scroll = 0; }
+                        _ => {}
+                    }
+                } else if diff.is_some() {
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('D') => diff = None,
+                        KeyCode::Char('q') => break,
+                        _ => {}
+                    }
+                } else if preset_menu {
+                    let presets = config.presets();
+                    match key.code {
+                        KeyCode::Esc => preset_menu = false,
+                        KeyCode::Enter if !preset_name.as_str().trim().is_empty() => {
+                            let (name, filter) = (preset_name.as_str().trim().to_string(), search_query.as_str().trim().to_string());
+                            if !filter.is_empty() {
+                                notice = Some((match config.save_preset(&name, &filter) {
+                                    Ok(()) => format!("saved preset {} = {}", name, filter),
+                                    Err(e) => format!("⚠ could not save preset {}: {}", name, e),
+                                }, Instant::now()));
+                                preset_menu = false;
+                            }
+                        }
+                        KeyCode::Enter => {
+                            if let Some((_, filter)) = preset_state.selected().and_then(|i| presets.get(i)) {
+                                search_query.set(filter.clone());
+                                selected_stream = None;
+                            }
+                            preset_menu = false;
+                        }
+                        KeyCode::Down if !presets.is_empty() => {
+                            let i = preset_state.selected().map_or(0, |i| (i + 1) % presets.len());
+                            preset_state.select(Some(i));
+                        }
+                        KeyCode::Up if !presets.is_empty() => {
+                            let i = preset_state.selected().map_or(0, |i| if i == 0 { presets.len() - 1 } else { i - 1 });
+                            preset_state.select(Some(i));
+                        }
+                        // Names go into `preset.<name>` config keys, so no spaces or '='
+                        KeyCode::Char(c) if c.is_whitespace() || c == '=' => {}
+                        code => { preset_name.handle(code); }
+                    }
+                } else if finding {
+                    let (keys, texts): (Vec<String>, Vec<String>) = ui::finder_candidates(&session.conversations).into_iter().unzip();
+                    let matches = fuzzy::rank(finder_query.as_str(), &texts);
+                    match key.code {
+                        KeyCode::Enter => {
+                            if let Some(&i) = finder_state.selected().and_then(|i| matches.get(i)) {
+                                selected_stream = Some(keys[i].clone());
+                                search_query.clear();
+                            }
+                            finding = false;
+                        }
+                        KeyCode::Esc => finding = false,
+                        KeyCode::Down if !matches.is_empty() => {
+                            let i = finder_state.selected().map_or(0, |i| (i + 1) % matches.len());
+                            finder_state.select(Some(i));
+                        }
+                        KeyCode::Up if !matches.is_empty() => {
+                            let i = finder_state.selected().map_or(0, |i| if i == 0 { matches.len() - 1 } else { i - 1 });
+                            finder_state.select(Some(i));
+                        }
+                        code => if finder_query.handle(code) { finder_state.select(Some(0)); },
+                    }
+                } else if capture_editing {
+                    match key.code {
+                        KeyCode::Enter => { capture_editing = false; capture_pending = Some(capture_input.as_str().trim().to_string()); }
+                        KeyCode::Esc => capture_editing = false,
+                        code => { capture_input.handle(code); }
+                    }
+                } else if let Some(spec) = capture_pending.clone() {
+                    match key.code {
+                        KeyCode::Char(c @ ('y' | 'n')) => {
+                            capture_pending = None;
+                            if c == 'n' { clear_session(&mut session, &mut selected_stream); }
+                            // A fresh channel keeps the old reader's trailing session.events and Stopped out of the new run
+                            config.set_capture_spec(&spec);
+                            (tx, rx) = mpsc::channel();
+                            let started = match sniffer.take() {
+                                Some(old) => network::restart_sniffer(old, tx.clone(), &config),
+                                None => network::run_sniffer(tx.clone(), &config),
+                            };
+                            start_capture(started, &mut sniffer, &mut capture_stopped, &mut capture_error);
+                            consumed = 0;
+                            lag.reset();
+                        }
+                        KeyCode::Esc => capture_pending = None,
                         _ => {}
                     }
+                } else if let Some((a, b)) = direction_menu {
+                    match key.code {
+                        KeyCode::Char(c @ '1'..='4') => {
+                            let host = if c <= '2' { a } else { b };
+                            search_query.set(filter::direction_filter(host, c == '1' || c == '3'));
+                            selected_stream = None;
+                            direction_menu = None;
+                        }
+                        KeyCode::Esc => direction_menu = None,
+                        KeyCode::Char('q') => break,
+                        _ => {}
+                    }
+                } else if goto_editing {
+                    match key.code {
+                        KeyCode::Enter => if let Ok(bound) = goto_input.as_str().trim().parse::<config::TimeBound>() {
+                            goto_editing = false;
+                            // Selects the packet's stream and holds the inspector on it, as a bookmark jump does
+                            let target = bound.resolve(session.capture_start.unwrap_or_default());
+                            match model::first_packet_at(&session.conversations, target) {
+                                Some(pkt) => {
+                                    selected_stream = Some(merges.resolve(model::stream_key(&pkt.summary)).to_string());
+                                    inspected = Some(pkt.clone());
+                                    inspector_locked = true;
+                                }
+                                None => notice = Some(("no packets at or after that time".to_string(), Instant::now())),
+                            }
+                        }
+                        KeyCode::Esc => goto_editing = false,
+                        code => { goto_input.handle(code); }
+                    }
+                } else if hex_searching {
+                    match key.code {
+                        // Only a valid, non-empty pattern is applied; errors stay visible inline
+                        KeyCode::Enter => if let Ok(pattern) = decode::parse_hex(hex_query.as_str()) {
+                            if !pattern.is_empty() {
+                                search_query.set(filter::hex_filter(&pattern));
+                                selected_stream = None;
+                            }
+                            hex_searching = false;
+                        }
+                        KeyCode::Esc => hex_searching = false,
+                        code => { hex_query.handle(code); }
+                    }
+                } else if searching {
+                    match key.code {
+                        KeyCode::Enter => searching = false,
+                        KeyCode::Esc => escape(&mut searching, &mut search_query, &mut inspector_popup, &mut selected_stream, &mut merge_pending),
+                        // Walk the streams the query leaves in the sidebar without leaving the search bar
+                        KeyCode::Tab | KeyCode::BackTab => if let Some(s) = model::step_stream(&streams, selected_stream.as_deref(), key.code == KeyCode::Tab) {
+                            selected_stream = Some(s);
+                        }
+                        code => { search_query.handle(code); }
+                    }
                 } else {
+                    // Any key disarms a pending clear; only a prompt second `c` goes through with it
+                    let clear_confirmed = clear_armed.take().is_some_and(|t| t.elapsed() < ui::CLEAR_CONFIRM_WINDOW);
                     match key.code {
-                        KeyCode::Char('q') => { let _ = child_process.kill(); break; }
+                        KeyCode::Char('q') => break,
                         KeyCode::Char('/') => { searching = true; search_query.clear(); }
-                        KeyCode::Char('c') => { conversations.clear(); chat_history.clear(); selected_stream = None; }
-                        KeyCode::Down => if !streams.is_empty() {
+                        KeyCode::Char('#') => { hex_searching = true; hex_query.clear(); }
+                        KeyCode::Esc => escape(&mut searching, &mut search_query, &mut inspector_popup, &mut selected_stream, &mut merge_pending),
+                        KeyCode::Enter if layout_mode.for_height(terminal.size()?.height) == LayoutMode::Compact => inspector_popup = !inspector_popup,
+                        KeyCode::Char('u') => reset_view(&mut selected_stream, &mut search_query),
+                        KeyCode::Char('p') => if let Some(s) = selected_stream.clone() {
+                            if !session.pinned.remove(&s) { session.pinned.insert(s); }
+                        }
+                        KeyCode::Char('x') => { layout_mode = layout_mode.toggle_full_hex(); hex_scroll = 0; hex_selection = ui::ByteSelection::default(); }
+                        KeyCode::Char('T') if !live => { goto_editing = true; goto_input.clear(); }
+                        KeyCode::Char('R') if live => { capture_editing = true; capture_input.set(config.capture_spec()); }
+                        KeyCode::Char('r') if capture_stopped.is_some() && live => {
+                            drop(sniffer.take());
+                            start_capture(network::run_sniffer(tx.clone(), &config), &mut sniffer, &mut capture_stopped, &mut capture_error);
+                            consumed = 0;
+                            lag.reset();
+                        }
+                        // Copy the inspected packet's summary line, or a filter expression for its conversation
+                        KeyCode::Char(c @ ('y' | 'Y')) => {
+                            if let Some(pkt) = &inspected {
+                                let text = if c == 'y' { pkt.summary.clone() } else { filter::packet_filter(pkt) };
+                                clipboard::copy(terminal.backend_mut(), &text)?;
+                            }
+                        }
+                        KeyCode::Char('!') => match (&config.on_packet, &inspected) {
+                            (Some(template), Some(pkt)) => {
+                                let command = action::command_line(template, pkt);
+                                notice = Some((match action::spawn(&command) {
+                                    Ok(()) => format!("ran --on-packet for No. {}", pkt.frame),
+                                    Err(e) => format!("⚠ could not run --on-packet: {}", e),
+                                }, Instant::now()));
+                            }
+                            (None, _) => notice = Some(("no --on-packet command configured".to_string(), Instant::now())),
+                            (Some(_), None) => {}
+                        },
+                        KeyCode::Char('t') => time_format = time_format.next(),
+                        KeyCode::Char('a') => show_arp_table = !show_arp_table,
+                        KeyCode::Char('s') => show_roles = !show_roles,
+                        KeyCode::Char('g') => show_subnets = !show_subnets,
+                        KeyCode::Char('G') => show_seq_graph = !show_seq_graph,
+                        KeyCode::Char('O') => oriented = !oriented,
+                        KeyCode::Char('v') => show_preview = !show_preview,
+                        KeyCode::Char('j') => show_json = !show_json,
+                        KeyCode::Char('e') => show_events = !show_events,
+                        KeyCode::Char('+') | KeyCode::Char('-') => if let Some(speed) = loading.as_ref().and_then(|p| p.replay.as_ref()) {
+                            let index = speed.load(Ordering::Relaxed);
+                            let index = if key.code == KeyCode::Char('+') { (index + 1).min(network::REPLAY_SPEEDS.len() - 1) } else { index.saturating_sub(1) };
+                            speed.store(index, Ordering::Relaxed);
+                        },
+                        KeyCode::Char('X') => session.events.clear(),
+                        KeyCode::Char('W') => hex_width = ui::next_hex_width(hex_width),
+                        // Inspect as: cycle the inspected packet's flow through HTTP → DNS → TLS → automatic
+                        KeyCode::Char('i') => if let Some(flow) = inspected.as_ref().and_then(|p| model::FlowKey::from_packet(&p.raw_data)) {
+                            match decode::DecodeAs::cycle(decode_overrides.get(&flow).copied()) {
+                                Some(decode_as) => {
+                                    decode_overrides.insert(flow, decode_as);
+                                    if let Some(conversation) = inspected.as_ref().and_then(|p| session.conversations.get_mut(merges.resolve(model::stream_key(&p.summary)))) { conversation.decode_as(&flow, decode_as); }
+                                    for p in session.chat_history.iter_mut().chain(inspected.iter_mut()).filter(|p| model::FlowKey::from_packet(&p.raw_data) == Some(flow)) {
+                                        if let Some(tag) = decode_as.label(&p.raw_data) { p.retag(tag); }
+                                    }
+                                    notice = Some((format!("decoding flow as {}", decode_as.name()), Instant::now()));
+                                }
+                                None => {
+                                    decode_overrides.remove(&flow);
+                                    notice = Some(("flow back to automatic decoding for new packets".to_string(), Instant::now()));
+                                }
+                            }
+                        },
+                        KeyCode::Char('C') => color_mode = color_mode.toggle(),
+                        KeyCode::Char('h') => hide_small_flows = !hide_small_flows,
+                        KeyCode::Char('L') => rolling_window = !rolling_window,
+                        KeyCode::Char('S') => byte_mode = byte_mode.toggle(),
+                        // Merge: `M` on one stream, then `M` on another folds the first into the second
+                        KeyCode::Char('M') => if let Some(target) = selected_stream.clone() {
+                            match merge_pending.take() {
+                                Some(source) if source != target => {
+                                    merges.merge(&mut session.conversations, &source, &target);
+                                    session.pinned.remove(&source);
+                                    notice = Some((format!("merged {} into {}", source, target), Instant::now()));
+                                }
+                                Some(_) => {}
+                                None => {
+                                    notice = Some((format!("merge {} into… select another stream and press M", target), Instant::now()));
+                                    merge_pending = Some(target);
+                                }
+                            }
+                        },
+                        KeyCode::Char('[') => window = ui::step_window(window, false),
+                        KeyCode::Char(']') => window = ui::step_window(window, true),
+                        KeyCode::Char('B') => show_bookmarks = !show_bookmarks,
+                        KeyCode::Char('b') => if let Some(pkt) = &inspected { session.bookmarks.toggle(pkt); },
+                        KeyCode::Char('D') => match session.bookmarks.diff_pair() {
+                            Some((a, b)) => diff = Some((a.clone(), b.clone())),
+                            None => notice = Some(("bookmark two packets with b to diff them".to_string(), Instant::now())),
+                        },
+                        KeyCode::Char('n') | KeyCode::Char('N') => {
+                            // Jumping to a mark selects its stream and locks the inspector on it
+                            let mark = if key.code == KeyCode::Char('n') { session.bookmarks.next() } else { session.bookmarks.prev() };
+                            if let Some(pkt) = mark {
+                                selected_stream = Some(merges.resolve(model::stream_key(&pkt.summary)).to_string());
+                                inspected = Some(pkt.clone());
+                                inspector_locked = true;
+                            }
+                        }
+                        KeyCode::Char('m') => show_link_layer = !show_link_layer,
+                        KeyCode::Char('V') => show_parity = !show_parity,
+                        KeyCode::Char('P') => { payload_only = !payload_only; hex_selection = ui::ByteSelection::default(); }
+                        KeyCode::Char('o') => {
+                            // Filter everything on the selected conversation's service port (the lower of the pair)
+                            let port = selected_stream.as_ref()
+                                .and_then(|s| session.conversations.get(s))
+                                .and_then(|c| c.messages.back())
+                                .and_then(|p| decode::ports(&p.raw_data))
+                                .map(|(s, d)| s.min(d));
+                            if let Some(port) = port {
+                                search_query.set(filter::port_filter(port));
+                                selected_stream = None;
+                            }
+                        }
+                        // Egress/ingress of one of the selected conversation's hosts
+                        KeyCode::Char('d') => {
+                            let ip = selected_stream.as_ref()
+                                .and_then(|s| session.conversations.get(s))
+                                .and_then(|c| c.messages.back())
+                                .map(|p| &p.raw_data)
+                                .filter(|ip| ip.len() >= 20);
+                            if let Some(ip) = ip {
+                                direction_menu = Some((Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]), Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19])));
+                            }
+                        }
+                        KeyCode::Char('l') => inspector_locked = !inspector_locked,
+                        KeyCode::Char('F') => { preset_menu = true; preset_name.clear(); preset_state.select(Some(0)); }
+                        KeyCode::Char('f') => { finding = true; finder_query.clear(); finder_state.select(Some(0)); }
+                        // Flush everything still held in memory, not just what the feed shows
+                        KeyCode::Char('w') => {
+                            let path = format!("vshark-{}.pcap", SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs());
+                            let result = std::fs::File::create(&path).map(io::BufWriter::new)
+                                .and_then(|mut out| network::write_pcap(&mut out, model::retained_packets(&session.conversations, &session.bookmarks)));
+                            notice = Some((match result {
+                                Ok(n) => format!("wrote {} packets to {}", n, path),
+                                Err(e) => format!("⚠ could not write {}: {}", path, e),
+                            }, Instant::now()));
+                        }
+                        KeyCode::Char('E') => {
+                            let path = format!("vshark-flows-{}.csv", SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs());
+                            let result = std::fs::File::create(&path).map(io::BufWriter::new)
+                                .and_then(|mut out| export::write_flow_csv(&mut out, &session.conversations));
+                            notice = Some((match result {
+                                Ok(n) => format!("wrote {} flows to {}", n, path),
+                                Err(e) => format!("⚠ could not write {}: {}", path, e),
+                            }, Instant::now()));
+                        }
+                        KeyCode::Char('H') => snapshot_pending = true,
+                        KeyCode::Char('c') if !clear_confirmed => clear_armed = Some(Instant::now()),
+                        KeyCode::Char('c') => clear_session(&mut session, &mut selected_stream),
+                        // Once a search has narrowed the sidebar, Tab keeps to its hits from anywhere
+                        KeyCode::Tab | KeyCode::BackTab if display_filter != Filter::All => if let Some(s) = model::step_stream(&streams, selected_stream.as_deref(), key.code == KeyCode::Tab) {
+                            selected_stream = Some(s);
+                        }
+                        KeyCode::Down if !streams.is_empty() => {
                             let i = match list_state.selected() { Some(i) => if i >= streams.len() - 1 { 0 } else { i + 1 }, None => 0 };
                             selected_stream = Some(streams[i].clone());
                         }
-                        KeyCode::Up => if !streams.is_empty() {
+                        KeyCode::Up if !streams.is_empty() => {
                             let i = match list_state.selected() { Some(i) => if i == 0 { streams.len() - 1 } else { i - 1 }, None => 0 };
                             selected_stream = Some(streams[i].clone());
                         }
@@ -205,6 +1253,224 @@ async fn main() -> Result<(), io::Error> {
     }
 
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
-    Ok(())
+    leave_screen(terminal.backend_mut(), config.mouse_capture())?;
+    export_on_exit(&config, &session.conversations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, Tcp};
+
+    #[test]
+    fn hex_dump_wraps_and_pads_to_width() {
+        let data: Vec<u8> = (b'A'..b'A' + 20).collect();
+        let dump = format_hex(&data, 8);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines, [
+            "0000  41 42 43 44 45 46 47 48  | ABCDEFGH",
+            "0008  49 4a 4b 4c 4d 4e 4f 50  | IJKLMNOP",
+            "0010  51 52 53 54              | QRST",
+        ]);
+        assert!(lines.iter().all(|l| l.find('|') == Some(31)));
+    }
+
+    #[test]
+    fn diff_highlights_exactly_the_changed_offsets() {
+        let a: Vec<u8> = (0..10).collect();
+        let mut b = a.clone();
+        b[2] = 0xff;
+        b[9] = 0xee;
+        b.push(0x42);
+        assert_eq!(ui::diff_offsets(&a, &b), [2, 9, 10]);
+
+        // Each line is the offset column, `width` byte cells of a, the divider, then `width` of b
+        let lines = diff_lines(&a, &b, 8);
+        assert_eq!(lines.len(), 2);
+        let highlighted = |side: usize| -> Vec<usize> {
+            lines.iter().enumerate().flat_map(|(row, line)| {
+                line.spans[1 + side * 9..9 + side * 9].iter().enumerate()
+                    .filter(|(_, span)| span.style.fg == Some(Color::Red))
+                    .map(move |(col, _)| row * 8 + col)
+            }).collect()
+        };
+        assert_eq!(highlighted(0), [2, 9]);
+        assert_eq!(highlighted(1), [2, 9, 10]);
+        assert_eq!(lines[1].spans[3].content, "   "); // Past the end of a
+    }
+
+    #[test]
+    fn headless_run_stops_at_the_duration() {
+        let (tx, rx) = mpsc::channel();
+        let flow = Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80);
+        for secs in [1, 2] {
+            tx.send(CaptureEvent::Packet(testutil::update(flow.packet(b""), secs))).unwrap();
+        }
+        tx.send(CaptureEvent::Dropped(model::DROP_NOT_IP)).unwrap();
+
+        // The capture is still running (tx is alive), so only the clock can end the loop
+        let mut clock = testutil::FakeClock(Duration::from_secs(100));
+        let (conversations, accounting) = run_headless(&rx, Some(Duration::from_secs(5)), usize::MAX, &mut clock);
+        assert_eq!(clock.0, Duration::from_secs(105));
+        assert_eq!(conversations["10.0.0.1 ➔ 10.0.0.2"].packets, 2);
+        assert_eq!(accounting.summary(), "seen 3 · processed 2 · dropped 1 (non-ip 1)");
+
+        // One interface stopping leaves the run going while another reader still holds the channel
+        tx.send(CaptureEvent::Stopped(Some("eth0".into()))).unwrap();
+        let mut clock = testutil::FakeClock::default();
+        run_headless(&rx, Some(Duration::from_secs(5)), usize::MAX, &mut clock);
+        assert_eq!(clock.0, Duration::from_secs(5));
+
+        // Without a duration it runs until the capture ends
+        drop(tx);
+        let mut clock = testutil::FakeClock::default();
+        run_headless(&rx, None, usize::MAX, &mut clock);
+        assert_eq!(clock.0, Duration::ZERO);
+    }
+
+    #[test]
+    fn picked_interface_is_captured_on() {
+        let interfaces = network::parse_interface_list("1. eth0\n2. wlan0 (Wi-Fi)\n3. any (Pseudo-device that captures on all interfaces)\n");
+        assert_eq!(interfaces, ["eth0", "wlan0", "any"]);
+
+        let pick = |keys: &[KeyCode]| {
+            let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(60, 20)).unwrap();
+            let mut keys = keys.iter().map(|&code| Event::Key(event::KeyEvent::from(code)));
+            pick_interface(&mut terminal, &interfaces, || Ok(keys.next().expect("picker wanted more keys"))).unwrap()
+        };
+        assert_eq!(pick(&[KeyCode::Enter]).as_deref(), Some("eth0"));
+        assert_eq!(pick(&[KeyCode::Up, KeyCode::Enter]).as_deref(), Some("any"));
+        assert_eq!(pick(&[KeyCode::Down, KeyCode::Esc]), None);
+
+        let mut config = config::Config::default();
+        config.interfaces.extend(pick(&[KeyCode::Down, KeyCode::Enter]));
+        assert_eq!(network::capture_interfaces(&config), ["wlan0"]);
+        assert!(network::dumpcap_args(&config, "wlan0").windows(2).any(|w| w == ["-i", "wlan0"]));
+    }
+
+    #[test]
+    fn locked_inspector_keeps_its_packet() {
+        let flow = Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80);
+        let mut conversations = HashMap::new();
+        let key = "10.0.0.1 ➔ 10.0.0.2".to_string();
+        let conversation = conversations.entry(key.clone()).or_insert_with(|| Conversation::new(Duration::from_secs(1)));
+        conversation.push(testutil::update(flow.packet(b"first"), 1));
+
+        let mut inspected = None;
+        follow_selection(&mut inspected, Some(&key), &conversations, false);
+        assert_eq!(inspected.as_ref().map(|p| p.ts), Some(Duration::from_secs(1)));
+
+        conversations.get_mut(&key).unwrap().push(testutil::update(flow.packet(b"second"), 2));
+        follow_selection(&mut inspected, Some(&key), &conversations, true);
+        assert_eq!(inspected.as_ref().map(|p| p.ts), Some(Duration::from_secs(1)));
+
+        follow_selection(&mut inspected, Some(&key), &conversations, false);
+        assert_eq!(inspected.as_ref().map(|p| p.ts), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn esc_dismisses_search_before_deselecting() {
+        let (mut searching, mut query, mut popup) = (true, TextInput::default(), false);
+        query.set("dns".to_string());
+        let mut selected = Some("10.0.0.1 ➔ 10.0.0.2".to_string());
+        let mut merge_pending = None;
+
+        escape(&mut searching, &mut query, &mut popup, &mut selected, &mut merge_pending);
+        assert!(!searching);
+        assert_eq!(query.as_str(), "");
+        assert_eq!(selected.as_deref(), Some("10.0.0.1 ➔ 10.0.0.2"));
+
+        escape(&mut searching, &mut query, &mut popup, &mut selected, &mut merge_pending);
+        assert_eq!(selected, None);
+    }
+
+    #[test]
+    fn reset_view_keeps_captured_data_and_clear_drops_it() {
+        let flow = Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80);
+        let key = "10.0.0.1 ➔ 10.0.0.2".to_string();
+        let packet = testutil::update(flow.packet(b"GET"), 1);
+        let mut session = Session::new(&config::Config::default());
+        session.capture_start = Some(Duration::from_secs(1));
+        session.conversations.entry(key.clone()).or_insert_with(|| Conversation::new(Duration::from_secs(1))).push(packet.clone());
+        session.pinned.insert(key.clone());
+        session.tag_totals.insert("HTTP".to_string(), 1);
+        session.bookmarks.toggle(&packet);
+        session.chat_history.push(packet);
+        let mut selected = Some(key.clone());
+        let mut query = TextInput::default();
+        query.set("GET".to_string());
+
+        reset_view(&mut selected, &mut query);
+        assert_eq!(selected, None);
+        assert_eq!(query.as_str(), "");
+        assert!(session.conversations.contains_key(&key));
+        assert!(session.pinned.contains(&key));
+        assert_eq!(session.chat_history.len(), 1);
+
+        selected = Some(key.clone());
+        clear_session(&mut session, &mut selected);
+        assert_eq!(selected, None);
+        assert_eq!(session.capture_start, None);
+        assert!(session.conversations.is_empty());
+        assert!(session.pinned.is_empty());
+        assert!(session.tag_totals.is_empty());
+        assert_eq!(session.bookmarks.len(), 0);
+        assert!(session.chat_history.is_empty());
+    }
+
+    #[test]
+    fn no_mouse_leaves_mouse_reporting_off() {
+        const MOUSE_ON: &str = "\x1b[?1000h";
+        let mut config = config::Config::default();
+        let mut out = Vec::new();
+        enter_screen(&mut out, config.mouse_capture()).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains(MOUSE_ON));
+
+        config.apply_args(["--no-mouse".to_string()]).unwrap();
+        let mut out = Vec::new();
+        enter_screen(&mut out, config.mouse_capture()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\x1b[?1049h"));
+        assert!(!out.contains(MOUSE_ON));
+    }
+
+    #[test]
+    fn pane_counts_follow_the_filter() {
+        let mut session = Session::new(&config::Config::default());
+        for (host, dport, secs) in [(1, 53, 1), (1, 53, 2), (2, 443, 3), (3, 443, 4), (3, 22, 5)] {
+            let packet = testutil::update(Tcp::new([10, 0, 0, host], 40000, [10, 0, 0, 100], dport).packet(b""), secs);
+            session.record(model::stream_key(&packet.summary).to_string(), packet);
+        }
+        let merges = model::Merges::default();
+        let shown = |filter: &Filter, selected: Option<&String>| {
+            let streams = model::visible_streams(&session.conversations, filter, 0, Duration::ZERO);
+            let feed = feed_packets(&session.chat_history, selected, filter, &merges, Duration::ZERO);
+            (ui::conversations_label(session.conversations.len(), streams.len()), ui::feed_title(feed.len(), session.chat_history.len(), ColorMode::Protocol))
+        };
+
+        assert_eq!(shown(&Filter::All, None), (" 3 conversations (3 shown) ".to_string(), " Feed ── 5 packets shown / 5 total ".to_string()));
+        assert_eq!(shown(&Filter::Port(443), None), (" 3 conversations (2 shown) ".to_string(), " Feed ── 2 packets shown / 5 total ".to_string()));
+        assert_eq!(shown(&Filter::Port(443), Some(&"10.0.0.3 ➔ 10.0.0.100".to_string())).1, " Feed ── 2 packets shown / 5 total ");
+        assert_eq!(shown(&Filter::Port(80), None).0, " 3 conversations (0 shown) ");
+    }
+
+    #[test]
+    fn flush_includes_packets_scrolled_out_of_the_feed() {
+        let flow = Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80);
+        let mut session = Session::new(&config::Config::default());
+        for secs in 1..=FEED_LEN as u64 + 10 {
+            session.record("10.0.0.1 ➔ 10.0.0.2".to_string(), testutil::update(flow.packet(&secs.to_be_bytes()), secs));
+        }
+        assert_eq!(session.chat_history.first().map(|p| p.ts), Some(Duration::from_secs(11)));
+
+        let mut out = Vec::new();
+        let written = network::write_pcap(&mut out, model::retained_packets(&session.conversations, &session.bookmarks)).unwrap();
+        assert_eq!(written, FEED_LEN + 10);
+
+        let (tx, rx) = mpsc::channel();
+        network::read_pcap(out.as_slice(), &tx, &config::TimeRange::default(), network::LinkOptions::default());
+        let stamps: Vec<Duration> = rx.try_iter().filter_map(|e| match e { CaptureEvent::Packet(p) => Some(p.ts), _ => None }).collect();
+        assert_eq!(stamps.len(), FEED_LEN + 10);
+        assert_eq!(stamps[..10], (1..=10).map(Duration::from_secs).collect::<Vec<_>>());
+    }
 }
@@ -1,11 +1,983 @@
 // src/model.rs
+use crate::decode;
+use crate::filter::Filter;
+use crate::network::PacketUpdate;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+// Direction-independent identity of a TCP/UDP flow: endpoints are stored in sorted order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FlowKey {
+    pub protocol: u8,
+    pub a: (Ipv4Addr, u16),
+    pub b: (Ipv4Addr, u16),
+}
+
+impl FlowKey {
+    pub fn new(protocol: u8, src: (Ipv4Addr, u16), dst: (Ipv4Addr, u16)) -> FlowKey {
+        let (a, b) = if src <= dst { (src, dst) } else { (dst, src) };
+        FlowKey { protocol, a, b }
+    }
+
+    pub fn from_packet(packet: &[u8]) -> Option<FlowKey> {
+        let (sport, dport) = decode::ports(packet)?;
+        let src = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+        let dst = Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]);
+        Some(FlowKey::new(packet[9], (src, sport), (dst, dport)))
+    }
+
+    // FNV-1a over the key's bytes: unlike std's RandomState, identical across runs
+    pub fn stable_hash(&self) -> u64 {
+        let mut bytes = vec![self.protocol];
+        for (ip, port) in [self.a, self.b] {
+            bytes.extend(ip.octets());
+            bytes.extend(port.to_be_bytes());
+        }
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+    }
+}
+
+// Direction-independent pair of subnets: `FlowKey` with hosts masked to a prefix and ports dropped,
+// so every conversation between two networks lands on the same key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubnetKey {
+    pub prefix: u8,
+    pub a: Ipv4Addr,
+    pub b: Ipv4Addr,
+}
+
+impl SubnetKey {
+    pub fn new(src: Ipv4Addr, dst: Ipv4Addr, prefix: u8) -> SubnetKey {
+        let mask = u32::MAX.checked_shl(32 - prefix.min(32) as u32).unwrap_or(0);
+        let (src, dst) = (Ipv4Addr::from(u32::from(src) & mask), Ipv4Addr::from(u32::from(dst) & mask));
+        let (a, b) = if src <= dst { (src, dst) } else { (dst, src) };
+        SubnetKey { prefix, a, b }
+    }
+
+    pub fn label(&self) -> String {
+        format!("{}/{} ⇄ {}/{}", self.a, self.prefix, self.b, self.prefix)
+    }
+}
+
+// Totals of the conversations sharing a `SubnetKey`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubnetGroup {
+    pub conversations: usize,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+// Aggregates conversations by the subnets of their first packet's endpoints, busiest first
+pub fn group_by_subnet<'a>(conversations: impl IntoIterator<Item = &'a Conversation>, prefix: u8) -> Vec<(SubnetKey, SubnetGroup)> {
+    let mut groups: HashMap<SubnetKey, SubnetGroup> = HashMap::new();
+    for conversation in conversations {
+        let Some(origin) = conversation.origin else { continue };
+        let group = groups.entry(SubnetKey::new(origin.src.0, origin.dst.0, prefix)).or_default();
+        group.conversations += 1;
+        group.packets += conversation.packets;
+        group.bytes += conversation.bytes;
+    }
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_by(|(ka, a), (kb, b)| b.bytes.cmp(&a.bytes).then(ka.cmp(kb)));
+    groups
+}
+
+// Private, link-local and loopback addresses are "ours"; everything else is remote
+pub fn is_local(ip: Ipv4Addr) -> bool {
+    ip.is_private() || ip.is_link_local() || ip.is_loopback()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlowDirection {
+    Outbound, // Local → remote
+    Inbound,  // Remote → local
+}
+
+impl FlowDirection {
+    // Between two local or two remote hosts there is no inside, so the flow's lower endpoint
+    // (as `FlowKey` sorts them) stands in for the local side
+    pub fn of(packet: &[u8]) -> Option<FlowDirection> {
+        let ip = packet.get(..20)?;
+        let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+        let dst = Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]);
+        let outbound = match (is_local(src), is_local(dst)) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => {
+                let (sport, dport) = decode::ports(packet).unwrap_or((0, 0));
+                FlowKey::new(ip[9], (src, sport), (dst, dport)).a == (src, sport)
+            }
+        };
+        Some(if outbound { FlowDirection::Outbound } else { FlowDirection::Inbound })
+    }
+}
+
+// A conversation's first packet as sent: who spoke first, to which port. Ports are 0 outside TCP/UDP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Endpoints {
+    pub protocol: u8,
+    pub src: (Ipv4Addr, u16),
+    pub dst: (Ipv4Addr, u16),
+    pub syn: bool, // The packet was a bare SYN, so the capture saw the connection open
+}
+
+impl Endpoints {
+    pub fn from_packet(packet: &[u8]) -> Option<Endpoints> {
+        let ip = packet.get(..20)?;
+        let (sport, dport) = decode::ports(packet).unwrap_or((0, 0));
+        Some(Endpoints {
+            protocol: ip[9],
+            src: (Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]), sport),
+            dst: (Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]), dport),
+            syn: decode::tcp_flags(packet).is_some_and(|f| f & 0x12 == 0x02),
+        })
+    }
+
+    // (client, server): a SYN's destination is the server; mid-stream, the side on the lower
+    // (well-known) port is, with the first packet's destination breaking ties
+    pub fn roles(&self) -> ((Ipv4Addr, u16), (Ipv4Addr, u16)) {
+        if !self.syn && self.src.1 < self.dst.1 { (self.dst, self.src) } else { (self.src, self.dst) }
+    }
+}
+
+// Seconds of per-conversation activity kept for the sidebar sparklines
+pub const ACTIVITY_SECONDS: usize = 8;
+
+// Packets per second over a conversation's most recent `ACTIVITY_SECONDS`, as a ring indexed by
+// capture second; seconds the ring has moved past are zeroed as it goes
+#[derive(Clone, Copy, Default)]
+pub struct Activity {
+    counts: [u64; ACTIVITY_SECONDS],
+    last: u64, // Capture second of the newest packet counted
+}
+
+impl Activity {
+    pub fn record(&mut self, ts: Duration) {
+        let second = ts.as_secs();
+        if second + (ACTIVITY_SECONDS as u64) <= self.last {
+            return;
+        }
+        if second > self.last {
+            for s in (self.last + 1).max(second.saturating_sub(ACTIVITY_SECONDS as u64 - 1))..=second {
+                self.counts[s as usize % ACTIVITY_SECONDS] = 0;
+            }
+            self.last = second;
+        }
+        self.counts[second as usize % ACTIVITY_SECONDS] += 1;
+    }
+
+    // Counts for the seconds up to and including `now`, oldest first; silence since the last packet reads as zeros
+    pub fn recent(&self, now: Duration) -> [u64; ACTIVITY_SECONDS] {
+        let now = now.as_secs().max(self.last);
+        let mut recent = [0; ACTIVITY_SECONDS];
+        for (i, slot) in recent.iter_mut().enumerate() {
+            let Some(second) = (now + 1 + i as u64).checked_sub(ACTIVITY_SECONDS as u64) else { continue };
+            if second + (ACTIVITY_SECONDS as u64) > self.last && second <= self.last {
+                *slot = self.counts[second as usize % ACTIVITY_SECONDS];
+            }
+        }
+        recent
+    }
+
+    fn absorb(&mut self, other: &Activity) {
+        let now = Duration::from_secs(self.last.max(other.last));
+        let (mine, theirs) = (self.recent(now), other.recent(now));
+        self.last = now.as_secs();
+        for (i, (a, b)) in mine.iter().zip(theirs).enumerate() {
+            if let Some(second) = (self.last + 1 + i as u64).checked_sub(ACTIVITY_SECONDS as u64) {
+                self.counts[second as usize % ACTIVITY_SECONDS] = a + b;
+            }
+        }
+    }
+}
+
 pub struct Conversation {
-    pub participants: (IpAddr, IpAddr),
-    pub messages: Vec<PacketData>,
+    pub packets: u64,                   // Every packet seen, including ones since evicted
+    pub bytes: u64,                     // On-the-wire length of every packet seen
+    pub captured_bytes: u64,            // What the capture kept of them; less than `bytes` under a snaplen
+    pub first_seen: Duration,           // Timestamp of the oldest packet, since the Unix epoch
+    pub last_seen: Duration,            // Timestamp of the newest packet, since the Unix epoch
+    pub origin: Option<Endpoints>,      // From the first packet
+    pub messages: VecDeque<PacketUpdate>, // Retained packets, oldest first
+    pub retained_bytes: usize,          // Estimated heap footprint of `messages`
+    pub tls: Option<decode::TlsParams>, // From the ServerHello, once one has been seen
+    pub activity: Activity,             // Recent packets per second, for the sidebar sparkline
+}
+
+impl Conversation {
+    pub fn new(ts: Duration) -> Conversation {
+        Conversation { packets: 0, bytes: 0, captured_bytes: 0, first_seen: ts, last_seen: ts, origin: None, messages: VecDeque::new(), retained_bytes: 0, tls: None, activity: Activity::default() }
+    }
+
+    pub fn push(&mut self, update: PacketUpdate) {
+        self.packets += 1;
+        self.bytes += update.orig_len as u64;
+        self.captured_bytes += update.cap_len as u64;
+        self.first_seen = self.first_seen.min(update.ts);
+        self.last_seen = self.last_seen.max(update.ts);
+        self.activity.record(update.ts);
+        if self.origin.is_none() {
+            self.origin = Endpoints::from_packet(&update.raw_data);
+        }
+        self.retained_bytes += update.memory_size();
+        if let Some(params) = decode::server_hello(&update.raw_data) {
+            self.tls = Some(params);
+        }
+        self.messages.push_back(update);
+    }
+
+    // Reassembles the latest HTTP message that `pkt`'s TCP direction started at or before `pkt`,
+    // from the retained segments in sequence order. Stops at the first gap.
+    pub fn http_message(&self, pkt: &PacketUpdate) -> Option<Vec<u8>> {
+        let direction = |p: &[u8]| p.get(12..20).map(<[u8]>::to_vec).zip(decode::ports(p));
+        let want = direction(&pkt.raw_data)?;
+        let mut segments: Vec<(u32, &[u8], Duration)> = self.messages.iter()
+            .filter(|m| direction(&m.raw_data).as_ref() == Some(&want))
+            .filter_map(|m| Some((decode::tcp_seq(&m.raw_data)?, decode::transport_payload(&m.raw_data), m.ts)))
+            .filter(|(_, payload, _)| !payload.is_empty())
+            .collect();
+        let start = segments.iter()
+            .filter(|(_, payload, ts)| *ts <= pkt.ts && decode::http_start_line(payload).is_some())
+            .map(|(seq, _, _)| *seq)
+            .next_back()?;
+        segments.retain(|(seq, _, _)| seq.wrapping_sub(start) < 1 << 31);
+        segments.sort_by_key(|(seq, _, _)| seq.wrapping_sub(start));
+
+        let mut message = Vec::new();
+        let mut expected = start;
+        for (seq, payload, _) in segments {
+            if seq.wrapping_sub(start) > expected.wrapping_sub(start) { break; } // Gap: lost or not yet seen
+            let skip = expected.wrapping_sub(seq) as usize; // Overlap with what's already assembled
+            if skip < payload.len() {
+                message.extend_from_slice(&payload[skip..]);
+                expected = expected.wrapping_add((payload.len() - skip) as u32);
+            }
+        }
+        Some(message)
+    }
+
+    // Time-sequence points for a TCP conversation, one series per direction ([origin's src → dst, reverse]):
+    // seconds since the first retained packet against the sequence number relative to that direction's
+    // first, so wraps and random ISNs don't matter. Only the origin's own flow is plotted.
+    pub fn seq_points(&self) -> Option<[Vec<(f64, f64)>; 2]> {
+        let origin = self.origin.filter(|o| o.protocol == 6)?;
+        let start = self.messages.front()?.ts;
+        let mut base: [Option<u32>; 2] = [None, None];
+        let mut points = [Vec::new(), Vec::new()];
+        for pkt in &self.messages {
+            let Some(ends) = Endpoints::from_packet(&pkt.raw_data) else { continue };
+            let direction = if (ends.src, ends.dst) == (origin.src, origin.dst) { 0 } else if (ends.src, ends.dst) == (origin.dst, origin.src) { 1 } else { continue };
+            let Some(seq) = decode::tcp_seq(&pkt.raw_data) else { continue };
+            let base = *base[direction].get_or_insert(seq);
+            points[direction].push((pkt.ts.saturating_sub(start).as_secs_f64(), seq.wrapping_sub(base) as f64));
+        }
+        Some(points)
+    }
+
+    // Re-tags the retained packets of one flow with a forced decode, keeping the memory estimate exact
+    pub fn decode_as(&mut self, flow: &FlowKey, decode_as: decode::DecodeAs) {
+        for pkt in self.messages.iter_mut().filter(|m| FlowKey::from_packet(&m.raw_data).as_ref() == Some(flow)) {
+            if let Some(tag) = decode_as.label(&pkt.raw_data) {
+                self.retained_bytes -= pkt.memory_size();
+                pkt.retag(tag);
+                self.retained_bytes += pkt.memory_size();
+            }
+        }
+    }
+
+    // Folds another conversation into this one: counts add up and retained packets interleave by time
+    pub fn absorb(&mut self, other: Conversation) {
+        self.packets += other.packets;
+        self.bytes += other.bytes;
+        self.captured_bytes += other.captured_bytes;
+        self.first_seen = self.first_seen.min(other.first_seen);
+        self.last_seen = self.last_seen.max(other.last_seen);
+        self.origin = self.origin.or(other.origin);
+        self.tls = self.tls.or(other.tls);
+        self.activity.absorb(&other.activity);
+        self.retained_bytes += other.retained_bytes;
+        self.messages.extend(other.messages);
+        self.messages.make_contiguous().sort_by_key(|p| (p.ts, p.frame));
+    }
+
+    fn evict_oldest(&mut self) -> bool {
+        match self.messages.pop_front() {
+            Some(old) => { self.retained_bytes -= old.memory_size(); true }
+            None => false,
+        }
+    }
+}
+
+// Conversations the user has merged into one logical entity (NAT, multi-homed hosts), for the session
+#[derive(Default)]
+pub struct Merges {
+    into: HashMap<String, String>, // Merged-away stream → the stream it now lives under
+}
+
+impl Merges {
+    // The stream packets of `key` are filed under
+    pub fn resolve<'a>(&'a self, key: &'a str) -> &'a str {
+        self.into.get(key).map_or(key, String::as_str)
+    }
+
+    // Streams folded into `key`, not counting itself
+    pub fn merged_into(&self, key: &str) -> usize {
+        self.into.values().filter(|target| *target == key).count()
+    }
+
+    // Moves `from` (and anything already merged into it) under `into`, combining their stats
+    pub fn merge(&mut self, conversations: &mut HashMap<String, Conversation>, from: &str, into: &str) {
+        let (from, into) = (self.resolve(from).to_string(), self.resolve(into).to_string());
+        if from == into {
+            return;
+        }
+        for target in self.into.values_mut().filter(|target| **target == from) {
+            target.clone_from(&into);
+        }
+        self.into.insert(from.clone(), into.clone());
+        if let Some(old) = conversations.remove(&from) {
+            match conversations.get_mut(&into) {
+                Some(conversation) => conversation.absorb(old),
+                None => { conversations.insert(into, old); }
+            }
+        }
+    }
+}
+
+// Conversation a packet is filed under: its summary without the trailing "[tag]"
+pub fn stream_key(summary: &str) -> &str {
+    summary.find(" [").map_or(summary, |pos| &summary[..pos])
+}
+
+// Drop reasons, from the capture reader through to in-app retention
+pub const DROP_LINK: &str = "link";      // Unsupported or non-data link-layer frame
+pub const DROP_NOT_IP: &str = "non-ip";  // No IPv4 header behind the link layer
+pub const DROP_NOISE: &str = "noise";    // ARP probes with no sender binding
+pub const DROP_ZERO_SRC: &str = "zero-src";   // IPv4 source 0.0.0.0 (DHCP discovery and the like)
+pub const DROP_ZERO_DST: &str = "zero-dst";   // IPv4 destination 0.0.0.0
+pub const DROP_BCAST_SRC: &str = "bcast-src"; // IPv4 source 255.255.255.255
+pub const DROP_RANGE: &str = "range";    // Outside --start/--end
+pub const DROP_SHED: &str = "shed";      // Evicted by the --max-memory guard
+pub const DROP_EXPIRED: &str = "expired"; // Retained by a conversation that hit --idle-timeout
+
+// Every record the reader sees ends up either processed or counted under exactly one drop reason,
+// so `seen` always equals processed plus dropped. Drops after processing (shed, expired) are
+// tracked separately since those packets were already counted as processed.
+#[derive(Default)]
+pub struct Accounting {
+    pub processed: u64,
+    pub dropped: BTreeMap<&'static str, u64>,
+}
+
+impl Accounting {
+    pub fn record_drop(&mut self, reason: &'static str, count: u64) {
+        if count > 0 {
+            *self.dropped.entry(reason).or_insert(0) += count;
+        }
+    }
+
+    fn reader_drops(&self) -> u64 {
+        self.dropped.iter().filter(|(r, _)| **r != DROP_SHED && **r != DROP_EXPIRED).map(|(_, n)| n).sum()
+    }
+
+    pub fn seen(&self) -> u64 {
+        self.processed + self.reader_drops()
+    }
+
+    pub fn total_dropped(&self) -> u64 {
+        self.dropped.values().sum()
+    }
+
+    // e.g. "seen 1200 · processed 1150 · dropped 70 (noise 50, shed 20)"
+    pub fn summary(&self) -> String {
+        let mut line = format!("seen {} · processed {} · dropped {}", self.seen(), self.processed, self.total_dropped());
+        if !self.dropped.is_empty() {
+            let reasons: Vec<String> = self.dropped.iter().map(|(r, n)| format!("{} {}", r, n)).collect();
+            line.push_str(&format!(" ({})", reasons.join(", ")));
+        }
+        line
+    }
+}
+
+// Protocol name a tag is counted under: "MQTT PUBLISH sensors/temp" -> "MQTT"
+pub fn protocol_of(tag: &str) -> &str {
+    tag.split_whitespace().next().unwrap_or(tag)
+}
+
+pub fn retained_bytes(conversations: &HashMap<String, Conversation>) -> usize {
+    conversations.values().map(|c| c.retained_bytes).sum()
+}
+
+// Soft memory cap: once usage exceeds `limit`, evicts the oldest packets of the largest
+// conversations until usage is back under 90% of it. Returns how many packets were shed.
+pub fn shed_to_limit(conversations: &mut HashMap<String, Conversation>, limit: usize) -> u64 {
+    let mut used = retained_bytes(conversations);
+    if used <= limit {
+        return 0;
+    }
+    let target = limit / 10 * 9;
+    let mut shed = 0;
+    while used > target {
+        let Some(largest) = conversations.values_mut().max_by_key(|c| c.retained_bytes) else { break };
+        let before = largest.retained_bytes;
+        if !largest.evict_oldest() { break; }
+        used -= before - largest.retained_bytes;
+        shed += 1;
+    }
+    shed
+}
+
+// Drops conversations that have been silent for longer than `timeout`; pinned streams never expire.
+// Returns how many retained packets went with them.
+pub fn expire_idle(conversations: &mut HashMap<String, Conversation>, pinned: &HashSet<String>, now: Duration, timeout: Duration) -> u64 {
+    let mut expired = 0;
+    conversations.retain(|key, conv| {
+        let keep = pinned.contains(key) || now.saturating_sub(conv.last_seen) <= timeout;
+        if !keep { expired += conv.messages.len() as u64; }
+        keep
+    });
+    expired
+}
+
+// Start of the rolling window, or the beginning of time without one. It follows packet time rather than
+// the wall clock, so it works the same for --read.
+pub fn active_since(latest: Duration, window: Option<Duration>) -> Duration {
+    window.map_or(Duration::ZERO, |window| latest.saturating_sub(window))
+}
+
+// Sidebar entries matching the display filter, sorted; a conversation matches if any retained packet does.
+// Conversations with fewer than `min_packets` packets, or silent since `active_since`, are left out but keep accumulating.
+pub fn visible_streams(conversations: &HashMap<String, Conversation>, filter: &Filter, min_packets: u64, active_since: Duration) -> Vec<String> {
+    let mut streams: Vec<String> = conversations.iter()
+        .filter(|(_, c)| c.packets >= min_packets && c.last_seen >= active_since)
+        .filter(|(_, c)| *filter == Filter::All || c.messages.iter().any(|p| filter.matches(p)))
+        .map(|(key, _)| key.clone())
+        .collect();
+    streams.sort();
+    streams
+}
+
+// Next (or previous) stream after `current` in the filtered list, wrapping; the first or last when
+// nothing in the list is selected yet
+pub fn step_stream(streams: &[String], current: Option<&str>, forward: bool) -> Option<String> {
+    let last = streams.len().checked_sub(1)?;
+    let i = match (streams.iter().position(|s| Some(s.as_str()) == current), forward) {
+        (Some(i), true) => if i == last { 0 } else { i + 1 },
+        (Some(i), false) => if i == 0 { last } else { i - 1 },
+        (None, true) => 0,
+        (None, false) => last,
+    };
+    Some(streams[i].clone())
+}
+
+// Every retained packet plus any bookmarked ones since evicted, in capture order, for pcap export
+pub fn retained_packets<'a>(conversations: &'a HashMap<String, Conversation>, bookmarks: &'a Bookmarks) -> Vec<&'a PacketUpdate> {
+    let mut packets: Vec<&PacketUpdate> = conversations.values().flat_map(|c| c.messages.iter()).chain(bookmarks.iter()).collect();
+    packets.sort_by_key(|p| (p.ts, p.frame));
+    packets.dedup_by_key(|p| (p.ts, p.frame));
+    packets
 }
 
-pub struct AppState {
-    pub conversations: HashMap<FlowKey, Conversation>,
-    pub selected_index: usize,
-    pub filter_query: String,
+// The earliest retained packet at or after `ts`, for jumping a loaded capture to a point in time
+pub fn first_packet_at(conversations: &HashMap<String, Conversation>, ts: Duration) -> Option<&PacketUpdate> {
+    conversations.values().flat_map(|c| c.messages.iter()).filter(|p| p.ts >= ts).min_by_key(|p| (p.ts, p.frame))
+}
+
+pub const DNS_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DNS_TRACKED: usize = 1024;
+
+// A query and its response: (client, server, transaction ID)
+type DnsKey = ((Ipv4Addr, u16), (Ipv4Addr, u16), u16);
+
+// A query paired with its response, for the inspector
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsExchange {
+    pub name: String,
+    pub rcode: u8,
+    pub answers: Vec<String>,
+    pub latency: Duration,
+}
+
+impl DnsExchange {
+    // "DNS github.com → 140.82.121.3 (12.3 ms)"
+    pub fn describe(&self) -> String {
+        let answers = if !self.answers.is_empty() { self.answers.join(", ") }
+            else if self.rcode == 0 { "no answers".to_string() }
+            else { decode::dns_rcode_name(self.rcode) };
+        format!("DNS {} → {} ({:.1} ms)", self.name, answers, self.latency.as_secs_f64() * 1000.0)
+    }
+}
+
+// Pairs DNS responses with their queries by transaction ID and endpoints. Both maps are bounded:
+// unanswered queries are dropped after a timeout, and the oldest entries go once `capacity` is reached.
+pub struct DnsTracker {
+    timeout: Duration,
+    capacity: usize,
+    pending: HashMap<DnsKey, (Duration, String)>,
+    pending_order: VecDeque<(Duration, DnsKey)>,
+    answered: HashMap<DnsKey, DnsExchange>,
+    answered_order: VecDeque<DnsKey>,
+}
+
+impl DnsTracker {
+    pub fn new(timeout: Duration, capacity: usize) -> DnsTracker {
+        DnsTracker { timeout, capacity, pending: HashMap::new(), pending_order: VecDeque::new(), answered: HashMap::new(), answered_order: VecDeque::new() }
+    }
+
+    // (key, message) for a DNS packet, with the client always the querying side
+    fn key(packet: &[u8]) -> Option<(DnsKey, decode::DnsMessage)> {
+        let message = decode::dns_message(decode::dns_payload(packet)?)?;
+        let (sport, dport) = decode::ports(packet)?;
+        let src = (Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]), sport);
+        let dst = (Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]), dport);
+        let (client, server) = if message.response { (dst, src) } else { (src, dst) };
+        Some(((client, server, message.id), message))
+    }
+
+    // Returns the exchange when `update` answers a pending query
+    pub fn observe(&mut self, update: &PacketUpdate) -> Option<DnsExchange> {
+        while let Some(&(ts, key)) = self.pending_order.front() {
+            if update.ts.saturating_sub(ts) <= self.timeout && self.pending_order.len() <= self.capacity { break; }
+            self.pending_order.pop_front();
+            // A retransmitted query re-inserts its key, so only forget the entry this record was for
+            if self.pending.get(&key).is_some_and(|(t, _)| *t == ts) { self.pending.remove(&key); }
+        }
+        let (key, message) = Self::key(&update.raw_data)?;
+        if !message.response {
+            self.pending.insert(key, (update.ts, message.name));
+            self.pending_order.push_back((update.ts, key));
+            return None;
+        }
+        let (asked, name) = self.pending.remove(&key)?;
+        let exchange = DnsExchange { name, rcode: message.rcode, answers: message.answers, latency: update.ts.saturating_sub(asked) };
+        if self.answered.insert(key, exchange.clone()).is_none() {
+            self.answered_order.push_back(key);
+        }
+        if self.answered_order.len() > self.capacity {
+            if let Some(oldest) = self.answered_order.pop_front() { self.answered.remove(&oldest); }
+        }
+        Some(exchange)
+    }
+
+    // The answered exchange a query or response packet belongs to
+    pub fn exchange(&self, packet: &[u8]) -> Option<&DnsExchange> {
+        self.answered.get(&Self::key(packet)?.0)
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.pending_order.clear();
+        self.answered.clear();
+        self.answered_order.clear();
+    }
+}
+
+// MAC addresses observed for each IP, from ARP senders and the Ethernet source of local traffic
+#[derive(Default)]
+pub struct ArpTable {
+    pub bindings: BTreeMap<Ipv4Addr, BTreeSet<[u8; 6]>>,
+}
+
+impl ArpTable {
+    pub fn observe(&mut self, ip: Ipv4Addr, mac: [u8; 6]) {
+        self.bindings.entry(ip).or_default().insert(mac);
+    }
+
+    // Frames from remote hosts carry the gateway's MAC, so only private/link-local sources are bound
+    pub fn observe_packet(&mut self, pkt: &PacketUpdate) {
+        if let (decode::LinkInfo::Ethernet { src, .. }, Some(ip)) = (pkt.link, pkt.raw_data.get(12..16)) {
+            let ip = Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]);
+            if ip.is_private() || ip.is_link_local() {
+                self.observe(ip, src);
+            }
+        }
+    }
+
+    // One IP claimed by several MACs is the classic ARP-spoofing signature
+    pub fn is_conflict(&self, ip: &Ipv4Addr) -> bool {
+        self.bindings.get(ip).is_some_and(|macs| macs.len() > 1)
+    }
+}
+
+// Packets marked with `b`, kept in capture order. Each mark holds its own copy of the packet,
+// so it survives eviction, and is identified by timestamp and frame number rather than list position.
+#[derive(Default)]
+pub struct Bookmarks {
+    marks: Vec<PacketUpdate>,
+    cursor: Option<usize>, // Mark last jumped to with `n`/`N`
+}
+
+impl Bookmarks {
+    fn position(&self, pkt: &PacketUpdate) -> Result<usize, usize> {
+        self.marks.binary_search_by(|m| m.ts.cmp(&pkt.ts).then(m.frame.cmp(&pkt.frame)))
+    }
+
+    pub fn is_marked(&self, pkt: &PacketUpdate) -> bool {
+        self.position(pkt).is_ok()
+    }
+
+    // Marks the packet, or unmarks it if it already was
+    pub fn toggle(&mut self, pkt: &PacketUpdate) {
+        match self.position(pkt) {
+            Ok(i) => { self.marks.remove(i); self.cursor = None; }
+            Err(i) => { self.marks.insert(i, pkt.clone()); self.cursor = Some(i); }
+        }
+    }
+
+    // Steps forward through the marks, wrapping around at the end
+    pub fn next(&mut self) -> Option<&PacketUpdate> {
+        if self.marks.is_empty() { return None; }
+        let i = self.cursor.map_or(0, |c| (c + 1) % self.marks.len());
+        self.cursor = Some(i);
+        self.marks.get(i)
+    }
+
+    pub fn prev(&mut self) -> Option<&PacketUpdate> {
+        if self.marks.is_empty() { return None; }
+        let i = self.cursor.map_or(self.marks.len() - 1, |c| (c + self.marks.len() - 1) % self.marks.len());
+        self.cursor = Some(i);
+        self.marks.get(i)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PacketUpdate> {
+        self.marks.iter()
+    }
+
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+
+    pub fn len(&self) -> usize {
+        self.marks.len()
+    }
+
+    // The two marks to diff: the one last jumped to (or the first) and the one after it
+    pub fn diff_pair(&self) -> Option<(&PacketUpdate, &PacketUpdate)> {
+        let i = self.cursor.unwrap_or(0).min(self.marks.len().checked_sub(2)?);
+        Some((&self.marks[i], &self.marks[i + 1]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, Tcp, SYN};
+
+    // Files packets under their stream keys, as the UI does on ingest
+    fn ingest(conversations: &mut HashMap<String, Conversation>, packets: impl IntoIterator<Item = PacketUpdate>) {
+        for update in packets {
+            conversations.entry(stream_key(&update.summary).to_string()).or_insert_with(|| Conversation::new(update.ts)).push(update);
+        }
+    }
+
+    fn web(host: u8) -> Tcp {
+        Tcp::new([10, 0, 0, host], 40000, [93, 184, 216, 34], 80)
+    }
+
+    #[test]
+    fn expire_idle_drops_only_silent_flows() {
+        let mut conversations = HashMap::new();
+        ingest(&mut conversations, [testutil::update(web(1).packet(b"a"), 10), testutil::update(web(1).packet(b"b"), 11), testutil::update(web(2).packet(b"c"), 95)]);
+        let expired = expire_idle(&mut conversations, &HashSet::new(), Duration::from_secs(100), Duration::from_secs(30));
+        assert_eq!(expired, 2);
+        assert_eq!(conversations.keys().collect::<Vec<_>>(), ["10.0.0.2 ➔ 93.184.216.34"]);
+    }
+
+    #[test]
+    fn shedding_evicts_oldest_and_counts_drops() {
+        let mut conversations = HashMap::new();
+        ingest(&mut conversations, (0..10).map(|i| testutil::update(web(1).packet(&[0; 100]), i)));
+        ingest(&mut conversations, [testutil::update(web(2).packet(&[0; 100]), 5)]);
+        let limit = retained_bytes(&conversations) / 2;
+
+        let mut accounting = Accounting::default();
+        let shed = shed_to_limit(&mut conversations, limit);
+        accounting.record_drop(DROP_SHED, shed);
+
+        assert!(shed > 0);
+        assert!(retained_bytes(&conversations) <= limit);
+        assert_eq!(accounting.dropped.get(DROP_SHED), Some(&shed));
+        let busy = &conversations["10.0.0.1 ➔ 93.184.216.34"];
+        assert_eq!(busy.packets, 10);
+        assert_eq!(busy.messages.len() as u64, 10 - shed);
+        assert_eq!(busy.messages.front().map(|p| p.ts), Some(Duration::from_secs(shed)));
+        assert_eq!(shed_to_limit(&mut conversations, limit), 0);
+    }
+
+    #[test]
+    fn arp_table_flags_an_ip_claimed_by_two_macs() {
+        let (genuine, spoofer) = ([0x00, 0x50, 0x56, 0, 0, 1], [0x02, 0, 0, 0, 0, 0x66]);
+        let gateway = Ipv4Addr::new(192, 168, 1, 1);
+        let mut table = ArpTable::default();
+        table.observe(gateway, genuine);
+        assert!(!table.is_conflict(&gateway));
+
+        let mut forged = testutil::update(Tcp::new([192, 168, 1, 1], 53, [192, 168, 1, 20], 40000).packet(b""), 1);
+        forged.link = decode::LinkInfo::Ethernet { src: spoofer, dst: [0x02, 0, 0, 0, 0, 0x20] };
+        table.observe_packet(&forged);
+        assert!(table.is_conflict(&gateway));
+        assert_eq!(table.bindings[&gateway].len(), 2);
+
+        // Remote sources arrive behind the router's MAC and aren't bound
+        let mut remote = testutil::update(web(1).packet(b""), 1);
+        remote.raw_data[12..16].copy_from_slice(&[93, 184, 216, 34]);
+        remote.link = decode::LinkInfo::Ethernet { src: genuine, dst: spoofer };
+        table.observe_packet(&remote);
+        assert!(!table.bindings.contains_key(&Ipv4Addr::new(93, 184, 216, 34)));
+    }
+
+    #[test]
+    fn bookmarks_cycle_in_capture_order() {
+        let packets: Vec<PacketUpdate> = [30, 10, 20].into_iter().map(|t| testutil::update(web(1).packet(b""), t)).collect();
+        let mut bookmarks = Bookmarks::default();
+        for pkt in &packets { bookmarks.toggle(pkt); }
+        bookmarks.toggle(&packets[0]);
+        bookmarks.toggle(&packets[0]);
+        assert_eq!(bookmarks.len(), 3);
+
+        let secs = |p: Option<&PacketUpdate>| p.map(|p| p.ts.as_secs());
+        assert_eq!(secs(bookmarks.next()), Some(10));
+        assert_eq!(secs(bookmarks.next()), Some(20));
+        assert_eq!(secs(bookmarks.next()), Some(30));
+        assert_eq!(secs(bookmarks.next()), Some(10));
+        assert_eq!(secs(bookmarks.prev()), Some(30));
+        assert!(bookmarks.is_marked(&packets[1]));
+        assert!(Bookmarks::default().next().is_none());
+    }
+
+    #[test]
+    fn min_packets_hides_without_dropping() {
+        let mut conversations = HashMap::new();
+        ingest(&mut conversations, (0..3).map(|i| testutil::update(web(1).packet(b""), i)));
+        ingest(&mut conversations, [testutil::update(web(2).packet(b""), 0)]);
+
+        let visible = visible_streams(&conversations, &Filter::All, 2, Duration::ZERO);
+        assert_eq!(visible, ["10.0.0.1 ➔ 93.184.216.34"]);
+        assert_eq!(conversations.len(), 2);
+
+        ingest(&mut conversations, [testutil::update(web(2).packet(b""), 1)]);
+        assert_eq!(visible_streams(&conversations, &Filter::All, 2, Duration::ZERO).len(), 2);
+        assert_eq!(conversations["10.0.0.2 ➔ 93.184.216.34"].packets, 2);
+    }
+
+    #[test]
+    fn forced_http_decode_labels_the_request_line() {
+        let client = Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 8080);
+        let request = testutil::update(client.packet(b"GET /api/items?page=2 HTTP/1.1\r\nHost: app\r\n\r\n"), 1);
+        let opaque = testutil::update(client.packet(b"\x16\x00\x01binary"), 2);
+        let flow = FlowKey::from_packet(&request.raw_data).unwrap();
+        let mut conversation = Conversation::new(request.ts);
+        conversation.push(request);
+        conversation.push(opaque);
+
+        conversation.decode_as(&flow, decode::DecodeAs::Http);
+        assert_eq!(conversation.messages[0].tag.as_deref(), Some("HTTP GET /api/items?page=2"));
+        assert_eq!(conversation.messages[0].summary, "10.0.0.1 ➔ 10.0.0.2 [HTTP GET /api/items?page=2]");
+        assert_eq!(conversation.messages[1].tag, None);
+        assert_eq!(conversation.retained_bytes, conversation.messages.iter().map(PacketUpdate::memory_size).sum::<usize>());
+    }
+
+    #[test]
+    fn rolling_window_hides_flows_silent_since_it_began() {
+        let mut conversations = HashMap::new();
+        ingest(&mut conversations, [testutil::update(web(1).packet(b""), 10), testutil::update(web(1).packet(b""), 60)]);
+        ingest(&mut conversations, [testutil::update(web(2).packet(b""), 20)]);
+        ingest(&mut conversations, [testutil::update(web(3).packet(b""), 100)]);
+
+        let since = active_since(Duration::from_secs(100), Some(Duration::from_secs(60)));
+        assert_eq!(since, Duration::from_secs(40));
+        assert_eq!(visible_streams(&conversations, &Filter::All, 0, since), ["10.0.0.1 ➔ 93.184.216.34", "10.0.0.3 ➔ 93.184.216.34"]);
+        assert_eq!(visible_streams(&conversations, &Filter::All, 0, active_since(Duration::from_secs(100), None)).len(), 3);
+        assert_eq!(active_since(Duration::from_secs(5), Some(Duration::from_secs(60))), Duration::ZERO);
+    }
+
+    #[test]
+    fn merged_conversations_list_once_with_combined_counts() {
+        let mut conversations = HashMap::new();
+        ingest(&mut conversations, [testutil::update(web(1).packet(&[0; 10]), 1), testutil::update(web(1).packet(&[0; 10]), 3)]);
+        ingest(&mut conversations, [testutil::update(web(2).packet(&[0; 20]), 2)]);
+        ingest(&mut conversations, [testutil::update(web(3).packet(b""), 4)]);
+        let (a, b, c) = ("10.0.0.1 ➔ 93.184.216.34", "10.0.0.2 ➔ 93.184.216.34", "10.0.0.3 ➔ 93.184.216.34");
+
+        let mut merges = Merges::default();
+        merges.merge(&mut conversations, b, a);
+        merges.merge(&mut conversations, a, c); // Chains: b now lives under c as well
+        assert_eq!(visible_streams(&conversations, &Filter::All, 0, Duration::ZERO), [c]);
+        assert_eq!((merges.resolve(a), merges.resolve(b)), (c, c));
+        assert_eq!(merges.merged_into(c), 2);
+
+        let merged = &conversations[c];
+        assert_eq!(merged.packets, 4);
+        assert_eq!(merged.bytes, 2 * 50 + 60 + 40);
+        assert_eq!((merged.first_seen, merged.last_seen), (Duration::from_secs(1), Duration::from_secs(4)));
+        assert_eq!(merged.messages.iter().map(|p| p.ts.as_secs()).collect::<Vec<_>>(), [1, 2, 3, 4]);
+        assert_eq!(merged.retained_bytes, merged.messages.iter().map(PacketUpdate::memory_size).sum::<usize>());
+    }
+
+    #[test]
+    fn stepping_visits_only_the_filtered_streams() {
+        let mut conversations = HashMap::new();
+        for (host, dport) in [(1, 443), (2, 80), (3, 443), (4, 443)] {
+            ingest(&mut conversations, [testutil::update(Tcp::new([10, 0, 0, host], 40000, [93, 184, 216, 34], dport).packet(b""), 1)]);
+        }
+        let streams = visible_streams(&conversations, &Filter::Port(443), 0, Duration::ZERO);
+
+        let mut visited = Vec::new();
+        let mut current = None;
+        for _ in 0..4 {
+            current = step_stream(&streams, current.as_deref(), true);
+            visited.push(current.clone().unwrap());
+        }
+        assert_eq!(visited, ["10.0.0.1 ➔ 93.184.216.34", "10.0.0.3 ➔ 93.184.216.34", "10.0.0.4 ➔ 93.184.216.34", "10.0.0.1 ➔ 93.184.216.34"]);
+        assert_eq!(step_stream(&streams, Some(&streams[0]), false).as_ref(), streams.last());
+
+        // A selection the filter hides starts the cycle over from either end
+        assert_eq!(step_stream(&streams, Some("10.0.0.2 ➔ 93.184.216.34"), true).as_ref(), streams.first());
+        assert_eq!(step_stream(&streams, Some("10.0.0.2 ➔ 93.184.216.34"), false).as_ref(), streams.last());
+        assert_eq!(step_stream(&[], None, true), None);
+    }
+
+    #[test]
+    fn jump_lands_on_the_first_packet_at_or_after_the_time() {
+        let at = |host, millis, frame| PacketUpdate { ts: Duration::from_millis(millis), frame, ..testutil::update(web(host).packet(b""), 0) };
+        let mut conversations = HashMap::new();
+        ingest(&mut conversations, [at(1, 1000, 1), at(1, 4000, 5), at(1, 9000, 8)]);
+        ingest(&mut conversations, [at(2, 2500, 3), at(2, 2500, 2), at(2, 6000, 6)]);
+        let landed = |secs: f64| first_packet_at(&conversations, Duration::from_secs_f64(secs)).map(|p| p.frame);
+
+        assert_eq!(landed(0.0), Some(1));
+        assert_eq!(landed(2.0), Some(2)); // Across streams, and the lower frame of a tie
+        assert_eq!(landed(4.0), Some(5)); // Exactly on a packet
+        assert_eq!(landed(4.001), Some(6));
+        assert_eq!(landed(9.5), None);
+    }
+
+    // A query for github.com with transaction `id`, or its response carrying `answer`
+    fn dns(id: u16, answer: Option<[u8; 4]>, millis: u64) -> PacketUpdate {
+        let mut message = id.to_be_bytes().to_vec();
+        message.extend_from_slice(&[if answer.is_some() { 0x81 } else { 0x01 }, 0x80, 0, 1, 0, answer.is_some() as u8, 0, 0, 0, 0]);
+        message.extend_from_slice(b"\x06github\x03com\x00\x00\x01\x00\x01");
+        if let Some(address) = answer {
+            message.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+            message.extend_from_slice(&address);
+        }
+        let (client, server) = (([10, 0, 0, 1], 53000), ([1, 1, 1, 1], 53));
+        let ((src, sport), (dst, dport)) = if answer.is_some() { (server, client) } else { (client, server) };
+        let mut update = testutil::update(testutil::udp(src, sport, dst, dport, &message), 0);
+        update.ts = Duration::from_millis(millis);
+        update
+    }
+
+    #[test]
+    fn dns_responses_pair_with_their_queries() {
+        let mut tracker = DnsTracker::new(DNS_QUERY_TIMEOUT, DNS_TRACKED);
+        assert_eq!(tracker.observe(&dns(7, None, 1000)), None);
+        assert_eq!(tracker.observe(&dns(8, None, 1001)), None);
+        let exchange = tracker.observe(&dns(7, Some([140, 82, 121, 3]), 1012)).unwrap();
+        assert_eq!(exchange.latency, Duration::from_millis(12));
+        assert_eq!(exchange.describe(), "DNS github.com → 140.82.121.3 (12.0 ms)");
+        assert_eq!(tracker.exchange(&dns(7, None, 0).raw_data), Some(&exchange));
+        assert_eq!(tracker.exchange(&dns(8, None, 0).raw_data), None);
+
+        // Answered once only, and a response after the query timed out has nothing to pair with
+        assert_eq!(tracker.observe(&dns(7, Some([140, 82, 121, 3]), 1020)), None);
+        assert_eq!(tracker.observe(&dns(8, Some([140, 82, 121, 4]), 1001 + 6000)), None);
+    }
+
+    #[test]
+    fn seq_points_follow_each_direction() {
+        let mut client = Tcp::new([10, 0, 0, 1], 40000, [93, 184, 216, 34], 80);
+        let mut server = Tcp::new([93, 184, 216, 34], 80, [10, 0, 0, 1], 40000);
+        client.seq = u32::MAX - 99; // Wraps partway through
+        server.seq = 7_000;
+        let mut conversation = Conversation::new(Duration::ZERO);
+        for (millis, from_client, len) in [(1000, true, 100), (1250, false, 500), (1500, true, 200), (2000, true, 0)] {
+            let side = if from_client { &mut client } else { &mut server };
+            let mut update = testutil::update(side.packet(&vec![0; len]), 0);
+            update.ts = Duration::from_millis(millis);
+            conversation.push(update);
+            side.seq = side.seq.wrapping_add(len as u32);
+        }
+        // Another flow filed under the same stream isn't plotted
+        conversation.push(testutil::update(Tcp::new([10, 0, 0, 1], 40001, [93, 184, 216, 34], 80).packet(b"x"), 2));
+
+        let [outbound, inbound] = conversation.seq_points().unwrap();
+        assert_eq!(outbound, [(0.0, 0.0), (0.5, 100.0), (1.0, 300.0)]);
+        assert_eq!(inbound, [(0.25, 0.0)]);
+
+        let mut udp = Conversation::new(Duration::ZERO);
+        udp.push(testutil::update(testutil::udp([10, 0, 0, 1], 5000, [10, 0, 0, 2], 53, b""), 1));
+        assert!(udp.seq_points().is_none());
+    }
+
+    #[test]
+    fn hosts_in_one_subnet_group_together() {
+        let mut conversations = HashMap::new();
+        // Three hosts of 10.0.0.0/24 talking to one server, one of them answered from the server side
+        ingest(&mut conversations, [testutil::update(web(1).packet(&[0; 10]), 1), testutil::update(web(1).packet(&[0; 10]), 2)]);
+        ingest(&mut conversations, [testutil::update(web(2).packet(&[0; 10]), 1)]);
+        ingest(&mut conversations, [testutil::update(Tcp::new([93, 184, 216, 34], 80, [10, 0, 0, 200], 40000).packet(&[0; 10]), 1)]);
+        ingest(&mut conversations, [testutil::update(Tcp::new([10, 0, 1, 1], 40000, [93, 184, 216, 34], 80).packet(b""), 1)]);
+
+        let groups = group_by_subnet(conversations.values(), 24);
+        let local = SubnetKey::new(Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(93, 184, 216, 0), 24);
+        assert_eq!(local.label(), "10.0.0.0/24 ⇄ 93.184.216.0/24");
+        assert_eq!(groups[0], (local, SubnetGroup { conversations: 3, packets: 4, bytes: 4 * 50 }));
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[1].1.conversations, 1);
+
+        // A /16 takes the neighbouring subnet in too
+        assert_eq!(group_by_subnet(conversations.values(), 16).len(), 1);
+    }
+
+    #[test]
+    fn server_role_goes_to_the_service_side() {
+        let roles = |tcp: Tcp| Endpoints::from_packet(&tcp.packet(b"")).unwrap().roles();
+        let (client, server) = (([10, 0, 0, 1].into(), 40000), ([93, 184, 216, 34].into(), 443));
+
+        // Capture joined mid-stream: the first packet seen is the server's reply
+        assert_eq!(roles(Tcp::new([93, 184, 216, 34], 443, [10, 0, 0, 1], 40000)), (client, server));
+        assert_eq!(roles(Tcp::new([10, 0, 0, 1], 40000, [93, 184, 216, 34], 443)), (client, server));
+
+        // A SYN names the server outright, even to a high port from a low one
+        let syn = Tcp::new([10, 0, 0, 1], 53, [10, 0, 0, 2], 8080).flags(SYN);
+        assert_eq!(roles(syn), (([10, 0, 0, 1].into(), 53), ([10, 0, 0, 2].into(), 8080)));
+        // Equal ports: whoever was sent to serves
+        assert_eq!(roles(Tcp::new([10, 0, 0, 1], 5000, [10, 0, 0, 2], 5000)).1, ([10, 0, 0, 2].into(), 5000));
+    }
+
+    #[test]
+    fn accounting_aggregates_drops_by_stage() {
+        let mut accounting = Accounting { processed: 100, ..Accounting::default() };
+        for (reason, n) in [(DROP_NOT_IP, 3), (DROP_ZERO_SRC, 2), (DROP_RANGE, 5), (DROP_NOT_IP, 1), (DROP_LINK, 0)] {
+            accounting.record_drop(reason, n);
+        }
+        // Shed and expired packets were already processed, so they don't add to what was seen
+        accounting.record_drop(DROP_SHED, 7);
+        accounting.record_drop(DROP_EXPIRED, 4);
+
+        assert_eq!(accounting.seen(), 111);
+        assert_eq!(accounting.total_dropped(), 22);
+        assert!(!accounting.dropped.contains_key(DROP_LINK));
+        assert_eq!(accounting.summary(), "seen 111 · processed 100 · dropped 22 (expired 4, non-ip 4, range 5, shed 7, zero-src 2)");
+    }
+
+    #[test]
+    fn chunked_json_body_is_reassembled_and_pretty_printed() {
+        let head = b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\nc\r\n{\"ok\":true,\"\r\n";
+        let tail = b"b\r\nids\":[1,2]}\r\n0\r\n\r\n";
+        let mut server = Tcp::new([93, 184, 216, 34], 80, [10, 0, 0, 1], 40000);
+        server.seq = 1000;
+        let first = testutil::update(server.packet(head), 1);
+        server.seq += head.len() as u32;
+        let second = testutil::update(server.packet(tail), 2);
+
+        let mut conversation = Conversation::new(first.ts);
+        conversation.push(second.clone());
+        conversation.push(first);
+        let message = conversation.http_message(&second).unwrap();
+        assert_eq!(decode::http_body(&message).as_deref(), Some(&b"{\"ok\":true,\"ids\":[1,2]}"[..]));
+        assert_eq!(decode::json_body(&message).as_deref(), Some("{\n  \"ok\": true,\n  \"ids\": [\n    1,\n    2\n  ]\n}"));
+    }
 }
@@ -1,81 +1,1181 @@
+use crate::config::{Config, TimeRange};
+use crate::decode::{self, LinkInfo};
+use crate::model::{FlowKey, DROP_BCAST_SRC, DROP_LINK, DROP_NOISE, DROP_NOT_IP, DROP_RANGE, DROP_ZERO_DST, DROP_ZERO_SRC};
+use std::collections::HashMap;
 use std::process::{Command, Stdio, Child};
-use std::io::Read;
-use std::sync::mpsc::Sender;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, mpsc::{self, Receiver, Sender}, Arc};
 use etherparse::Ipv4Header;
+use flate2::bufread::MultiGzDecoder;
+use tracing::{debug, warn};
+use pcap_parser::{traits::PcapReaderIterator, LegacyPcapReader, Linktype, PcapBlockOwned, PcapError};
 use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
 
+#[derive(Clone)]
 pub struct PacketUpdate {
     pub summary: String,
+    pub tag: Option<String>,
+    pub labels: Vec<String>, // Hook labels, kept apart from the protocol tag so a retag can't drop them
     pub raw_data: Vec<u8>, // New: Holds the actual packet bytes
+    pub cap_len: u32,      // Bytes of the frame actually captured (after snaplen)
+    pub orig_len: u32,     // Length of the frame on the wire
+    pub ts: Duration,      // Capture timestamp since the Unix epoch
+    pub link: LinkInfo,    // L2 addressing stripped before IP parsing
+    pub frame: u64,        // 1-based record number in the capture, as Wireshark's "No." column
+    pub interface: Option<Arc<str>>, // Capturing interface, when one was named with --interface
+    pub dup_ack: Option<u32>,  // Which repeat of its direction's last ACK this is; set by the UI on ingest
 }
 
-pub fn run_sniffer(tx: Sender<PacketUpdate>) -> Child {
-    let mut child = Command::new("/run/wrappers/bin/dumpcap")
-        // REMOVED "-f ip" to ensure data flows; we'll filter in Rust instead
-        .args(["-i", "any", "-F", "pcap", "-n", "-q", "-w", "-"])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()
-        .expect("Failed to spawn dumpcap");
+impl PacketUpdate {
+    pub fn is_truncated(&self) -> bool {
+        self.cap_len < self.orig_len
+    }
 
-    let mut stdout = child.stdout.take().expect("Failed to take stdout");
+    // Hook labels ahead of the protocol tag, as the summary's "[...]" suffix shows them, e.g. "DB HTTPS"
+    pub fn display_tag(&self) -> Option<String> {
+        let parts: Vec<&str> = self.labels.iter().map(String::as_str).chain(self.tag.as_deref()).collect();
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
 
-    tokio::task::spawn_blocking(move || {
-        let mut buffer = Vec::new();
-        let mut temp_buf = [0u8; 2048];
+    // Replaces the protocol tag, keeping any hook labels and the summary's suffix in step
+    pub fn retag(&mut self, tag: String) {
+        self.tag = Some(tag);
+        self.resummarize();
+    }
+
+    pub fn set_labels(&mut self, labels: Vec<String>) {
+        self.labels = labels;
+        self.resummarize();
+    }
+
+    fn resummarize(&mut self) {
+        let key = crate::model::stream_key(&self.summary).to_string();
+        self.summary = match self.display_tag() {
+            Some(tag) => format!("{} [{}]", key, tag),
+            None => key,
+        };
+    }
+
+    // Rough heap + inline footprint, used by the --max-memory guard
+    pub fn memory_size(&self) -> usize {
+        let labels: usize = self.labels.iter().map(String::len).sum();
+        std::mem::size_of::<Self>() + self.raw_data.len() + self.summary.len() + self.tag.as_ref().map_or(0, |t| t.len()) + labels
+    }
+}
+
+// What a packet's summary is generated from, read back out of `raw_data` with the decode helpers
+// rather than parsed from the summary string. `V` shows its rendering beside the legacy summary, so
+// the two can be checked for parity while the string-based paths are retired. The tag is decoded
+// afresh from the bytes, so a summary tagged any other way (flow state, "inspect as") shows as differing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PacketFields {
+    pub protocol: u8,
+    pub src: Ipv4Addr,
+    pub dst: Ipv4Addr,
+    pub ports: Option<(u16, u16)>,
+    pub tag: Option<String>,
+}
+
+impl PacketFields {
+    pub fn of(pkt: &PacketUpdate) -> Option<PacketFields> {
+        let ip = pkt.raw_data.get(..20)?;
+        let decoded = decode::app_label(&pkt.raw_data);
+        let parts: Vec<&str> = pkt.labels.iter().map(String::as_str).chain(decoded.as_deref()).collect();
+        Some(PacketFields {
+            protocol: ip[9],
+            src: Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]),
+            dst: Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]),
+            ports: decode::ports(&pkt.raw_data),
+            tag: (!parts.is_empty()).then(|| parts.join(" ")),
+        })
+    }
+
+    // The summary these fields generate, in the feed's "src ➔ dst [tag]" form
+    pub fn summary(&self) -> String {
+        match &self.tag {
+            Some(tag) => format!("{} ➔ {} [{}]", self.src, self.dst, tag),
+            None => format!("{} ➔ {}", self.src, self.dst),
+        }
+    }
+
+    // Field-by-field rendering, e.g. "TCP 10.0.0.1:40000 → 10.0.0.2:443 · HTTPS"
+    pub fn render(&self) -> String {
+        let mut text = match self.ports {
+            Some((sport, dport)) => format!("{} {}:{} → {}:{}", decode::ip_protocol_name(self.protocol), self.src, sport, self.dst, dport),
+            None => format!("{} {} → {}", decode::ip_protocol_name(self.protocol), self.src, self.dst),
+        };
+        if let Some(tag) = &self.tag {
+            text.push_str(&format!(" · {}", tag));
+        }
+        text
+    }
+}
+
+// Everything the capture thread reports to the UI
+pub enum CaptureEvent {
+    Packet(PacketUpdate),
+    Binding(Ipv4Addr, [u8; 6]), // IP-to-MAC mapping announced by an ARP sender
+    Stopped(Option<Arc<str>>),  // A live capture's stdout hit EOF (dumpcap exited or was killed), with its interface if named
+    Dropped(&'static str),      // A record the reader saw but didn't deliver, with the reason
+    Loaded,                     // Every --read source has been consumed
+}
+
+// Per-flow state for labels that depend on earlier packets of the same conversation
+#[derive(Default)]
+pub struct FlowTracker {
+    websocket: HashMap<FlowKey, bool>, // false = upgrade requested, true = switched to WebSocket framing
+}
+
+impl FlowTracker {
+    // Tag for a flow switched to WebSocket framing, or None to fall back to the decoder registry
+    pub fn label(&mut self, packet: &[u8]) -> Option<String> {
+        if packet.get(9) != Some(&6) {
+            return None;
+        }
+        let key = FlowKey::from_packet(packet)?;
+        let payload = decode::transport_payload(packet);
+
+        // Once upgraded the bytes are WebSocket frames, not HTTP
+        if self.websocket.get(&key) == Some(&true) {
+            return Some(match decode::websocket_opcode(payload) {
+                Some(op) if !payload.is_empty() => format!("WS {}", op),
+                _ => "WS".to_string(),
+            });
+        }
+        if decode::is_websocket_upgrade(payload) {
+            self.websocket.insert(key, false);
+        } else if decode::is_websocket_accept(payload) && self.websocket.contains_key(&key) {
+            self.websocket.insert(key, true);
+        }
+        None
+    }
+}
+
+pub const DUMPCAP: &str = "/run/wrappers/bin/dumpcap";
+
+// Owns the dumpcap children, one per interface; dropping it kills and reaps them
+pub struct Sniffer {
+    program: PathBuf, // dumpcap, or a stand-in under test; restarts run the same one
+    children: Vec<Child>,
+    records: Arc<AtomicU64>, // Records read from every dumpcap so far, whether or not the UI has caught up
+    stopped: usize,          // Readers that have hit EOF
+}
+
+impl Sniffer {
+    pub fn records(&self) -> u64 {
+        self.records.load(Ordering::Relaxed)
+    }
+
+    // Counts one reader's `Stopped`; true once every interface's has, so the whole capture is over
+    pub fn reader_stopped(&mut self) -> bool {
+        self.stopped += 1;
+        self.stopped >= self.children.len()
+    }
+
+    // Exit code once a child has actually exited; None while running or if killed by a signal
+    pub fn exit_code(&mut self) -> Option<i32> {
+        self.children.iter_mut().find_map(|child| child.try_wait().ok().flatten()).and_then(|status| status.code())
+    }
+}
+
+impl Drop for Sniffer {
+    fn drop(&mut self) {
+        for child in &mut self.children {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+// Interfaces to capture on; "any" unless some were named
+pub fn capture_interfaces(config: &Config) -> Vec<String> {
+    if config.interfaces.is_empty() { vec!["any".to_string()] } else { config.interfaces.clone() }
+}
+
+// `dumpcap -D` prints one interface per line, numbered, with an optional description:
+//
+//     1. eth0
+//     2. any (Pseudo-device that captures on all interfaces)
+pub fn parse_interface_list(output: &str) -> Vec<String> {
+    output.lines()
+        .filter_map(|line| line.trim().split_once(". "))
+        .filter(|(number, _)| number.parse::<u32>().is_ok())
+        .filter_map(|(_, rest)| rest.split_whitespace().next().map(str::to_string))
+        .collect()
+}
+
+pub fn list_interfaces() -> io::Result<Vec<String>> {
+    let output = Command::new(DUMPCAP).arg("-D").stderr(Stdio::null()).output()?;
+    Ok(parse_interface_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+pub fn dumpcap_args(config: &Config, interface: &str) -> Vec<String> {
+    // REMOVED "-f ip" to ensure data flows; we'll filter in Rust instead
+    let mut args: Vec<String> = ["-i", interface, "-F", "pcap", "-n", "-q"].iter().map(|a| a.to_string()).collect();
+    // Monitor mode needs a real wireless interface; "any" can't be put into it
+    if config.monitor {
+        args.push("-I".to_string());
+    }
+    if let Some(snaplen) = config.snaplen {
+        args.extend(["-s".to_string(), snaplen.to_string()]);
+    }
+    if let Some(filter) = &config.capture_filter {
+        args.extend(["-f".to_string(), filter.clone()]);
+    }
+    args.extend(["-w".to_string(), "-".to_string()]);
+    args
+}
+
+// dumpcap can only write pcapng for several interfaces, so each one gets its own process and reader.
+// If any of them fails to start, the ones already running are killed along with the partial Sniffer.
+pub fn run_sniffer(tx: Sender<CaptureEvent>, config: &Config) -> io::Result<Sniffer> {
+    spawn_sniffer(Path::new(DUMPCAP), tx, config)
+}
+
+fn spawn_sniffer(program: &Path, tx: Sender<CaptureEvent>, config: &Config) -> io::Result<Sniffer> {
+    let mut sniffer = Sniffer { program: program.to_path_buf(), children: Vec::new(), records: Arc::new(AtomicU64::new(0)), stopped: 0 };
+    for interface in capture_interfaces(config) {
+        let mut child = Command::new(program)
+            .args(dumpcap_args(config, &interface))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("cannot start {} on {}: {}", program.display(), interface, e)))?;
+
+        let Some(stdout) = child.stdout.take() else {
+            sniffer.children.push(child);
+            return Err(io::Error::other("dumpcap has no stdout"));
+        };
+        debug!("dumpcap started on {} (pid {})", interface, child.id());
+
+        let tx = tx.clone();
+        let tag = (!config.interfaces.is_empty()).then(|| Arc::from(interface.as_str()));
+        let link = config.link_options();
+        let records = sniffer.records.clone();
+        tokio::task::spawn_blocking(move || capture_until_eof(stdout, &tx, tag, link, &records));
+        sniffer.children.push(child);
+    }
+    Ok(sniffer)
+}
+
+// Swaps in a capture with new settings; the old dumpcaps are killed and reaped before the new ones start
+pub fn restart_sniffer(old: Sniffer, tx: Sender<CaptureEvent>, config: &Config) -> io::Result<Sniffer> {
+    debug!("restarting capture on {}", config.capture_spec());
+    let program = old.program.clone();
+    drop(old);
+    spawn_sniffer(&program, tx, config)
+}
+
+// A live capture never ends on its own, so running out of input is reported as a stop
+pub fn capture_until_eof<R: Read>(source: R, tx: &Sender<CaptureEvent>, interface: Option<Arc<str>>, link: LinkOptions, records: &AtomicU64) {
+    read_pcap_from(source, tx, &TimeRange::default(), interface.clone(), link, Some(records));
+    debug!("capture stopped");
+    let _ = tx.send(CaptureEvent::Stopped(interface));
+}
+
+pub type CaptureSource = Box<dyn Read + Send>;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// `--read -` pipes a pcap stream in on stdin; keyboard input still works since crossterm falls back to /dev/tty.
+// Also returns the file size, which stdin doesn't have. Gzipped input (`.pcap.gz`) is recognised by its
+// magic and decompressed on the fly.
+pub fn open_capture(path: &Path) -> io::Result<(CaptureSource, Option<u64>)> {
+    if path == Path::new("-") {
+        let mut stdin = io::BufReader::new(io::stdin());
+        if stdin.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            return Ok((Box::new(MultiGzDecoder::new(stdin)), None));
+        }
+        Ok((Box::new(stdin), None))
+    } else {
+        let mut file = File::open(path)?;
+        let mut magic = [0; 2];
+        let gzip = file.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+        file.rewind()?;
+        if !gzip {
+            let size = file.metadata()?.len();
+            return Ok((Box::new(file), Some(size)));
+        }
+        // Progress counts decompressed bytes, so the total is the trailer's ISIZE: the uncompressed length mod 4 GiB
+        let mut isize = [0; 4];
+        file.seek(SeekFrom::End(-4))?;
+        file.read_exact(&mut isize)?;
+        file.rewind()?;
+        Ok((Box::new(MultiGzDecoder::new(io::BufReader::new(file))), Some(u32::from_le_bytes(isize) as u64)))
+    }
+}
+
+// Bytes consumed across all --read sources, against their combined size when every one is known
+#[derive(Clone)]
+pub struct LoadProgress {
+    pub bytes_read: Arc<AtomicU64>,
+    pub total: Option<u64>,
+    pub replay: Option<Arc<AtomicUsize>>, // --replay: index into REPLAY_SPEEDS, adjusted from the UI
+}
+
+impl LoadProgress {
+    pub fn fraction(&self) -> Option<f64> {
+        self.total.map(|total| progress_fraction(self.bytes_read.load(Ordering::Relaxed), total))
+    }
+}
+
+// --replay speed multipliers, slowest first; the last is "as fast as possible"
+pub const REPLAY_SPEEDS: [f64; 5] = [0.5, 1.0, 2.0, 10.0, f64::INFINITY];
+pub const DEFAULT_REPLAY_SPEED: usize = 1;
+
+pub fn replay_speed_name(index: usize) -> String {
+    match REPLAY_SPEEDS.get(index) {
+        Some(speed) if speed.is_finite() => format!("{}x", speed),
+        _ => "max".to_string(),
+    }
+}
+
+// Time source for replay pacing and --duration, so either can be checked without sleeping
+pub trait Clock {
+    fn now(&self) -> Duration;
+    fn sleep(&mut self, d: Duration);
+}
+
+pub struct WallClock(Instant);
+
+impl WallClock {
+    pub fn start() -> WallClock {
+        WallClock(Instant::now())
+    }
+}
+
+impl Clock for WallClock {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+
+    fn sleep(&mut self, d: Duration) {
+        std::thread::sleep(d);
+    }
+}
+
+// Holds each packet back until its offset in the capture, scaled by the current speed, has passed.
+// A speed change re-anchors the schedule at the current replay position rather than rescaling the past.
+pub struct Pacer {
+    speed: Arc<AtomicUsize>,
+    anchor: Option<(Duration, Duration, usize)>, // Clock time, capture time, speed index
+}
+
+impl Pacer {
+    pub fn new(speed: Arc<AtomicUsize>) -> Pacer {
+        Pacer { speed, anchor: None }
+    }
+
+    pub fn wait(&mut self, ts: Duration, clock: &mut impl Clock) {
         loop {
-            match stdout.read(&mut temp_buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    buffer.extend_from_slice(&temp_buf[..n]);
-                    if buffer.len() > 8000 { buffer.drain(..4000); }
-
-                    let mut i = 0;
-                    while i < buffer.len().saturating_sub(20) {
-                        // Look for IPv4 Magic Byte
-                        if buffer[i] == 0x45 {
-                            if let Ok((h, _)) = Ipv4Header::from_slice(&buffer[i..]) {
-                                let src = Ipv4Addr::from(h.source);
-                                let dst = Ipv4Addr::from(h.destination);
-
-                                // --- NOISE FILTER: The "0.0.0.0" Fix ---
-                                // Skip if either IP is all zeros or if it's a broadcast
-                                if src.is_unspecified() || dst.is_unspecified() || src.is_broadcast() {
-                                    i += 1;
-                                    continue;
-                                }
+            let index = self.speed.load(Ordering::Relaxed).min(REPLAY_SPEEDS.len() - 1);
+            let (wall, start) = match self.anchor {
+                Some((wall, start, i)) if i == index => (wall, start),
+                Some((wall, start, i)) => {
+                    let now = clock.now();
+                    let position = if REPLAY_SPEEDS[i].is_finite() { start + now.saturating_sub(wall).mul_f64(REPLAY_SPEEDS[i]) } else { ts };
+                    self.anchor = Some((now, position.min(ts), index));
+                    (now, position.min(ts))
+                }
+                None => {
+                    self.anchor = Some((clock.now(), ts, index));
+                    return;
+                }
+            };
+            let speed = REPLAY_SPEEDS[index];
+            if speed.is_infinite() {
+                return;
+            }
+            let due = wall + ts.saturating_sub(start).div_f64(speed);
+            let now = clock.now();
+            if due <= now {
+                return;
+            }
+            // Short naps, so a speed change during a long gap takes effect promptly
+            clock.sleep((due - now).min(Duration::from_millis(100)));
+        }
+    }
+}
+
+// Forwards reader events to the UI on the capture's own schedule
+fn replay_paced(rx: Receiver<CaptureEvent>, tx: &Sender<CaptureEvent>, mut pacer: Pacer) {
+    let mut clock = WallClock::start();
+    for event in rx {
+        if let CaptureEvent::Packet(update) = &event {
+            pacer.wait(update.ts, &mut clock);
+        }
+        if tx.send(event).is_err() { break; }
+    }
+}
+
+pub fn progress_fraction(read: u64, total: u64) -> f64 {
+    if total == 0 { 1.0 } else { (read as f64 / total as f64).min(1.0) }
+}
+
+struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+// --read mode: loads captures through the same record parser instead of spawning dumpcap.
+// A single source is streamed; several are merged into one timeline. `Loaded` follows the last packet.
+pub fn run_reader(sources: Vec<(CaptureSource, Option<u64>)>, tx: Sender<CaptureEvent>, config: &Config) -> LoadProgress {
+    let range = config.time_range;
+    let link = config.link_options();
+    let progress = LoadProgress {
+        bytes_read: Arc::new(AtomicU64::new(0)),
+        total: sources.iter().map(|(_, size)| *size).sum(),
+        replay: config.replay.then(|| Arc::new(AtomicUsize::new(DEFAULT_REPLAY_SPEED))),
+    };
+    let mut sources: Vec<CountingReader<CaptureSource>> = sources.into_iter()
+        .map(|(inner, _)| CountingReader { inner, count: progress.bytes_read.clone() })
+        .collect();
+    // Replay reads ahead at full speed and a second thread releases the packets on schedule
+    let tx = match &progress.replay {
+        Some(speed) => {
+            let (paced_tx, paced_rx) = mpsc::channel();
+            let pacer = Pacer::new(speed.clone());
+            tokio::task::spawn_blocking(move || replay_paced(paced_rx, &tx, pacer));
+            paced_tx
+        }
+        None => tx,
+    };
+    tokio::task::spawn_blocking(move || {
+        if sources.len() == 1 {
+            read_pcap(sources.remove(0), &tx, &range, link);
+        } else {
+            merge_pcaps(sources, &tx, &range, link);
+        }
+        debug!("every capture loaded");
+        let _ = tx.send(CaptureEvent::Loaded);
+    });
+    progress
+}
+
+// Loads every capture (each stripped with its own link type), then replays the packets in timestamp
+// order. `range` is applied afterwards so relative bounds count from the earliest packet of any file.
+pub fn merge_pcaps<R: Read>(sources: Vec<R>, tx: &Sender<CaptureEvent>, range: &TimeRange, link: LinkOptions) {
+    let mut packets = Vec::new();
+    for source in sources {
+        let (file_tx, file_rx) = mpsc::channel();
+        read_pcap(source, &file_tx, &TimeRange::default(), link);
+        drop(file_tx);
+        for event in file_rx {
+            match event {
+                CaptureEvent::Packet(update) => packets.push(update),
+                other => { let _ = tx.send(other); }
+            }
+        }
+    }
+    packets.sort_by_key(|p| p.ts); // Stable, so same-timestamp packets keep their file order
+    // Renumber the merged timeline the way mergecap would
+    for (i, update) in packets.iter_mut().enumerate() {
+        update.frame = i as u64 + 1;
+    }
+    let Some(start) = packets.first().map(|p| p.ts) else { return };
+    for update in packets {
+        let frame = update.frame;
+        let event = if range.contains(update.ts, start) { CaptureEvent::Packet(update) } else { CaptureEvent::Dropped(DROP_RANGE) };
+        if tx.send(event).is_ok() { debug!("merged record {} sent", frame); }
+    }
+}
+
+// Streams pcap records from any reader into the channel until EOF or a parse error.
+// Records outside `range` are skipped before they are parsed.
+pub fn read_pcap<R: Read>(source: R, tx: &Sender<CaptureEvent>, range: &TimeRange, link: LinkOptions) {
+    read_pcap_from(source, tx, range, None, link, None);
+}
 
-                                let total_len = h.total_len as usize;
-                                if buffer.len() >= i + total_len {
-                                    let raw_packet = buffer[i..i + total_len].to_vec();
-                                    
-                                    // Port Detection logic...
-                                    let mut tag = String::new();
-                                    if raw_packet.len() >= 24 {
-                                        let d_port = u16::from_be_bytes([raw_packet[22], raw_packet[23]]);
-                                        tag = match d_port {
-                                            443 => " [HTTPS]".to_string(),
-                                            53  => " [DNS]".to_string(),
-                                            22  => " [SSH]".to_string(),
-                                            _   => "".to_string(),
-                                        };
-                                    }
-
-                                    let _ = tx.send(PacketUpdate {
-                                        summary: format!("{} ➔ {}{}", src, dst, tag),
-                                        raw_data: raw_packet,
-                                    });
-                                    i += total_len;
-                                    continue;
+// As `read_pcap`, tagging each packet with the interface it was captured on and counting records into
+// `records`. Live readers share that count, so packets from several interfaces are numbered as one capture.
+pub fn read_pcap_from<R: Read>(mut source: R, tx: &Sender<CaptureEvent>, range: &TimeRange, interface: Option<Arc<str>>, link: LinkOptions, records: Option<&AtomicU64>) {
+    // Pipes can hand us the global header in pieces, but the reader needs it whole on its first read
+    let mut header = [0u8; 24];
+    if let Err(e) = source.read_exact(&mut header) {
+        warn!("no pcap header: {}", e);
+        return;
+    }
+    // Large enough for a full record at dumpcap's maximum snaplen (262144), so jumbo frames and
+    // unsplit TSO segments are read whole
+    let Ok(mut reader) = LegacyPcapReader::new(1 << 19, io::Cursor::new(header).chain(source)) else {
+        warn!("not a pcap stream (magic {:02x?})", &header[..4]);
+        return;
+    };
+    let mut linktype = Linktype::ETHERNET;
+    let mut nanos = false;
+    let mut capture_start = None;
+    let mut frame = 0;
+    let mut tracker = FlowTracker::default();
+    loop {
+        match reader.next() {
+            Ok((offset, block)) => {
+                match block {
+                    PcapBlockOwned::LegacyHeader(header) => {
+                        linktype = header.network;
+                        nanos = header.is_nanosecond_precision();
+                        debug!("pcap header: linktype {:?}, snaplen {}, nanosecond {}", linktype, header.snaplen, nanos);
+                    }
+                    PcapBlockOwned::Legacy(record) => {
+                        // Every record counts, including ones dropped below, so numbers match Wireshark's
+                        frame = match records {
+                            Some(records) => records.fetch_add(1, Ordering::Relaxed) + 1,
+                            None => frame + 1,
+                        };
+                        let frac = if nanos { record.ts_usec } else { record.ts_usec.saturating_mul(1000) };
+                        let ts = Duration::new(record.ts_sec as u64, frac);
+                        let start = *capture_start.get_or_insert(ts);
+                        debug!("record {} at {:?}: caplen {}, origlen {}", frame, ts, record.caplen, record.origlen);
+                        let event = if !range.contains(ts, start) {
+                            debug!("record skipped: outside time range");
+                            CaptureEvent::Dropped(DROP_RANGE)
+                        } else {
+                            match parse_record(linktype, link, record.data, record.caplen, record.origlen, ts, &mut tracker) {
+                                Ok(CaptureEvent::Packet(mut update)) => {
+                                    update.frame = frame;
+                                    update.interface = interface.clone();
+                                    CaptureEvent::Packet(update)
                                 }
+                                Ok(event) => event,
+                                Err(reason) => CaptureEvent::Dropped(reason),
                             }
+                        };
+                        match tx.send(event) {
+                            Ok(()) => debug!("record {} sent", frame),
+                            Err(_) => debug!("send failed: receiver gone"),
                         }
-                        i += 1;
                     }
+                    PcapBlockOwned::NG(_) => {}
+                }
+                reader.consume(offset);
+            }
+            Err(PcapError::Incomplete(_)) => {
+                if let Err(e) = reader.refill() {
+                    debug!("end of capture: {}", e);
+                    break;
                 }
-                Err(_) => break,
             }
+            Err(PcapError::Eof) => { debug!("end of capture"); break; }
+            Err(e) => { warn!("pcap parse error: {:?}", e); break; }
         }
-    });
-    child
+    }
+}
+
+// User overrides for how frames are stripped down to their IP packet
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinkOptions {
+    pub offset: Option<usize>, // --link-offset: fixed header length, replacing link-type detection
+    pub fcs: bool,             // --has-fcs: every frame ends in a 4-byte frame check sequence
+}
+
+// Not in pcap-parser's table of named link types
+const IEEE802_11: Linktype = Linktype(105);
+const IEEE802_11_RADIOTAP: Linktype = Linktype(127);
+
+// Bytes of link-layer header in front of the IP packet, or None for unsupported link types
+fn link_header_len(linktype: Linktype, frame: &[u8]) -> Option<usize> {
+    match linktype {
+        Linktype::ETHERNET => match frame.get(12..14) {
+            Some([0x81, 0x00]) => Some(18), // 802.1Q VLAN tag
+            _ => Some(14),
+        },
+        Linktype::LINUX_SLL => Some(16),
+        Linktype::LINUX_SLL2 => Some(20),
+        Linktype::NULL | Linktype::LOOP => Some(4),
+        Linktype::RAW | Linktype::IPV4 => Some(0),
+        IEEE802_11 => decode::wifi_payload_offset(frame),
+        IEEE802_11_RADIOTAP => {
+            let radiotap = decode::radiotap_len(frame)?;
+            Some(radiotap + decode::wifi_payload_offset(&frame[radiotap..])?)
+        }
+        _ => None,
+    }
+}
+
+// EtherType of the frame's payload, where the link layer records one
+fn link_ethertype(linktype: Linktype, frame: &[u8], header_len: usize) -> Option<u16> {
+    let field = match linktype {
+        // For 802.11 the EtherType closes the LLC/SNAP header
+        Linktype::ETHERNET | Linktype::LINUX_SLL | IEEE802_11 | IEEE802_11_RADIOTAP => frame.get(header_len - 2..header_len)?,
+        Linktype::LINUX_SLL2 => frame.get(0..2)?,
+        _ => return None,
+    };
+    Some(u16::from_be_bytes([field[0], field[1]]))
+}
+
+// Err carries the drop reason counted in the status bar
+fn parse_record(linktype: Linktype, link: LinkOptions, frame: &[u8], cap_len: u32, orig_len: u32, ts: Duration, tracker: &mut FlowTracker) -> Result<CaptureEvent, &'static str> {
+    // Only a frame captured in full still ends in its FCS; a snaplen cut has already lost it
+    let frame = if link.fcs && cap_len >= orig_len { &frame[..frame.len().saturating_sub(4)] } else { frame };
+    // A forced offset means the link type can't be trusted, so nothing else is read from the link header
+    if let Some(offset) = link.offset {
+        return parse_frame(Linktype::RAW, frame, offset, cap_len, orig_len, ts, tracker).map(CaptureEvent::Packet);
+    }
+    let Some(offset) = link_header_len(linktype, frame) else {
+        debug!("record dropped: unsupported linktype {:?}", linktype);
+        return Err(DROP_LINK);
+    };
+    if link_ethertype(linktype, frame, offset) == Some(0x0806) {
+        let binding = frame.get(offset..).and_then(parse_arp);
+        debug!("ARP record: {}", binding.map_or("no sender binding".to_string(), |(ip, mac)| format!("{} is at {}", ip, decode::format_mac(&mac))));
+        let (ip, mac) = binding.ok_or(DROP_NOISE)?;
+        return Ok(CaptureEvent::Binding(ip, mac));
+    }
+    parse_frame(linktype, frame, offset, cap_len, orig_len, ts, tracker).map(CaptureEvent::Packet)
+}
+
+// Why the noise filter drops a packet between these addresses, if it does: an all-zeros source or
+// destination, or a broadcast source. The first that applies wins, so each packet counts once.
+pub fn noise_reason(src: Ipv4Addr, dst: Ipv4Addr) -> Option<&'static str> {
+    if src.is_unspecified() {
+        Some(DROP_ZERO_SRC)
+    } else if dst.is_unspecified() {
+        Some(DROP_ZERO_DST)
+    } else if src.is_broadcast() {
+        Some(DROP_BCAST_SRC)
+    } else {
+        None
+    }
+}
+
+// Sender binding of an Ethernet/IPv4 ARP request or reply; probes from 0.0.0.0 carry none
+fn parse_arp(arp: &[u8]) -> Option<(Ipv4Addr, [u8; 6])> {
+    if arp.len() < 28 || arp[0..2] != [0, 1] || arp[2..4] != [0x08, 0x00] || arp[4] != 6 || arp[5] != 4 {
+        return None;
+    }
+    let mac: [u8; 6] = arp[8..14].try_into().ok()?;
+    let ip = Ipv4Addr::new(arp[14], arp[15], arp[16], arp[17]);
+    (!ip.is_unspecified()).then_some((ip, mac))
+}
+
+fn parse_frame(linktype: Linktype, frame: &[u8], offset: usize, cap_len: u32, orig_len: u32, ts: Duration, tracker: &mut FlowTracker) -> Result<PacketUpdate, &'static str> {
+    let ip = frame.get(offset..).ok_or(DROP_LINK)?;
+    let link = match linktype {
+        Linktype::ETHERNET => LinkInfo::from_ethernet(frame),
+        Linktype::LINUX_SLL => LinkInfo::from_sll(frame, false),
+        Linktype::LINUX_SLL2 => LinkInfo::from_sll(frame, true),
+        IEEE802_11 => LinkInfo::from_wifi(frame),
+        IEEE802_11_RADIOTAP => decode::radiotap_len(frame).map_or(LinkInfo::Other, |len| LinkInfo::from_wifi(&frame[len..])),
+        _ => LinkInfo::Other,
+    };
+    let h = match Ipv4Header::from_slice(ip) {
+        Ok((h, _)) => h,
+        Err(e) => {
+            let ethertype = link_ethertype(linktype, frame, offset).map_or("none".to_string(), |t| format!("0x{:04x}", t));
+            debug!("record dropped: not IPv4 (ethertype {}): {}", ethertype, e);
+            return Err(DROP_NOT_IP);
+        }
+    };
+    let src = Ipv4Addr::from(h.source);
+    let dst = Ipv4Addr::from(h.destination);
+
+    // --- NOISE FILTER: The "0.0.0.0" Fix ---
+    // Each reason is counted separately, so the accounting shows what the filter is hiding
+    if let Some(reason) = noise_reason(src, dst) {
+        debug!("record dropped: noise filter, {} ({} ➔ {})", reason, src, dst);
+        return Err(reason);
+    }
+
+    // Drop link-layer padding past the IP length; a snaplen cut may leave fewer bytes than that.
+    // Segments captured before TSO/GSO splits them can exceed what the field holds and carry 0 instead.
+    let ip_len = if h.total_len == 0 { ip.len() } else { ip.len().min(h.total_len as usize) };
+    let raw_packet = ip[..ip_len].to_vec();
+
+    let tag = tracker.label(&raw_packet).or_else(|| decode::app_label(&raw_packet));
+
+    let summary = match &tag {
+        Some(t) => format!("{} ➔ {} [{}]", src, dst, t),
+        None => format!("{} ➔ {}", src, dst),
+    };
+    debug!("parsed: {}", summary);
+    Ok(PacketUpdate {
+        summary,
+        tag,
+        labels: Vec::new(),
+        raw_data: raw_packet,
+        cap_len,
+        orig_len,
+        ts,
+        link,
+        frame: 0, // Numbered and tagged by the reader
+        interface: None,
+        dup_ack: None,
+    })
+}
+
+// Writes packets as a classic pcap. Only the IPv4 packet is retained, so the link type is RAW;
+// a snaplen cut still shows up as a record shorter than its original length.
+pub fn write_pcap<'a, W: Write>(out: &mut W, packets: impl IntoIterator<Item = &'a PacketUpdate>) -> io::Result<usize> {
+    out.write_all(&0xa1b2_c3d4u32.to_le_bytes())?;
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&4u16.to_le_bytes())?;
+    out.write_all(&[0; 8])?; // thiszone, sigfigs
+    out.write_all(&262_144u32.to_le_bytes())?;
+    out.write_all(&(Linktype::RAW.0 as u32).to_le_bytes())?;
+    let mut written = 0;
+    for pkt in packets {
+        let orig = pkt.raw_data.len() as u32 + pkt.orig_len.saturating_sub(pkt.cap_len);
+        out.write_all(&(pkt.ts.as_secs() as u32).to_le_bytes())?;
+        out.write_all(&pkt.ts.subsec_micros().to_le_bytes())?;
+        out.write_all(&(pkt.raw_data.len() as u32).to_le_bytes())?;
+        out.write_all(&orig.to_le_bytes())?;
+        out.write_all(&pkt.raw_data)?;
+        written += 1;
+    }
+    out.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TimeBound;
+    use crate::testutil::{self, Tcp};
+
+    // Every packet a reader sends, in order, ignoring drops and bindings
+    fn packets(rx: &mpsc::Receiver<CaptureEvent>) -> Vec<PacketUpdate> {
+        rx.try_iter().filter_map(|event| match event { CaptureEvent::Packet(p) => Some(p), _ => None }).collect()
+    }
+
+    #[test]
+    fn snaplen_reaches_dumpcap_and_marks_truncation() {
+        let mut config = Config::default();
+        config.apply_args(["--snaplen".to_string(), "96".to_string()]).unwrap();
+        let args = dumpcap_args(&config, "eth0");
+        assert!(args.windows(2).any(|w| w == ["-s", "96"]));
+
+        let mut cut = testutil::update(Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80).packet(&[0; 200]), 1);
+        cut.orig_len = cut.cap_len + 100;
+        let mut pcap = Vec::new();
+        write_pcap(&mut pcap, [&cut]).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        read_pcap(pcap.as_slice(), &tx, &TimeRange::default(), LinkOptions::default());
+        let read = packets(&rx);
+        assert_eq!(read.len(), 1);
+        assert!(read[0].is_truncated());
+        assert_eq!(read[0].orig_len - read[0].cap_len, 100);
+    }
+
+    // A raw-IP capture with one record from 10.0.0.`host` to 10.0.0.2 at each of `secs`
+    fn fixture(host: u8, secs: &[u64]) -> Vec<u8> {
+        let flow = Tcp::new([10, 0, 0, host], 40000, [10, 0, 0, 2], 80);
+        let records: Vec<(Duration, Vec<u8>)> = secs.iter().map(|&s| (Duration::from_secs(s), flow.packet(&[s as u8]))).collect();
+        let records: Vec<(Duration, &[u8])> = records.iter().map(|(ts, data)| (*ts, data.as_slice())).collect();
+        testutil::pcap(Linktype::RAW.0 as u32, &records)
+    }
+
+    // Five records a second apart, from 100s
+    fn timed_fixture() -> Vec<u8> {
+        fixture(1, &[100, 101, 102, 103, 104])
+    }
+
+    #[test]
+    fn interfaces_merge_into_one_tagged_stream() {
+        let (tx, rx) = mpsc::channel();
+        let records = AtomicU64::new(0);
+        std::thread::scope(|scope| {
+            for (host, name, secs) in [(1, "eth0", &[1, 3, 5][..]), (3, "wlan0", &[2, 4][..])] {
+                let (tx, records) = (tx.clone(), &records);
+                scope.spawn(move || capture_until_eof(io::Cursor::new(fixture(host, secs)), &tx, Some(Arc::from(name)), LinkOptions::default(), records));
+            }
+        });
+        drop(tx);
+
+        let events: Vec<CaptureEvent> = rx.iter().collect();
+        let mut stopped: Vec<String> = events.iter().filter_map(|e| match e { CaptureEvent::Stopped(i) => i.as_deref().map(str::to_string), _ => None }).collect();
+        stopped.sort();
+        assert_eq!(stopped, ["eth0", "wlan0"]);
+        assert_eq!(records.load(Ordering::Relaxed), 5);
+        // One numbering across interfaces: no two packets share a "No."
+        let mut frames: Vec<u64> = events.iter().filter_map(|e| match e { CaptureEvent::Packet(p) => Some(p.frame), _ => None }).collect();
+        frames.sort();
+        assert_eq!(frames, [1, 2, 3, 4, 5]);
+        let mut seen: Vec<(u64, u8, String)> = events.into_iter()
+            .filter_map(|e| match e { CaptureEvent::Packet(p) => Some((p.ts.as_secs(), p.raw_data[15], p.interface?.to_string())), _ => None })
+            .collect();
+        seen.sort();
+        assert_eq!(seen, [(1, 1, "eth0"), (2, 3, "wlan0"), (3, 1, "eth0"), (4, 3, "wlan0"), (5, 1, "eth0")].map(|(t, h, i)| (t, h, i.to_string())));
+    }
+
+    #[test]
+    fn progress_follows_bytes_read() {
+        let progress = LoadProgress { bytes_read: Arc::new(AtomicU64::new(0)), total: Some(1000), replay: None };
+        let mut reader = CountingReader { inner: io::Cursor::new(vec![0u8; 1000]), count: progress.bytes_read.clone() };
+        assert_eq!(progress.fraction(), Some(0.0));
+        reader.read_exact(&mut [0; 250]).unwrap();
+        assert_eq!(progress.fraction(), Some(0.25));
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        assert_eq!(progress.fraction(), Some(1.0));
+
+        assert_eq!(progress_fraction(1200, 1000), 1.0); // A gzip ISIZE can undercount
+        assert_eq!(progress_fraction(0, 0), 1.0);
+        assert_eq!(LoadProgress { total: None, ..progress }.fraction(), None);
+    }
+
+    #[test]
+    fn link_offset_overrides_the_link_header() {
+        // A 6-byte vendor header in front of IPv4, in a file that claims to be Ethernet
+        let mut frame = vec![0xde, 0xad, 0xbe, 0xef, 0x00, 0x01];
+        frame.extend(Tcp::new([10, 0, 0, 7], 40000, [10, 0, 0, 2], 80).packet(b"x"));
+        let pcap = testutil::pcap(Linktype::ETHERNET.0 as u32, &[(Duration::from_secs(1), &frame)]);
+
+        let (tx, rx) = mpsc::channel();
+        read_pcap(pcap.as_slice(), &tx, &TimeRange::default(), Config::default().link_options());
+        assert!(packets(&rx).is_empty());
+
+        let mut config = Config::default();
+        config.apply_args(["--link-offset".to_string(), "6".to_string()]).unwrap();
+        read_pcap(pcap.as_slice(), &tx, &TimeRange::default(), config.link_options());
+        let parsed = packets(&rx);
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].raw_data, frame[6..]);
+        assert!(parsed[0].summary.starts_with("10.0.0.7 ➔ 10.0.0.2"));
+    }
+
+    #[test]
+    fn replay_pacing_scales_with_speed() {
+        let replay = |index: usize| {
+            let (mut pacer, mut clock) = (Pacer::new(Arc::new(AtomicUsize::new(index))), testutil::FakeClock::default());
+            for secs in [10, 11, 13] {
+                pacer.wait(Duration::from_secs(secs), &mut clock);
+            }
+            clock.0
+        };
+        assert_eq!(REPLAY_SPEEDS[1..], [1.0, 2.0, 10.0, f64::INFINITY]);
+        assert_eq!(replay(1), Duration::from_secs(3));
+        assert_eq!(replay(2), Duration::from_millis(1500));
+        assert_eq!(replay(3), Duration::from_millis(300));
+        assert_eq!(replay(4), Duration::ZERO);
+
+        // Speeding up mid-replay only shortens what's still to come
+        let speed = Arc::new(AtomicUsize::new(1));
+        let (mut pacer, mut clock) = (Pacer::new(speed.clone()), testutil::FakeClock::default());
+        pacer.wait(Duration::from_secs(10), &mut clock);
+        pacer.wait(Duration::from_secs(11), &mut clock);
+        speed.store(3, Ordering::Relaxed);
+        pacer.wait(Duration::from_secs(21), &mut clock);
+        assert_eq!(clock.0, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn has_fcs_strips_the_trailing_checksum() {
+        // A segment captured before TSO split it: total length 0, so everything past the header counts
+        let mut ip = Tcp::new([10, 0, 0, 7], 40000, [10, 0, 0, 2], 80).packet(b"payload");
+        ip[2..4].copy_from_slice(&[0, 0]);
+        let mut frame = testutil::ethernet([2, 0, 0, 0, 0, 1], [2, 0, 0, 0, 0, 2], &ip);
+        frame.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        let pcap = testutil::pcap(Linktype::ETHERNET.0 as u32, &[(Duration::from_secs(1), &frame)]);
+
+        let (tx, rx) = mpsc::channel();
+        read_pcap(pcap.as_slice(), &tx, &TimeRange::default(), LinkOptions::default());
+        assert!(packets(&rx)[0].raw_data.ends_with(&[0xde, 0xad, 0xbe, 0xef]));
+
+        let mut config = Config::default();
+        config.apply_args(["--has-fcs".to_string()]).unwrap();
+        read_pcap(pcap.as_slice(), &tx, &TimeRange::default(), config.link_options());
+        assert_eq!(packets(&rx)[0].raw_data, ip);
+    }
+
+    #[test]
+    fn each_noise_reason_counts_separately() {
+        let from = |src: [u8; 4], dst: [u8; 4]| testutil::udp(src, 68, dst, 67, b"");
+        let frames = [
+            from([0, 0, 0, 0], [255, 255, 255, 255]),
+            from([0, 0, 0, 0], [0, 0, 0, 0]), // Both zero: the source is checked first
+            from([10, 0, 0, 1], [0, 0, 0, 0]),
+            from([255, 255, 255, 255], [10, 0, 0, 1]),
+            from([10, 0, 0, 1], [255, 255, 255, 255]), // A broadcast destination is ordinary traffic
+        ];
+        let records: Vec<(Duration, &[u8])> = frames.iter().map(|f| (Duration::from_secs(1), f.as_slice())).collect();
+        let (tx, rx) = mpsc::channel();
+        read_pcap(testutil::pcap(Linktype::RAW.0 as u32, &records).as_slice(), &tx, &TimeRange::default(), LinkOptions::default());
+
+        let mut accounting = crate::model::Accounting::default();
+        for event in rx.try_iter() {
+            match event {
+                CaptureEvent::Dropped(reason) => accounting.record_drop(reason, 1),
+                CaptureEvent::Packet(_) => accounting.processed += 1,
+                _ => {}
+            }
+        }
+        assert_eq!(accounting.processed, 1);
+        assert_eq!(accounting.dropped.iter().map(|(r, n)| (*r, *n)).collect::<Vec<_>>(), [(DROP_BCAST_SRC, 1), (DROP_ZERO_DST, 1), (DROP_ZERO_SRC, 2)]);
+        assert_eq!(accounting.seen(), 5);
+    }
+
+    #[test]
+    fn gzipped_capture_reads_like_the_plain_one() {
+        use flate2::{write::GzEncoder, Compression};
+        let plain = timed_fixture();
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&plain).unwrap();
+        let dir = std::env::temp_dir();
+        let (plain_path, gzip_path) = (dir.join(format!("vshark-plain-{}.pcap", std::process::id())), dir.join(format!("vshark-gzip-{}.pcap.gz", std::process::id())));
+        std::fs::write(&plain_path, &plain).unwrap();
+        std::fs::write(&gzip_path, gzip.finish().unwrap()).unwrap();
+
+        let read = |path: &Path| {
+            let (source, size) = open_capture(path).unwrap();
+            let (tx, rx) = mpsc::channel();
+            read_pcap(source, &tx, &TimeRange::default(), LinkOptions::default());
+            (size, packets(&rx).into_iter().map(|p| (p.ts, p.raw_data)).collect::<Vec<_>>())
+        };
+        let (plain_size, plain_packets) = read(&plain_path);
+        let (gzip_size, gzip_packets) = read(&gzip_path);
+        std::fs::remove_file(plain_path).unwrap();
+        std::fs::remove_file(gzip_path).unwrap();
+
+        assert_eq!(plain_packets.len(), 5);
+        assert_eq!(gzip_packets, plain_packets);
+        // Progress for the gzip counts decompressed bytes, so both report the plain size
+        assert_eq!((plain_size, gzip_size), (Some(plain.len() as u64), Some(plain.len() as u64)));
+    }
+
+    #[test]
+    fn jumbo_frame_is_read_whole() {
+        let payload: Vec<u8> = (0..8946).map(|i| i as u8).collect();
+        let ip = testutil::Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 5001).packet(&payload);
+        let jumbo = testutil::ethernet([2, 0, 0, 0, 0, 1], [2, 0, 0, 0, 0, 2], &ip);
+        assert_eq!(jumbo.len(), 9000);
+        let small = testutil::ethernet([2, 0, 0, 0, 0, 1], [2, 0, 0, 0, 0, 2], &testutil::udp([10, 0, 0, 1], 5353, [224, 0, 0, 251], 5353, b"after"));
+        let pcap = testutil::pcap(1, &[(Duration::from_secs(1), &jumbo), (Duration::from_secs(2), &small)]);
+
+        let (tx, rx) = mpsc::channel();
+        read_pcap(pcap.as_slice(), &tx, &TimeRange::default(), LinkOptions::default());
+        let packets = packets(&rx);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].raw_data, ip);
+        assert_eq!((packets[0].cap_len, packets[0].orig_len), (9000, 9000));
+        assert_eq!(decode::transport_payload(&packets[0].raw_data), payload.as_slice());
+        // The reader keeps its place in the stream after the oversized record
+        assert_eq!(decode::transport_payload(&packets[1].raw_data), b"after");
+    }
+
+    #[test]
+    fn structured_fields_agree_with_the_summary() {
+        let (client, server) = ([10, 0, 0, 1], [10, 0, 0, 2]);
+        let samples = [
+            Tcp::new(client, 40000, server, 80).packet(b"GET /index.html HTTP/1.1\r\nHost: a\r\n\r\n"),
+            Tcp::new(client, 40001, server, 443).packet(b""),
+            Tcp::new(client, 40002, server, 22).packet(b""),
+            testutil::udp(client, 53000, server, 53, b"\x00\x07\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x01a\x00\x00\x01\x00\x01"),
+            testutil::udp(client, 50000, server, 50001, b"opaque"),
+            testutil::ipv4(1, client, server, &[8, 0, 0, 0, 0, 1, 0, 1]),
+        ];
+        let records: Vec<(Duration, &[u8])> = samples.iter().map(|p| (Duration::from_secs(1), p.as_slice())).collect();
+        let (tx, rx) = mpsc::channel();
+        read_pcap(testutil::pcap(Linktype::RAW.0 as u32, &records).as_slice(), &tx, &TimeRange::default(), LinkOptions::default());
+        let mut parsed = packets(&rx);
+        assert_eq!(parsed.len(), samples.len());
+        parsed[1].set_labels(vec!["DB".to_string()]);
+        // A tag only flow state can give, as the WebSocket tracker would
+        parsed[4].retag("WS text".to_string());
+
+        let agree: Vec<bool> = parsed.iter().map(|pkt| PacketFields::of(pkt).unwrap().summary() == pkt.summary).collect();
+        assert_eq!(agree, [true, true, true, true, false, true]);
+        assert_eq!(parsed[4].summary, "10.0.0.1 ➔ 10.0.0.2 [WS text]");
+        let rendered: Vec<String> = parsed.iter().map(|p| PacketFields::of(p).unwrap().render()).collect();
+        assert_eq!(rendered, [
+            "TCP 10.0.0.1:40000 → 10.0.0.2:80 · HTTP GET /index.html",
+            "TCP 10.0.0.1:40001 → 10.0.0.2:443 · DB HTTPS",
+            "TCP 10.0.0.1:40002 → 10.0.0.2:22 · SSH",
+            "UDP 10.0.0.1:53000 → 10.0.0.2:53 · DNS query a",
+            "UDP 10.0.0.1:50000 → 10.0.0.2:50001",
+            "ICMP 10.0.0.1 → 10.0.0.2",
+        ]);
+    }
+
+    #[test]
+    fn time_range_keeps_only_in_range_records() {
+        let range = TimeRange { start: Some(TimeBound::Relative(Duration::from_secs(1))), end: Some(TimeBound::Absolute(Duration::from_secs(103))) };
+        let (tx, rx) = mpsc::channel();
+        read_pcap_from(timed_fixture().as_slice(), &tx, &range, None, LinkOptions::default(), None);
+        let events: Vec<CaptureEvent> = rx.try_iter().collect();
+        let kept: Vec<u64> = events.iter().filter_map(|e| match e { CaptureEvent::Packet(p) => Some(p.ts.as_secs()), _ => None }).collect();
+        assert_eq!(kept, vec![101, 102, 103]);
+        assert_eq!(events.iter().filter(|e| matches!(e, CaptureEvent::Dropped(DROP_RANGE))).count(), 2);
+    }
+
+    #[test]
+    fn flow_tracker_follows_websocket_upgrade() {
+        let client = Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80);
+        let server = Tcp::new([10, 0, 0, 2], 80, [10, 0, 0, 1], 40000);
+        let mut tracker = FlowTracker::default();
+
+        let request = client.packet(b"GET /chat HTTP/1.1\r\nHost: example.com\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n");
+        // The handshake itself is still HTTP, which the decoder registry names
+        assert_eq!(tracker.label(&request), None);
+        assert_eq!(decode::app_label(&request).as_deref(), Some("HTTP GET /chat"));
+        let accept = server.packet(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n");
+        assert_eq!(tracker.label(&accept), None);
+
+        assert_eq!(tracker.label(&client.packet(&[0x81, 0x02, b'h', b'i'])).as_deref(), Some("WS text"));
+        assert_eq!(tracker.label(&server.packet(&[0x89, 0x00])).as_deref(), Some("WS ping"));
+    }
+
+    #[test]
+    fn accept_without_request_is_not_websocket() {
+        let server = Tcp::new([10, 0, 0, 2], 80, [10, 0, 0, 1], 40000);
+        let mut tracker = FlowTracker::default();
+        tracker.label(&server.packet(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n"));
+        assert_ne!(tracker.label(&server.packet(&[0x81, 0x00])).as_deref(), Some("WS text"));
+    }
+
+    // Hands out at most a few bytes per read, as a pipe on stdin can
+    struct Trickle<R>(R);
+
+    impl<R: Read> Read for Trickle<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(7);
+            self.0.read(&mut buf[..n])
+        }
+    }
+
+    #[test]
+    fn reads_a_piped_stream() {
+        let (tx, rx) = mpsc::channel();
+        read_pcap_from(Trickle(io::Cursor::new(timed_fixture())), &tx, &TimeRange::default(), None, LinkOptions::default(), None);
+        let read = packets(&rx);
+        assert_eq!(read.len(), 5);
+        assert_eq!(read.iter().map(|p| p.frame).collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+        assert_eq!(read[0].summary, "10.0.0.1 ➔ 10.0.0.2");
+    }
+
+    #[test]
+    fn end_of_input_reports_stopped() {
+        let (tx, rx) = mpsc::channel();
+        let records = AtomicU64::new(0);
+        capture_until_eof(io::Cursor::new(timed_fixture()), &tx, None, LinkOptions::default(), &records);
+        let events: Vec<CaptureEvent> = rx.try_iter().collect();
+        assert_eq!(events.len(), 6);
+        assert!(matches!(events.last(), Some(CaptureEvent::Stopped(None))));
+        assert_eq!(records.load(Ordering::Relaxed), 5);
+
+        capture_until_eof(io::Cursor::new(Vec::new()), &tx, None, LinkOptions::default(), &records);
+        assert!(matches!(rx.try_iter().collect::<Vec<_>>().as_slice(), [CaptureEvent::Stopped(None)]));
+    }
+
+    #[test]
+    fn capture_stops_with_its_last_reader() {
+        let sleeper = || Command::new("sleep").arg("30").spawn().unwrap();
+        let mut sniffer = Sniffer { program: PathBuf::from("sleep"), children: vec![sleeper(), sleeper()], records: Arc::default(), stopped: 0 };
+        assert!(!sniffer.reader_stopped());
+        assert_eq!(sniffer.exit_code(), None);
+        assert!(sniffer.reader_stopped());
+    }
+
+    #[test]
+    fn merged_captures_are_chronological() {
+        let (a, b) = (fixture(1, &[10, 13, 14]), fixture(3, &[11, 12, 15]));
+        let (tx, rx) = mpsc::channel();
+        merge_pcaps(vec![a.as_slice(), b.as_slice()], &tx, &TimeRange::default(), LinkOptions::default());
+        let merged = packets(&rx);
+        assert_eq!(merged.iter().map(|p| p.ts.as_secs()).collect::<Vec<_>>(), [10, 11, 12, 13, 14, 15]);
+        assert_eq!(merged.iter().map(|p| p.raw_data[15]).collect::<Vec<_>>(), [1, 3, 3, 1, 1, 3]);
+        assert_eq!(merged.iter().map(|p| p.frame).collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn frame_numbers_count_every_record_and_survive_filtering() {
+        let web = Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80).packet(b"");
+        let dns = testutil::udp([10, 0, 0, 1], 5353, [10, 0, 0, 53], 53, b"");
+        let junk = [0x60; 40]; // Not IPv4, so dropped but still numbered
+        let records: Vec<(Duration, &[u8])> = [&web[..], &junk, &dns, &web, &dns].into_iter().enumerate().map(|(i, r)| (Duration::from_secs(i as u64), r)).collect();
+        let (tx, rx) = mpsc::channel();
+        read_pcap(testutil::pcap(Linktype::RAW.0 as u32, &records).as_slice(), &tx, &TimeRange::default(), LinkOptions::default());
+        let read = packets(&rx);
+        assert_eq!(read.iter().map(|p| p.frame).collect::<Vec<_>>(), [1, 3, 4, 5]);
+
+        let udp = crate::filter::Filter::parse("udp");
+        let shown: Vec<u64> = read.iter().filter(|p| udp.matches(p)).map(|p| p.frame).collect();
+        assert_eq!(shown, [3, 5]);
+        let all: Vec<u64> = read.iter().filter(|p| crate::filter::Filter::All.matches(p)).map(|p| p.frame).collect();
+        assert_eq!(all, [1, 3, 4, 5]);
+    }
+
+    #[test]
+    fn radiotap_wifi_frame_parses() {
+        let (ap, station, server) = ([0x00, 0x1b, 0x63, 0, 0, 1], [0x02, 0, 0, 0, 0, 2], [0x02, 0, 0, 0, 0, 3]);
+        let mut frame = vec![0, 0, 12, 0, 0, 0, 0, 0, 0xaa, 0xbb, 0xcc, 0xdd]; // Radiotap v0 with 4 bytes of fields
+        frame.extend_from_slice(&[0x88, 0x01, 0, 0]); // QoS data, To DS
+        frame.extend_from_slice(&ap);
+        frame.extend_from_slice(&station);
+        frame.extend_from_slice(&server);
+        frame.extend_from_slice(&[0, 0, 0, 0]); // Sequence control, QoS control
+        frame.extend_from_slice(&[0xaa, 0xaa, 0x03, 0, 0, 0, 0x08, 0x00]);
+        let ip = Tcp::new([192, 168, 1, 20], 40000, [10, 0, 0, 2], 80).packet(b"hi");
+        frame.extend_from_slice(&ip);
+
+        let (tx, rx) = mpsc::channel();
+        read_pcap(testutil::pcap(IEEE802_11_RADIOTAP.0 as u32, &[(Duration::from_secs(1), &frame)]).as_slice(), &tx, &TimeRange::default(), LinkOptions::default());
+        let read = packets(&rx);
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].raw_data, ip);
+        assert_eq!(read[0].link, LinkInfo::Wifi { frame_type: 2, subtype: 8, bssid: Some(ap), src: station, dst: server });
+    }
+
+    // A dumpcap stand-in that records its arguments next to itself, per pid, then idles until killed
+    fn fake_dumpcap(name: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("vshark-{}-{}", name, std::process::id()));
+        std::fs::write(&path, "#!/bin/sh\necho \"$@\" > \"$0.$$\"\nexec sleep 30\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    // The arguments the fake dumpcap with this pid was started with, once it has written them
+    fn recorded_args(program: &Path, pid: u32) -> String {
+        let file = PathBuf::from(format!("{}.{}", program.display(), pid));
+        for _ in 0..200 {
+            if let Ok(args) = std::fs::read_to_string(&file) {
+                if args.ends_with('\n') { return args.trim_end().to_string(); }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("{} never wrote its arguments", pid);
+    }
+
+    #[tokio::test]
+    async fn restart_replaces_the_child_with_new_args() {
+        let program = fake_dumpcap("restart");
+        let mut config = Config::default();
+        config.set_capture_spec("eth0");
+        let (tx, _rx) = mpsc::channel();
+        let old = spawn_sniffer(&program, tx.clone(), &config).unwrap();
+        let old_pid = old.children[0].id();
+        assert_eq!(recorded_args(&program, old_pid), dumpcap_args(&config, "eth0").join(" "));
+
+        config.set_capture_spec("eth1 port 53");
+        let new = restart_sniffer(old, tx, &config).unwrap();
+        assert!(!Path::new(&format!("/proc/{}", old_pid)).exists(), "old dumpcap still running");
+        let new_pid = new.children[0].id();
+        assert_ne!(new_pid, old_pid);
+        assert_eq!(recorded_args(&program, new_pid), dumpcap_args(&config, "eth1").join(" "));
+        assert!(dumpcap_args(&config, "eth1").windows(2).any(|w| w == ["-f", "port 53"]));
+
+        drop(new);
+        assert!(!Path::new(&format!("/proc/{}", new_pid)).exists());
+        for file in [program.clone(), PathBuf::from(format!("{}.{}", program.display(), old_pid)), PathBuf::from(format!("{}.{}", program.display(), new_pid))] {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_dumpcap_is_an_error() {
+        let (tx, _rx) = mpsc::channel();
+        let err = spawn_sniffer(Path::new("/nonexistent/dumpcap"), tx, &Config::default()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with("cannot start /nonexistent/dumpcap on any"));
+    }
 }
@@ -0,0 +1,164 @@
+// `--self-test`: checks the usual reasons for an empty feed before any TUI starts, e.g.
+//
+//     PASS  capture binary      /run/wrappers/bin/dumpcap
+//     FAIL  capture permission  permission denied on any: You don't have permission to capture on that device
+//     PASS  terminal            xterm-256color, 120x40
+//     PASS  config              1 interface(s), no capture filter
+use crate::config::Config;
+use crate::network;
+use std::io::{self, IsTerminal};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass(String),
+    Fail(String),
+}
+
+// The environment the checks probe; tests substitute their own
+pub trait Probe {
+    fn binary(&self, path: &Path) -> io::Result<bool>; // Whether it's executable; NotFound if it's missing
+    fn capture_one(&self, config: &Config, interface: &str) -> Result<(), String>; // dumpcap's complaint on failure
+    fn terminal(&self) -> Result<String, String>;
+}
+
+pub struct System;
+
+impl Probe for System {
+    fn binary(&self, path: &Path) -> io::Result<bool> {
+        Ok(std::fs::metadata(path)?.permissions().mode() & 0o111 != 0)
+    }
+
+    // One packet, or a few quiet seconds, is enough to prove the capture starts
+    fn capture_one(&self, config: &Config, interface: &str) -> Result<(), String> {
+        let mut args = network::dumpcap_args(config, interface);
+        args.extend(["-c", "1", "-a", "duration:3"].map(String::from));
+        let output = Command::new(network::DUMPCAP).args(args).stdout(Stdio::null()).stderr(Stdio::piped()).output().map_err(|e| e.to_string())?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(stderr.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("dumpcap failed").to_string())
+    }
+
+    fn terminal(&self) -> Result<String, String> {
+        if !io::stdout().is_terminal() {
+            return Err("stdout is not a terminal".to_string());
+        }
+        let term = std::env::var("TERM").unwrap_or_default();
+        if term.is_empty() || term == "dumb" {
+            return Err(format!("TERM is {:?}; colours and cursor movement may not work", term));
+        }
+        let (w, h) = crossterm::terminal::size().map_err(|e| e.to_string())?;
+        Ok(format!("{}, {}x{}", term, w, h))
+    }
+}
+
+// `load_error` is why `Config::load` refused the config, in which case `config` is the default
+pub fn run(probe: &impl Probe, config: &Config, load_error: Option<&str>) -> Vec<(&'static str, Outcome)> {
+    let path = Path::new(network::DUMPCAP);
+    let binary = match probe.binary(path) {
+        Ok(true) => Outcome::Pass(path.display().to_string()),
+        Ok(false) => Outcome::Fail(format!("{} is not executable", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Outcome::Fail(format!("{} not found; is Wireshark's dumpcap installed?", path.display())),
+        Err(e) => Outcome::Fail(format!("{}: {}", path.display(), e)),
+    };
+    // Without the binary a capture attempt would only repeat the same failure
+    let capture = if matches!(binary, Outcome::Fail(_)) {
+        Outcome::Fail("skipped: no usable capture binary".to_string())
+    } else {
+        let failures: Vec<String> = network::capture_interfaces(config).iter()
+            .filter_map(|interface| probe.capture_one(config, interface).err().map(|e| {
+                let kind = if e.to_lowercase().contains("permission") { "permission denied" } else { "capture failed" };
+                format!("{} on {}: {}", kind, interface, e)
+            }))
+            .collect();
+        if failures.is_empty() { Outcome::Pass("test capture succeeded".to_string()) } else { Outcome::Fail(failures.join("; ")) }
+    };
+    let terminal = match probe.terminal() {
+        Ok(info) => Outcome::Pass(info),
+        Err(e) => Outcome::Fail(e),
+    };
+    // A valid config shows what will actually be used
+    let config_check = if let Some(e) = load_error {
+        Outcome::Fail(e.to_string())
+    } else if !config.ignored_lines.is_empty() {
+        Outcome::Fail(format!("ignored {}", config.ignored_lines.join("; ")))
+    } else {
+        Outcome::Pass(format!("{} interface(s), {}", network::capture_interfaces(config).len(),
+            config.capture_filter.as_ref().map_or("no capture filter".to_string(), |f| format!("capture filter {:?}", f))))
+    };
+    vec![("capture binary", binary), ("capture permission", capture), ("terminal", terminal), ("config", config_check)]
+}
+
+// Prints the report; the exit status is 0 only when every check passed
+pub fn report(results: &[(&'static str, Outcome)]) -> i32 {
+    for (name, outcome) in results {
+        match outcome {
+            Outcome::Pass(detail) => println!("PASS  {:<18}  {}", name, detail),
+            Outcome::Fail(detail) => println!("FAIL  {:<18}  {}", name, detail),
+        }
+    }
+    if results.iter().all(|(_, o)| matches!(o, Outcome::Pass(_))) { 0 } else { 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Answers every check from its fields instead of touching the system
+    struct Fake {
+        binary: Option<io::ErrorKind>,
+        capture: Result<(), String>,
+    }
+
+    impl Probe for Fake {
+        fn binary(&self, _path: &Path) -> io::Result<bool> {
+            self.binary.map_or(Ok(true), |kind| Err(kind.into()))
+        }
+
+        fn capture_one(&self, _config: &Config, _interface: &str) -> Result<(), String> {
+            self.capture.clone()
+        }
+
+        fn terminal(&self) -> Result<String, String> {
+            Ok("xterm-256color, 120x40".to_string())
+        }
+    }
+
+    fn outcome<'a>(results: &'a [(&'static str, Outcome)], check: &str) -> &'a Outcome {
+        &results.iter().find(|(name, _)| *name == check).unwrap().1
+    }
+
+    #[test]
+    fn missing_binary_and_denied_capture_fail_differently() {
+        let config = Config::default();
+        let missing = run(&Fake { binary: Some(io::ErrorKind::NotFound), capture: Ok(()) }, &config, None);
+        assert_eq!(outcome(&missing, "capture binary"), &Outcome::Fail(format!("{} not found; is Wireshark's dumpcap installed?", network::DUMPCAP)));
+        assert_eq!(outcome(&missing, "capture permission"), &Outcome::Fail("skipped: no usable capture binary".to_string()));
+        assert_eq!(report(&missing), 1);
+
+        let denied = run(&Fake { binary: None, capture: Err("You don't have permission to capture on that device".to_string()) }, &config, None);
+        assert_eq!(outcome(&denied, "capture binary"), &Outcome::Pass(network::DUMPCAP.to_string()));
+        assert_eq!(outcome(&denied, "capture permission"), &Outcome::Fail("permission denied on any: You don't have permission to capture on that device".to_string()));
+        assert_eq!(report(&denied), 1);
+
+        let healthy = run(&Fake { binary: None, capture: Ok(()) }, &config, None);
+        assert_eq!(outcome(&healthy, "config"), &Outcome::Pass("1 interface(s), no capture filter".to_string()));
+        assert_eq!(report(&healthy), 0);
+    }
+
+    #[test]
+    fn bad_config_fails_its_check() {
+        let probe = Fake { binary: None, capture: Ok(()) };
+        let config = Config::parse("# comment\nfps = fast\ncolor.http = red\nnonsense\n");
+        let results = run(&probe, &config, None);
+        assert_eq!(outcome(&results, "config"), &Outcome::Fail("ignored line 2: fps = fast; line 4: nonsense".to_string()));
+        assert_eq!(report(&results), 1);
+
+        let results = run(&probe, &Config::default(), Some("invalid --fps: 0"));
+        assert_eq!(outcome(&results, "config"), &Outcome::Fail("invalid --fps: 0".to_string()));
+    }
+}
@@ -0,0 +1,131 @@
+// Builders for the packets and captures the unit tests feed through the parsers
+use crate::decode::LinkInfo;
+use crate::network::{Clock, PacketUpdate};
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+pub const SYN: u8 = 0x02;
+pub const RST: u8 = 0x04;
+pub const ACK: u8 = 0x10;
+
+// An IPv4 packet around `transport`, with a valid header checksum
+pub fn ipv4(protocol: u8, src: [u8; 4], dst: [u8; 4], transport: &[u8]) -> Vec<u8> {
+    let total = (20 + transport.len()) as u16;
+    let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, protocol, 0, 0];
+    packet[2..4].copy_from_slice(&total.to_be_bytes());
+    packet.extend_from_slice(&src);
+    packet.extend_from_slice(&dst);
+    let sum = packet.chunks(2).map(|w| u16::from_be_bytes([w[0], w[1]]) as u32).sum::<u32>();
+    let sum = (sum & 0xffff) + (sum >> 16);
+    packet[10..12].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+    packet.extend_from_slice(transport);
+    packet
+}
+
+// One direction of a TCP connection; `packet` stamps out segments from it
+#[derive(Clone, Copy)]
+pub struct Tcp {
+    pub src: [u8; 4],
+    pub sport: u16,
+    pub dst: [u8; 4],
+    pub dport: u16,
+    pub flags: u8,
+    pub seq: u32,
+    pub ack: u32,
+    pub window: u16,
+}
+
+impl Tcp {
+    pub fn new(src: [u8; 4], sport: u16, dst: [u8; 4], dport: u16) -> Tcp {
+        Tcp { src, sport, dst, dport, flags: ACK, seq: 1, ack: 1, window: 65535 }
+    }
+
+    pub fn flags(self, flags: u8) -> Tcp {
+        Tcp { flags, ..self }
+    }
+
+    pub fn packet(&self, payload: &[u8]) -> Vec<u8> {
+        let mut segment = Vec::with_capacity(20 + payload.len());
+        segment.extend_from_slice(&self.sport.to_be_bytes());
+        segment.extend_from_slice(&self.dport.to_be_bytes());
+        segment.extend_from_slice(&self.seq.to_be_bytes());
+        segment.extend_from_slice(&self.ack.to_be_bytes());
+        segment.extend_from_slice(&[0x50, self.flags]);
+        segment.extend_from_slice(&self.window.to_be_bytes());
+        segment.extend_from_slice(&[0, 0, 0, 0]); // checksum, urgent pointer
+        segment.extend_from_slice(payload);
+        ipv4(6, self.src, self.dst, &segment)
+    }
+}
+
+pub fn udp(src: [u8; 4], sport: u16, dst: [u8; 4], dport: u16, payload: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(8 + payload.len());
+    datagram.extend_from_slice(&sport.to_be_bytes());
+    datagram.extend_from_slice(&dport.to_be_bytes());
+    datagram.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+    datagram.extend_from_slice(&[0, 0]);
+    datagram.extend_from_slice(payload);
+    ipv4(17, src, dst, &datagram)
+}
+
+pub fn ethernet(src: [u8; 6], dst: [u8; 6], ip: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(14 + ip.len());
+    frame.extend_from_slice(&dst);
+    frame.extend_from_slice(&src);
+    frame.extend_from_slice(&[0x08, 0x00]);
+    frame.extend_from_slice(ip);
+    frame
+}
+
+// A packet as the reader would deliver it, untagged, `secs` into the epoch
+pub fn update(raw: Vec<u8>, secs: u64) -> PacketUpdate {
+    let src = Ipv4Addr::new(raw[12], raw[13], raw[14], raw[15]);
+    let dst = Ipv4Addr::new(raw[16], raw[17], raw[18], raw[19]);
+    let len = raw.len() as u32;
+    PacketUpdate {
+        summary: format!("{} ➔ {}", src, dst),
+        tag: None,
+        labels: Vec::new(),
+        raw_data: raw,
+        cap_len: len,
+        orig_len: len,
+        ts: Duration::from_secs(secs),
+        link: LinkInfo::Other,
+        frame: 0,
+        interface: None,
+        dup_ack: None,
+    }
+}
+
+// A clock that only moves when slept on
+#[derive(Default)]
+pub struct FakeClock(pub Duration);
+
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        self.0
+    }
+
+    fn sleep(&mut self, d: Duration) {
+        self.0 += d;
+    }
+}
+
+// A classic little-endian, microsecond pcap holding `records` whole
+pub fn pcap(linktype: u32, records: &[(Duration, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&4u16.to_le_bytes());
+    out.extend_from_slice(&[0; 8]);
+    out.extend_from_slice(&262_144u32.to_le_bytes());
+    out.extend_from_slice(&linktype.to_le_bytes());
+    for (ts, data) in records {
+        out.extend_from_slice(&(ts.as_secs() as u32).to_le_bytes());
+        out.extend_from_slice(&ts.subsec_micros().to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+    out
+}
@@ -0,0 +1,48 @@
+// Payload bytes to display text. Everything that renders captured bytes goes through here,
+// so invalid UTF-8 and control characters (which could move the cursor or recolour the
+// terminal) are replaced the same way everywhere.
+
+// The ASCII-pane rendering of a byte; anything unprintable becomes '.'
+pub fn printable(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }
+}
+
+// Short ASCII preview of a payload for the feed's optional preview column
+pub fn preview(payload: &[u8], width: usize) -> String {
+    payload.iter().take(width).map(|&b| printable(b)).collect()
+}
+
+// Text that may contain UTF-8: invalid sequences become U+FFFD and control characters '.'
+pub fn lossy(bytes: &[u8]) -> String {
+    sanitize(&String::from_utf8_lossy(bytes))
+}
+
+// As `lossy`, keeping line breaks and tabs for bodies shown over several lines
+pub fn lossy_multiline(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).chars().map(|c| if c.is_control() && !matches!(c, '\n' | '\r' | '\t') { '.' } else { c }).collect()
+}
+
+// Already-decoded text from the wire, with control characters replaced by '.'
+pub fn sanitize(text: &str) -> String {
+    text.chars().map(|c| if c.is_control() { '.' } else { c }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preview_dots_unprintable_bytes() {
+        assert_eq!(preview(b"GET /\r\n\x00\x1b[2J\xff", 64), "GET /....[2J.");
+        assert_eq!(preview(b"HTTP/1.1 200 OK", 8), "HTTP/1.1");
+        assert_eq!(preview(b"", 8), "");
+    }
+
+    #[test]
+    fn invalid_utf8_and_controls_are_made_safe() {
+        assert_eq!(lossy(b"caf\xc3\xa9 \xff\xfe ok"), "café \u{fffd}\u{fffd} ok");
+        assert_eq!(lossy(b"\x1b]0;pwned\x07\x1b[31mred"), ".]0;pwned..[31mred");
+        assert_eq!(lossy_multiline(b"a\r\n\tb\x00\xc3"), "a\r\n\tb.\u{fffd}");
+        assert_eq!(sanitize("host\u{9b}2J\u{7f}"), "host.2J.");
+    }
+}
@@ -0,0 +1,644 @@
+use crate::detect::Severity;
+use crate::model::{self, Conversation, FlowDirection, FlowKey};
+use crate::network::{PacketFields, PacketUpdate};
+use crate::text;
+use crossterm::event::KeyCode;
+use ratatui::style::{Color, Modifier, Style};
+use std::collections::{HashMap, VecDeque};
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+// How long a first `c` stays armed waiting for the confirming second press
+pub const CLEAR_CONFIRM_WINDOW: Duration = Duration::from_secs(3);
+
+// Most capture events handled between two frames; the rest wait in the channel, so a flood
+// can't starve drawing and input, and the backlog shows up as lag instead
+pub const MAX_EVENTS_PER_FRAME: usize = 20_000;
+
+// How long a notice stays in the banner row
+pub const NOTICE_DURATION: Duration = Duration::from_secs(4);
+
+// Below this many rows the split layout's panes get too small to read
+pub const COMPACT_HEIGHT: u16 = 20;
+
+// Which top-level layout the frame is drawn with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LayoutMode {
+    #[default]
+    Split,
+    FullHex, // The inspected packet's hex dump takes over the whole terminal
+    Compact, // Feed only, with search and the inspector as overlays; chosen by terminal height, never toggled
+}
+
+impl LayoutMode {
+    pub fn toggle_full_hex(self) -> Self {
+        match self {
+            LayoutMode::Split | LayoutMode::Compact => LayoutMode::FullHex,
+            LayoutMode::FullHex => LayoutMode::Split,
+        }
+    }
+
+    // The layout actually drawn for a terminal `height` rows tall
+    pub fn for_height(self, height: u16) -> Self {
+        match self {
+            LayoutMode::Split if height < COMPACT_HEIGHT => LayoutMode::Compact,
+            mode => mode,
+        }
+    }
+
+    // Esc always backs out to the split layout
+    pub fn escape(self) -> Self {
+        LayoutMode::Split
+    }
+}
+
+// Sidebar title with the busiest protocols, e.g. " Streams (HTTPS:42 DNS:15) ", trimmed to fit `width`
+pub fn streams_title(tag_totals: &HashMap<String, u64>, width: usize) -> String {
+    let mut ranked: Vec<(&String, &u64)> = tag_totals.iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    let mut badges: Vec<String> = Vec::new();
+    for (proto, count) in ranked {
+        badges.push(format!("{}:{}", proto, count));
+        if format!(" Streams ({}) ", badges.join(" ")).chars().count() > width {
+            badges.pop();
+            break;
+        }
+    }
+    if badges.is_empty() {
+        String::from(" Streams ")
+    } else {
+        format!(" Streams ({}) ", badges.join(" "))
+    }
+}
+
+// Compares records the capture has read against events the UI has consumed over the last
+// second of ticks; the UI is lagging when it handles well under what arrives and a backlog builds
+#[derive(Default)]
+pub struct LagMonitor {
+    samples: VecDeque<(u64, u64)>, // (captured, consumed) totals, one per tick
+}
+
+impl LagMonitor {
+    const TICKS: usize = 5;                // 200ms ticks: one second of history
+    const MIN_RATE: u64 = 100;             // Records per second below which lag doesn't matter
+    const MIN_RATIO: f64 = 0.9;            // Consumed / captured
+
+    pub fn observe(&mut self, captured: u64, consumed: u64) {
+        self.samples.push_back((captured, consumed));
+        if self.samples.len() > Self::TICKS + 1 {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn backlog(&self) -> u64 {
+        self.samples.back().map_or(0, |&(captured, consumed)| captured.saturating_sub(consumed))
+    }
+
+    pub fn is_lagging(&self) -> bool {
+        let (Some(&(c0, p0)), Some(&(c1, p1))) = (self.samples.front(), self.samples.back()) else { return false };
+        let captured = c1.saturating_sub(c0);
+        let consumed = p1.saturating_sub(p0);
+        self.samples.len() > Self::TICKS && captured >= Self::MIN_RATE && self.backlog() > 0
+            && (consumed as f64) < captured as f64 * Self::MIN_RATIO
+    }
+
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+// Which byte count the sidebar shows; toggled with `S`. The two only differ under a snaplen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteMode {
+    #[default]
+    Wire,     // Original frame lengths (pcap orig_len)
+    Captured, // Bytes actually kept (pcap caplen)
+}
+
+impl ByteMode {
+    pub fn toggle(self) -> Self {
+        match self {
+            ByteMode::Wire => ByteMode::Captured,
+            ByteMode::Captured => ByteMode::Wire,
+        }
+    }
+
+    pub fn bytes(self, conversation: &Conversation) -> u64 {
+        match self {
+            ByteMode::Wire => conversation.bytes,
+            ByteMode::Captured => conversation.captured_bytes,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ByteMode::Wire => "wire",
+            ByteMode::Captured => "captured",
+        }
+    }
+}
+
+// 1000-based, one decimal once past a kilobyte: "512 B", "3.4 kB", "12.0 MB"
+pub fn format_bytes(bytes: u64) -> String {
+    let units = ["kB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < units.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, units[unit])
+}
+
+// Inline sparkline for a sidebar entry, one glyph per second scaled to the busiest; idle seconds are blank.
+// Only the newest `width` seconds are drawn, so a narrow sidebar loses history rather than overflowing.
+pub fn activity_sparkline(counts: &[u64], width: usize) -> String {
+    const GLYPHS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let counts = &counts[counts.len().saturating_sub(width)..];
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    counts.iter().map(|&n| if n == 0 { ' ' } else { GLYPHS[((n * GLYPHS.len() as u64).div_ceil(max) - 1) as usize] }).collect()
+}
+
+// Fuzzy-finder entries: each stream key with its first packet's ports, so "443" finds HTTPS flows.
+// Sorted by key, so the list doesn't reshuffle between frames.
+pub fn finder_candidates(conversations: &HashMap<String, Conversation>) -> Vec<(String, String)> {
+    let mut candidates: Vec<(String, String)> = conversations.iter().map(|(key, c)| {
+        let text = match c.origin.filter(|o| matches!(o.protocol, 6 | 17)) {
+            Some(o) => format!("{} :{} :{}", key, o.src.1, o.dst.1),
+            None => key.clone(),
+        };
+        (key.clone(), text)
+    }).collect();
+    candidates.sort();
+    candidates
+}
+
+// Sidebar footer, so it's clear how much the display filter and `h` are hiding
+pub fn conversations_label(total: usize, shown: usize) -> String {
+    format!(" {} conversation{} ({} shown) ", total, if total == 1 { "" } else { "s" }, shown)
+}
+
+pub fn feed_title(shown: usize, total: usize, color_mode: ColorMode) -> String {
+    let mut title = format!(" Feed ── {} packets shown / {} total ", shown, total);
+    if color_mode == ColorMode::Flow {
+        title.push_str("── colour by flow ");
+    }
+    title
+}
+
+// Sizes the rolling "last N" view steps through with `[` and `]`
+pub const WINDOW_STEPS: [Duration; 5] = [Duration::from_secs(30), Duration::from_secs(60), Duration::from_secs(300), Duration::from_secs(900), Duration::from_secs(3600)];
+
+// The next step up (`grow`) or down from `window`, stopping at either end
+pub fn step_window(window: Duration, grow: bool) -> Duration {
+    if grow {
+        WINDOW_STEPS.iter().copied().find(|&w| w > window).unwrap_or(window)
+    } else {
+        WINDOW_STEPS.iter().copied().rev().find(|&w| w < window).unwrap_or(window)
+    }
+}
+
+pub fn window_label(window: Duration) -> String {
+    let secs = window.as_secs();
+    if secs >= 60 && secs.is_multiple_of(60) { format!("last {}m", secs / 60) } else { format!("last {}s", secs) }
+}
+
+// How the feed's time column is rendered; cycled with `t`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeFormat {
+    #[default]
+    Relative, // +1.234s since the first captured packet
+    Absolute, // 14:22:05.123 wall clock (UTC)
+    Delta,    // +0.004s since the previous packet shown
+}
+
+impl TimeFormat {
+    pub fn next(self) -> Self {
+        match self {
+            TimeFormat::Relative => TimeFormat::Absolute,
+            TimeFormat::Absolute => TimeFormat::Delta,
+            TimeFormat::Delta => TimeFormat::Relative,
+        }
+    }
+
+    pub fn format(self, ts: Duration, capture_start: Duration, previous: Option<Duration>) -> String {
+        match self {
+            TimeFormat::Relative => format!("+{:.3}s", ts.saturating_sub(capture_start).as_secs_f64()),
+            TimeFormat::Delta => format!("+{:.3}s", ts.saturating_sub(previous.unwrap_or(ts)).as_secs_f64()),
+            TimeFormat::Absolute => {
+                let secs = ts.as_secs() % 86_400;
+                format!("{:02}:{:02}:{:02}.{:03}", secs / 3600, secs / 60 % 60, secs % 60, ts.subsec_millis())
+            }
+        }
+    }
+}
+
+// Event-poll timeout for the main loop: one frame at `fps` while packets are arriving,
+// backing off to 4x that (capped at the 200ms tick) once the capture goes quiet
+pub fn poll_interval(fps: u32, recent_packets: u64) -> Duration {
+    let frame = Duration::from_secs(1) / fps.max(1);
+    if recent_packets > 0 {
+        frame
+    } else {
+        (frame * 4).min(Duration::from_millis(200)).max(frame)
+    }
+}
+
+// Single-line text field with an editable cursor, used by the search prompts
+#[derive(Clone, Debug, Default)]
+pub struct TextInput {
+    text: String,
+    cursor: usize, // In chars, 0..=len
+}
+
+impl TextInput {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    // Replaces the text and parks the cursor at the end
+    pub fn set(&mut self, text: String) {
+        self.cursor = text.chars().count();
+        self.text = text;
+    }
+
+    pub fn clear(&mut self) {
+        self.set(String::new());
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.text.char_indices().nth(char_index).map_or(self.text.len(), |(i, _)| i)
+    }
+
+    // Applies an editing key; returns false for keys it doesn't handle
+    pub fn handle(&mut self, code: KeyCode) -> bool {
+        let len = self.text.chars().count();
+        match code {
+            KeyCode::Char(c) => {
+                self.text.insert(self.byte_index(self.cursor), c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.byte_index(self.cursor));
+            }
+            KeyCode::Delete if self.cursor < len => { self.text.remove(self.byte_index(self.cursor)); }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(len),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = len,
+            KeyCode::Backspace | KeyCode::Delete => {}
+            _ => return false,
+        }
+        true
+    }
+}
+
+// What the feed's line colour encodes; toggled with `C`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    #[default]
+    Protocol,
+    Flow, // Each TCP/UDP flow keeps one colour, so it can be followed through interleaved traffic
+}
+
+impl ColorMode {
+    pub fn toggle(self) -> Self {
+        match self {
+            ColorMode::Protocol => ColorMode::Flow,
+            ColorMode::Flow => ColorMode::Protocol,
+        }
+    }
+}
+
+// Bright, mutually distinct colours that stay readable on dark and light backgrounds
+pub const FLOW_PALETTE: [Color; 10] = [
+    Color::LightRed, Color::LightGreen, Color::LightYellow, Color::LightMagenta, Color::LightCyan,
+    Color::Rgb(255, 165, 0), Color::Rgb(135, 175, 255), Color::Rgb(255, 135, 215), Color::Rgb(175, 215, 95), Color::Rgb(215, 175, 255),
+];
+
+pub fn flow_palette_index(key: &FlowKey) -> usize {
+    (key.stable_hash() % FLOW_PALETTE.len() as u64) as usize
+}
+
+// Feed colour for a selected conversation's packets, so requests and responses alternate visibly
+pub fn direction_color(direction: FlowDirection) -> Color {
+    match direction {
+        FlowDirection::Outbound => Color::LightGreen,
+        FlowDirection::Inbound => Color::LightBlue,
+    }
+}
+
+// Sidebar label naming who serves whom, e.g. "client 10.0.0.5 → server 140.82.112.3:443";
+// None for traffic without ports, where there's no service to infer
+pub fn roles_label(conversation: &Conversation) -> Option<String> {
+    let origin = conversation.origin.filter(|o| matches!(o.protocol, 6 | 17))?;
+    let (client, server) = origin.roles();
+    Some(format!("client {} → server {}:{}", client.0, server.0, server.1))
+}
+
+// A selected conversation's summary with the local host always on the left: "local ⇒ remote" for
+// outbound packets, "local ⇐ remote" for inbound, keeping the "[tag]". Unchanged when there's no IP header.
+pub fn oriented_summary(summary: &str, packet: &[u8]) -> String {
+    let (Some(direction), Some(ip)) = (FlowDirection::of(packet), packet.get(..20)) else { return summary.to_string() };
+    let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+    let dst = Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]);
+    let tag = &summary[model::stream_key(summary).len()..];
+    match direction {
+        FlowDirection::Outbound => format!("{} ⇒ {}{}", src, dst, tag),
+        FlowDirection::Inbound => format!("{} ⇐ {}{}", dst, src, tag),
+    }
+}
+
+// `V`: the legacy summary over its structured rendering, marked with whether the two agree
+pub fn parity_lines(pkt: &PacketUpdate) -> String {
+    match PacketFields::of(pkt) {
+        Some(fields) => {
+            let verdict = if fields.summary() == pkt.summary { "✓ same" } else { "≠ differs" };
+            format!("SUM {}\nFLD {}  {}", pkt.summary, fields.render(), verdict)
+        }
+        None => format!("SUM {}\nFLD —", pkt.summary),
+    }
+}
+
+// Bytes per hex-dump line: narrow for split panes, wide for big terminals
+pub const HEX_WIDTHS: [usize; 3] = [8, 16, 32];
+
+pub fn next_hex_width(width: usize) -> usize {
+    let i = HEX_WIDTHS.iter().position(|&w| w == width).map_or(0, |i| i + 1);
+    HEX_WIDTHS[i % HEX_WIDTHS.len()]
+}
+
+// Byte cursor over the full-screen hex dump; shift+arrows drag a selection from `anchor`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ByteSelection {
+    cursor: usize,
+    anchor: Option<usize>,
+}
+
+impl ByteSelection {
+    // Moves the cursor by `delta` bytes within a packet of `len` bytes, extending the selection when `extend` is set
+    pub fn move_by(&mut self, delta: isize, len: usize, extend: bool) {
+        let cursor = self.cursor.min(len.saturating_sub(1));
+        if extend {
+            self.anchor.get_or_insert(cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = cursor.saturating_add_signed(delta).min(len.saturating_sub(1));
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    // Selected byte range, clamped to a packet of `len` bytes; just the cursor byte when nothing is dragged
+    pub fn range(&self, len: usize) -> std::ops::Range<usize> {
+        let anchor = self.anchor.unwrap_or(self.cursor);
+        let start = self.cursor.min(anchor).min(len);
+        let end = (self.cursor.max(anchor) + 1).min(len);
+        start..end
+    }
+}
+
+// Offsets at which two packets differ, aligned from the first byte; the longer one's tail counts as different
+pub fn diff_offsets(a: &[u8], b: &[u8]) -> Vec<usize> {
+    (0..a.len().max(b.len())).filter(|&i| a.get(i) != b.get(i)).collect()
+}
+
+// Readout for a hex selection: its leading bytes as unsigned integers in both byte orders, then as ASCII
+pub fn interpret_bytes(bytes: &[u8]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for width in [1, 2, 4, 8] {
+        let Some(chunk) = bytes.get(..width) else {
+            lines.push(format!("u{:<3} —", width * 8));
+            continue;
+        };
+        let be = chunk.iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+        let le = chunk.iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64);
+        if width == 1 {
+            lines.push(format!("u8   {} (0x{:02x})", be, be));
+        } else {
+            lines.push(format!("u{:<3} BE {} (0x{:0w$x})  LE {} (0x{:0w$x})", width * 8, be, be, le, le, w = width * 2));
+        }
+    }
+    lines.push(format!("ASCII {}", bytes.iter().map(|&b| text::printable(b)).collect::<String>()));
+    lines
+}
+
+pub fn severity_style(severity: Severity) -> Style {
+    match severity {
+        Severity::Info => Style::default().fg(Color::Cyan),
+        Severity::Warning => Style::default().fg(Color::Yellow),
+        Severity::Critical => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TimeRange;
+    use crate::network;
+    use crate::testutil::{self, Tcp};
+    use std::sync::mpsc;
+
+    #[test]
+    fn lag_warns_only_on_a_processing_deficit() {
+        // 1000 records a second, all consumed each tick
+        let mut balanced = LagMonitor::default();
+        for tick in 0..=5 {
+            balanced.observe(tick * 200, tick * 200);
+        }
+        assert!(!balanced.is_lagging());
+
+        // Same arrival rate, only half handled: the backlog grows and the warning shows
+        let mut behind = LagMonitor::default();
+        for tick in 0..=5 {
+            behind.observe(tick * 200, tick * 100);
+            assert_eq!(behind.is_lagging(), tick == 5);
+        }
+        assert_eq!(behind.backlog(), 500);
+
+        // A slow trickle never warns, however far behind
+        let mut quiet = LagMonitor::default();
+        for tick in 0..=5 {
+            quiet.observe(tick * 10, 0);
+        }
+        assert!(!quiet.is_lagging());
+        behind.reset();
+        assert!(!behind.is_lagging());
+    }
+
+    #[test]
+    fn wire_and_captured_totals_differ_by_what_snaplen_cut() {
+        let flow = Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 80);
+        let mut cut = testutil::update(flow.packet(&[0; 200]), 1);
+        cut.raw_data.truncate(96);
+        cut.cap_len = 96;
+        let whole = testutil::update(flow.packet(&[0; 10]), 2);
+        let mut pcap = Vec::new();
+        network::write_pcap(&mut pcap, [&cut, &whole]).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        network::read_pcap(pcap.as_slice(), &tx, &TimeRange::default(), network::LinkOptions::default());
+        let mut conversation = Conversation::new(Duration::ZERO);
+        for event in rx.try_iter() {
+            if let network::CaptureEvent::Packet(update) = event { conversation.push(update); }
+        }
+        assert_eq!(ByteMode::Wire.bytes(&conversation), 240 + 50);
+        assert_eq!(ByteMode::Captured.bytes(&conversation), 96 + 50);
+        assert_eq!(ByteMode::Wire.toggle(), ByteMode::Captured);
+    }
+
+    #[test]
+    fn layout_mode_transitions() {
+        let split = LayoutMode::default();
+        assert_eq!(split, LayoutMode::Split);
+        assert_eq!(split.toggle_full_hex(), LayoutMode::FullHex);
+        assert_eq!(split.toggle_full_hex().toggle_full_hex(), LayoutMode::Split);
+        assert_eq!(LayoutMode::Compact.toggle_full_hex(), LayoutMode::FullHex);
+        for mode in [LayoutMode::Split, LayoutMode::FullHex, LayoutMode::Compact] {
+            assert_eq!(mode.escape(), LayoutMode::Split);
+        }
+    }
+
+    #[test]
+    fn short_terminals_get_the_compact_layout() {
+        assert_eq!(LayoutMode::Split.for_height(COMPACT_HEIGHT - 1), LayoutMode::Compact);
+        assert_eq!(LayoutMode::Split.for_height(COMPACT_HEIGHT), LayoutMode::Split);
+        assert_eq!(LayoutMode::Split.for_height(50), LayoutMode::Split);
+        // Full-screen hex was asked for, so it wins at any height
+        assert_eq!(LayoutMode::FullHex.for_height(10), LayoutMode::FullHex);
+    }
+
+    #[test]
+    fn streams_title_fits_busiest_protocols() {
+        let totals: HashMap<String, u64> = [("HTTPS", 42), ("DNS", 15), ("SSH", 3)].into_iter().map(|(p, n)| (p.to_string(), n)).collect();
+        assert_eq!(streams_title(&totals, 80), " Streams (HTTPS:42 DNS:15 SSH:3) ");
+        assert_eq!(streams_title(&totals, 27), " Streams (HTTPS:42 DNS:15) ");
+        assert_eq!(streams_title(&totals, 10), " Streams ");
+        assert_eq!(streams_title(&HashMap::new(), 80), " Streams ");
+    }
+
+    #[test]
+    fn every_time_format_from_one_sequence() {
+        let start = Duration::from_millis(1_700_000_000_000); // 22:13:20 UTC
+        let times = [start, start + Duration::from_millis(4), start + Duration::from_millis(1234)];
+        let render = |format: TimeFormat| -> Vec<String> {
+            let mut previous = None;
+            times.iter().map(|&ts| { let s = format.format(ts, start, previous); previous = Some(ts); s }).collect()
+        };
+        assert_eq!(render(TimeFormat::Relative), ["+0.000s", "+0.004s", "+1.234s"]);
+        assert_eq!(render(TimeFormat::Absolute), ["22:13:20.000", "22:13:20.004", "22:13:21.234"]);
+        assert_eq!(render(TimeFormat::Delta), ["+0.000s", "+0.004s", "+1.230s"]);
+        assert_eq!(TimeFormat::default().next().next().next(), TimeFormat::Relative);
+    }
+
+    #[test]
+    fn poll_interval_backs_off_when_quiet() {
+        assert_eq!(poll_interval(30, 500), Duration::from_secs(1) / 30);
+        assert_eq!(poll_interval(30, 0), Duration::from_secs(1) / 30 * 4);
+        assert_eq!(poll_interval(10, 0), Duration::from_millis(200)); // Capped at the tick
+        assert_eq!(poll_interval(2, 0), Duration::from_millis(500)); // Never faster than a frame
+        assert_eq!(poll_interval(0, 1), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn text_input_edits_mid_string() {
+        let mut input = TextInput::default();
+        input.set("192.168.1".to_string());
+        for code in [KeyCode::Left, KeyCode::Left, KeyCode::Char('0')] {
+            assert!(input.handle(code));
+        }
+        assert_eq!((input.as_str(), input.cursor()), ("192.1680.1", 8));
+        input.handle(KeyCode::Backspace);
+        assert_eq!((input.as_str(), input.cursor()), ("192.168.1", 7));
+        input.handle(KeyCode::Home);
+        input.handle(KeyCode::Delete);
+        input.handle(KeyCode::Char('➔'));
+        assert_eq!((input.as_str(), input.cursor()), ("➔92.168.1", 1));
+        input.handle(KeyCode::Delete);
+        assert_eq!(input.as_str(), "➔2.168.1");
+        assert!(!input.handle(KeyCode::Enter));
+    }
+
+    #[test]
+    fn flow_colour_is_stable_and_direction_independent() {
+        let (client, server) = ((Ipv4Addr::new(10, 0, 0, 1), 40000), (Ipv4Addr::new(93, 184, 216, 34), 443));
+        let key = FlowKey::new(6, client, server);
+        // Pinned, so a change to the hash (and every flow's colour between runs) is caught
+        assert_eq!(key.stable_hash(), 0xc852_4e03_ea7b_a2af);
+        assert_eq!(flow_palette_index(&key), 7);
+        assert_eq!(flow_palette_index(&FlowKey::new(6, server, client)), 7);
+    }
+
+    #[test]
+    fn four_byte_selection_reads_both_byte_orders() {
+        assert_eq!(interpret_bytes(&[0x00, 0x00, 0x01, 0xbb]), [
+            "u8   0 (0x00)",
+            "u16  BE 0 (0x0000)  LE 0 (0x0000)",
+            "u32  BE 443 (0x000001bb)  LE 3137404928 (0xbb010000)",
+            "u64  —",
+            "ASCII ....",
+        ]);
+    }
+
+    #[test]
+    fn packets_are_coloured_by_direction() {
+        let colour = |packet: Vec<u8>| FlowDirection::of(&packet).map(direction_color);
+        let request = Tcp::new([192, 168, 1, 5], 40000, [140, 82, 112, 3], 443);
+        let response = Tcp::new([140, 82, 112, 3], 443, [192, 168, 1, 5], 40000);
+        assert_eq!(colour(request.packet(b"")), Some(Color::LightGreen));
+        assert_eq!(colour(response.packet(b"")), Some(Color::LightBlue));
+
+        // Both ends local: the lower endpoint counts as ours, whichever spoke first
+        let upper = Tcp::new([10, 0, 0, 9], 22, [10, 0, 0, 1], 50000);
+        assert_eq!(colour(upper.packet(b"")), Some(Color::LightBlue));
+        assert_eq!(colour(Tcp::new(upper.dst, upper.dport, upper.src, upper.sport).packet(b"")), Some(Color::LightGreen));
+        assert_eq!(colour(vec![0x45; 12]), None);
+    }
+
+    #[test]
+    fn oriented_summary_keeps_the_local_host_left() {
+        let request = Tcp::new([192, 168, 1, 5], 40000, [140, 82, 112, 3], 443).packet(b"");
+        let response = Tcp::new([140, 82, 112, 3], 443, [192, 168, 1, 5], 40000).packet(b"");
+        assert_eq!(oriented_summary("192.168.1.5 ➔ 140.82.112.3 [HTTPS]", &request), "192.168.1.5 ⇒ 140.82.112.3 [HTTPS]");
+        assert_eq!(oriented_summary("140.82.112.3 ➔ 192.168.1.5 [HTTPS]", &response), "192.168.1.5 ⇐ 140.82.112.3 [HTTPS]");
+        assert_eq!(oriented_summary("140.82.112.3 ➔ 192.168.1.5", &response), "192.168.1.5 ⇐ 140.82.112.3");
+        assert_eq!(oriented_summary("garbled", &[0x45; 8]), "garbled");
+    }
+
+    #[test]
+    fn sparkline_glyphs_for_a_known_pattern() {
+        let mut activity = model::Activity::default();
+        for (second, packets) in [(10, 1), (12, 4), (13, 8), (14, 2)] {
+            for _ in 0..packets {
+                activity.record(Duration::from_secs(second));
+            }
+        }
+        let counts = activity.recent(Duration::from_secs(14));
+        assert_eq!(counts, [0, 0, 0, 1, 0, 4, 8, 2]);
+        assert_eq!(activity_sparkline(&counts, 8), "   ▁ ▄█▂");
+        assert_eq!(activity_sparkline(&counts, 3), "▄█▂");
+        // Scaled to the busiest second still shown
+        assert_eq!(activity_sparkline(&counts, 1), "█");
+        assert_eq!(activity_sparkline(&activity.recent(Duration::from_secs(30)), 8), "        ");
+    }
+
+    #[test]
+    fn parity_view_flags_a_summary_out_of_step() {
+        let mut pkt = testutil::update(Tcp::new([10, 0, 0, 1], 40000, [10, 0, 0, 2], 443).packet(b""), 1);
+        pkt.retag("HTTPS".to_string());
+        assert_eq!(parity_lines(&pkt), "SUM 10.0.0.1 ➔ 10.0.0.2 [HTTPS]\nFLD TCP 10.0.0.1:40000 → 10.0.0.2:443 · HTTPS  ✓ same");
+        pkt.retag("SSH".to_string());
+        assert_eq!(parity_lines(&pkt), "SUM 10.0.0.1 ➔ 10.0.0.2 [SSH]\nFLD TCP 10.0.0.1:40000 → 10.0.0.2:443 · HTTPS  ≠ differs");
+    }
+}